@@ -5,7 +5,7 @@ use glium::{glutin, Surface};
 use nalgebra as na;
 
 use rendology::{
-    basic_obj, BasicObj, Instancing, InstancingMode, Light, Mesh, RenderList, ShadedScenePass,
+    basic_obj, BasicObj, Instancing, Light, Material, Mesh, RenderList, ShadedScenePass,
     ShadedScenePassSetup, ShadowPass,
 };
 
@@ -34,21 +34,25 @@ impl Pipeline {
     ) -> Result<Self, rendology::pipeline::CreationError> {
         let rendology = rendology::Pipeline::create(facade, config, WINDOW_SIZE)?;
 
+        let cube = BasicObj::Cube.create_mesh(facade)?;
+        let cube_instancing = Instancing::create(facade)?;
+
+        // `Instancing` falls back to uniforms if instancing is not supported,
+        // so we build our passes with whatever mode it ended up with.
         let shadow_pass =
-            rendology.create_shadow_pass(facade, basic_obj::Core, InstancingMode::Vertex)?;
+            rendology.create_shadow_pass(facade, basic_obj::Core, cube_instancing.mode())?;
         let scene_pass = rendology.create_shaded_scene_pass(
             facade,
             basic_obj::Core,
-            InstancingMode::Vertex,
+            cube_instancing.mode(),
             ShadedScenePassSetup {
                 draw_shadowed: true,
                 draw_glowing: false,
+                linearize_colors: false,
+                two_sided_lighting: false,
             },
         )?;
 
-        let cube = BasicObj::Cube.create_mesh(facade)?;
-        let cube_instancing = Instancing::create(facade)?;
-
         Ok(Pipeline {
             rendology,
             shadow_pass,
@@ -74,7 +78,7 @@ impl Pipeline {
         };
 
         self.rendology
-            .start_frame(facade, (0.0, 0.0, 0.0), context.clone(), target)?
+            .start_frame(facade, context.clone(), target)?
             .shadow_pass()
             .draw(
                 &self.shadow_pass,
@@ -145,11 +149,18 @@ fn scene(time: f32) -> Scene {
         transform: na::Matrix4::new_translation(&na::Vector3::new(0.0, 0.0, 3.0))
             * na::Matrix4::from_euler_angles(time, time, time),
         color: na::Vector4::new(0.9, 0.9, 0.9, 1.0),
+        material: Material {
+            specular: na::Vector3::new(0.5, 0.5, 0.5),
+            shininess: 32.0,
+            ..Default::default()
+        },
+        ..Default::default()
     });
 
     scene.cubes.add(basic_obj::Instance {
         transform: na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(10.0, 10.0, 0.1)),
         color: na::Vector4::new(0.0, 1.0, 0.0, 1.0),
+        ..Default::default()
     });
 
     scene.lights.push(Light {
@@ -170,13 +181,12 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
             &na::Point3::new(0.0, 0.0, 0.0),
             &na::Vector3::new(0.0, 0.0, 1.0),
         ),
-        projection: na::Perspective3::new(
-            target_size.0 as f32 / target_size.1 as f32,
-            60.0f32.to_radians(),
-            0.1,
-            1000.0,
-        )
-        .to_homogeneous(),
+        projection: rendology::Projection::Perspective {
+            fovy: 60.0f32.to_radians(),
+            aspect: target_size.0 as f32 / target_size.1 as f32,
+            near: 0.1,
+            far: 1000.0,
+        },
         viewport_size: na::Vector2::new(target_size.0 as f32, target_size.1 as f32),
     };
 
@@ -185,5 +195,11 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
         main_light_pos: na::Point3::new(10.0, 10.0, 10.0),
         main_light_center: na::Point3::new(0.0, 0.0, 0.0),
         ambient_light: na::Vector3::new(0.3, 0.3, 0.3),
+        elapsed_time: 0.0,
+        log_depth: false,
+        reverse_z: false,
+        fog: None,
+        camera_relative: false,
+        clear_color: Some((0.0, 0.0, 0.0, 1.0)),
     }
 }
@@ -117,6 +117,8 @@ impl Pipeline {
             ShadedScenePassSetup {
                 draw_shadowed: true,
                 draw_glowing: false,
+                linearize_colors: false,
+                two_sided_lighting: false,
             },
         )?;
         let glowing_scene_pass = rendology.create_shaded_scene_pass(
@@ -126,6 +128,8 @@ impl Pipeline {
             ShadedScenePassSetup {
                 draw_shadowed: true,
                 draw_glowing: true,
+                linearize_colors: false,
+                two_sided_lighting: false,
             },
         )?;
 
@@ -138,6 +142,8 @@ impl Pipeline {
             ShadedScenePassSetup {
                 draw_shadowed: true,
                 draw_glowing: false,
+                linearize_colors: false,
+                two_sided_lighting: false,
             },
         )?;
 
@@ -187,7 +193,7 @@ impl Pipeline {
         };
 
         self.rendology
-            .start_frame(facade, (0.0, 0.0, 0.0), context.clone(), target)?
+            .start_frame(facade, context.clone(), target)?
             .shadow_pass()
             .draw(
                 &self.shadow_pass,
@@ -244,7 +250,7 @@ fn main() {
         light_min_threshold: 0.0001,
     };
     let mut pipeline_config = rendology::Config {
-        hdr: Some(1.0),
+        hdr: Some(Default::default()),
         deferred_shading: Some(deferred_config.clone()),
         ..Default::default()
     };
@@ -277,6 +283,10 @@ fn main() {
                                     .deferred_shading
                                     .clone()
                                     .map_or(Some(deferred_config.clone()), |_| None);
+                                if pipeline_config.deferred_shading.is_none() {
+                                    // SSAO requires deferred shading
+                                    pipeline_config.ssao = None;
+                                }
                                 recreate_pipeline = true;
                             }
                             Some(VirtualKeyCode::F3) => {
@@ -286,6 +296,15 @@ fn main() {
                                     .map_or(Some(Default::default()), |_| None);
                                 recreate_pipeline = true;
                             }
+                            Some(VirtualKeyCode::F4)
+                                if pipeline_config.deferred_shading.is_some() =>
+                            {
+                                pipeline_config.ssao = pipeline_config
+                                    .ssao
+                                    .clone()
+                                    .map_or(Some(Default::default()), |_| None);
+                                recreate_pipeline = true;
+                            }
                             _ => (),
                         }
                     }
@@ -322,6 +341,7 @@ fn scene(time: f32) -> Scene {
     scene.cubes.add(basic_obj::Instance {
         transform: na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(10.0, 10.0, 0.1)),
         color: na::Vector4::new(0.9, 0.9, 0.9, 1.0),
+        ..Default::default()
     });
 
     let n = 10;
@@ -341,6 +361,7 @@ fn scene(time: f32) -> Scene {
         scene.glowing_cubes.add(basic_obj::Instance {
             transform: orbit_transform,
             color: color / 2.0,
+            ..Default::default()
         });
 
         scene.lights.push(Light {
@@ -375,13 +396,12 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
             &na::Point3::new(0.0, 0.0, 0.0),
             &na::Vector3::new(0.0, 0.0, 1.0),
         ),
-        projection: na::Perspective3::new(
-            target_size.0 as f32 / target_size.1 as f32,
-            60.0f32.to_radians(),
-            0.1,
-            1000.0,
-        )
-        .to_homogeneous(),
+        projection: rendology::Projection::Perspective {
+            fovy: 60.0f32.to_radians(),
+            aspect: target_size.0 as f32 / target_size.1 as f32,
+            near: 0.1,
+            far: 1000.0,
+        },
         viewport_size: na::Vector2::new(target_size.0 as f32, target_size.1 as f32),
     };
 
@@ -390,5 +410,11 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
         main_light_pos: na::Point3::new(1.0, 1.0, 10.0),
         main_light_center: na::Point3::new(0.0, 0.0, 0.0),
         ambient_light: na::Vector3::new(0.01, 0.01, 0.01),
+        elapsed_time: 0.0,
+        log_depth: false,
+        reverse_z: false,
+        fog: None,
+        camera_relative: false,
+        clear_color: Some((0.0, 0.0, 0.0, 1.0)),
     }
 }
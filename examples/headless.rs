@@ -0,0 +1,135 @@
+//! Renders a single frame without a window and writes it to `headless.png`.
+//!
+//! Since rendology is generic over the `Facade` and `Surface`, the same code
+//! works with a windowed `Display` and with a `HeadlessRenderer`. This can be
+//! used for comparing rendered images against reference images in CI.
+
+use glium::glutin;
+use nalgebra as na;
+
+use rendology::{basic_obj, BasicObj, Light, RenderList, RenderTarget, ShadedScenePassSetup};
+
+const IMAGE_SIZE: (u32, u32) = (640, 480);
+
+fn render_to_image_headless(width: u32, height: u32) -> image::RgbaImage {
+    let events_loop = glutin::EventsLoop::new();
+    let context = glutin::ContextBuilder::new()
+        .build_headless(
+            &events_loop,
+            glutin::dpi::PhysicalSize::new(width as f64, height as f64),
+        )
+        .unwrap();
+    let facade = glium::HeadlessRenderer::new(context).unwrap();
+
+    let mut pipeline =
+        rendology::Pipeline::create(&facade, &Default::default(), (width, height)).unwrap();
+    let cube = BasicObj::Cube.create_mesh(&facade).unwrap();
+    let shadow_pass = pipeline
+        .create_shadow_pass(
+            &facade,
+            basic_obj::Core,
+            rendology::InstancingMode::Uniforms,
+        )
+        .unwrap();
+    let scene_pass = pipeline
+        .create_shaded_scene_pass(
+            &facade,
+            basic_obj::Core,
+            rendology::InstancingMode::Uniforms,
+            ShadedScenePassSetup {
+                draw_shadowed: true,
+                draw_glowing: false,
+                linearize_colors: false,
+                two_sided_lighting: false,
+            },
+        )
+        .unwrap();
+
+    let mut cubes: RenderList<basic_obj::Instance> = RenderList::default();
+    cubes.add(basic_obj::Instance {
+        transform: na::Matrix4::new_translation(&na::Vector3::new(0.0, 0.0, 3.0)),
+        color: na::Vector4::new(0.9, 0.9, 0.9, 1.0),
+        ..Default::default()
+    });
+    cubes.add(basic_obj::Instance {
+        transform: na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(10.0, 10.0, 0.1)),
+        color: na::Vector4::new(0.0, 1.0, 0.0, 1.0),
+        ..Default::default()
+    });
+
+    let lights = vec![Light {
+        position: na::Point3::new(10.0, 10.0, 10.0),
+        attenuation: na::Vector3::new(1.0, 0.0, 0.0),
+        color: na::Vector3::new(1.0, 1.0, 1.0),
+        is_main: true,
+        ..Default::default()
+    }];
+
+    let draw_params = glium::DrawParameters {
+        backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
+        ..Default::default()
+    };
+
+    let render_target = RenderTarget::create(&facade, (width, height)).unwrap();
+    let mut target = render_target.as_surface(&facade).unwrap();
+
+    pipeline
+        .start_frame(&facade, render_context((width, height)), &mut target)
+        .unwrap()
+        .shadow_pass()
+        .draw(&shadow_pass, &cubes.as_drawable(&cube), &(), &draw_params)
+        .unwrap()
+        .shaded_scene_pass()
+        .draw(&scene_pass, &cubes.as_drawable(&cube), &(), &draw_params)
+        .unwrap()
+        .compose(&lights)
+        .unwrap()
+        .postprocess()
+        .unwrap()
+        .present()
+        .unwrap();
+
+    let raw = render_target.read_to_image();
+    let image = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned()).unwrap();
+
+    // OpenGL's origin is in the bottom left corner
+    image::imageops::flip_vertical(&image)
+}
+
+fn main() {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    render_to_image_headless(IMAGE_SIZE.0, IMAGE_SIZE.1)
+        .save("headless.png")
+        .unwrap();
+}
+
+fn render_context(target_size: (u32, u32)) -> rendology::Context {
+    let camera = rendology::Camera {
+        view: na::Matrix4::look_at_rh(
+            &na::Point3::new(9.0, -5.0, 7.0),
+            &na::Point3::new(0.0, 0.0, 0.0),
+            &na::Vector3::new(0.0, 0.0, 1.0),
+        ),
+        projection: rendology::Projection::Perspective {
+            fovy: 60.0f32.to_radians(),
+            aspect: target_size.0 as f32 / target_size.1 as f32,
+            near: 0.1,
+            far: 1000.0,
+        },
+        viewport_size: na::Vector2::new(target_size.0 as f32, target_size.1 as f32),
+    };
+
+    rendology::Context {
+        camera,
+        main_light_pos: na::Point3::new(10.0, 10.0, 10.0),
+        main_light_center: na::Point3::new(0.0, 0.0, 0.0),
+        ambient_light: na::Vector3::new(0.3, 0.3, 0.3),
+        elapsed_time: 0.0,
+        log_depth: false,
+        reverse_z: false,
+        fog: None,
+        camera_relative: false,
+        clear_color: Some((0.0, 0.0, 0.0, 1.0)),
+    }
+}
@@ -45,6 +45,8 @@ impl Pipeline {
             ShadedScenePassSetup {
                 draw_shadowed: true,
                 draw_glowing: false,
+                linearize_colors: false,
+                two_sided_lighting: false,
             },
         )?;
         let line_pass =
@@ -84,7 +86,7 @@ impl Pipeline {
         let line_params = line::Params { feather: 0.1 };
 
         self.rendology
-            .start_frame(facade, (1.0, 1.0, 1.0), context.clone(), target)?
+            .start_frame(facade, context.clone(), target)?
             .shadow_pass()
             .draw(
                 &self.shadow_pass,
@@ -162,11 +164,13 @@ fn scene(time: f32) -> Scene {
         transform: na::Matrix4::new_translation(&na::Vector3::new(0.0, 0.0, 3.0))
             * na::Matrix4::from_euler_angles(time, time, time),
         color: na::Vector4::new(0.9, 0.9, 0.9, 1.0),
+        ..Default::default()
     });
 
     scene.cubes.add(basic_obj::Instance {
         transform: na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(10.0, 10.0, 0.1)),
         color: na::Vector4::new(0.0, 1.0, 0.0, 1.0),
+        ..Default::default()
     });
 
     scene.lights.push(Light {
@@ -194,13 +198,12 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
             &na::Point3::new(0.0, 0.0, 0.0),
             &na::Vector3::new(0.0, 0.0, 1.0),
         ),
-        projection: na::Perspective3::new(
-            target_size.0 as f32 / target_size.1 as f32,
-            60.0f32.to_radians(),
-            0.1,
-            1000.0,
-        )
-        .to_homogeneous(),
+        projection: rendology::Projection::Perspective {
+            fovy: 60.0f32.to_radians(),
+            aspect: target_size.0 as f32 / target_size.1 as f32,
+            near: 0.1,
+            far: 1000.0,
+        },
         viewport_size: na::Vector2::new(target_size.0 as f32, target_size.1 as f32),
     };
 
@@ -209,5 +212,11 @@ fn render_context(target_size: (u32, u32)) -> rendology::Context {
         main_light_pos: na::Point3::new(10.0, 10.0, 10.0),
         main_light_center: na::Point3::new(0.0, 0.0, 0.0),
         ambient_light: na::Vector3::new(0.3, 0.3, 0.3),
+        elapsed_time: 0.0,
+        log_depth: false,
+        reverse_z: false,
+        fog: None,
+        camera_relative: false,
+        clear_color: Some((1.0, 1.0, 1.0, 1.0)),
     }
 }
@@ -0,0 +1,91 @@
+use nalgebra as na;
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: na::Point3<f32>,
+    pub max: na::Point3<f32>,
+}
+
+impl Aabb {
+    /// The smallest box that contains all of `points`.
+    ///
+    /// If `points` is empty, the box is empty as well, i.e. `min` is larger
+    /// than `max`.
+    pub fn from_points<I>(points: I) -> Aabb
+    where
+        I: IntoIterator<Item = na::Point3<f32>>,
+    {
+        let mut min = na::Point3::from(na::Vector3::repeat(std::f32::INFINITY));
+        let mut max = na::Point3::from(na::Vector3::repeat(std::f32::NEG_INFINITY));
+
+        for point in points {
+            min = na::Point3::from(min.coords.inf(&point.coords));
+            max = na::Point3::from(max.coords.sup(&point.coords));
+        }
+
+        Aabb { min, max }
+    }
+
+    pub fn center(&self) -> na::Point3<f32> {
+        na::center(&self.min, &self.max)
+    }
+
+    pub fn size(&self) -> na::Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [na::Point3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+
+        [
+            na::Point3::new(a.x, a.y, a.z),
+            na::Point3::new(b.x, a.y, a.z),
+            na::Point3::new(a.x, b.y, a.z),
+            na::Point3::new(b.x, b.y, a.z),
+            na::Point3::new(a.x, a.y, b.z),
+            na::Point3::new(b.x, a.y, b.z),
+            na::Point3::new(a.x, b.y, b.z),
+            na::Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Intersects the box with a ray, returning the smallest `t >= 0` such
+    /// that `origin + t * direction` is within the box.
+    pub fn ray_intersection(
+        &self,
+        origin: &na::Point3<f32>,
+        direction: &na::Vector3<f32>,
+    ) -> Option<f32> {
+        // Slab method, as in:
+        // https://tavianator.com/fast-branchless-raybounding-box-intersections/
+
+        let mut t_min = 0.0f32;
+        let mut t_max = std::f32::INFINITY;
+
+        for i in 0..3 {
+            let inv_d = 1.0 / direction[i];
+            let t_1 = (self.min[i] - origin[i]) * inv_d;
+            let t_2 = (self.max[i] - origin[i]) * inv_d;
+
+            t_min = t_min.max(t_1.min(t_2));
+            t_max = t_max.min(t_1.max(t_2));
+        }
+
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+
+    /// The axis-aligned box that contains this box after applying
+    /// `transform`.
+    pub fn transform(&self, transform: &na::Matrix4<f32>) -> Aabb {
+        Aabb::from_points(
+            self.corners()
+                .iter()
+                .map(|corner| transform.transform_point(corner)),
+        )
+    }
+}
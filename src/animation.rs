@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+
+use crate::Transform;
+
+/// Determines what happens when sampling outside of the keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Keep the first or last keyframe.
+    Clamp,
+
+    /// Repeat the animation, jumping from the last keyframe back to the
+    /// first one.
+    Loop,
+}
+
+/// Rigid animation of an instance, interpolating between keyframes.
+///
+/// Translation and scale are interpolated linearly, and rotation with slerp.
+/// The sampled `Transform` can be used for the instance's transform.
+#[derive(Debug, Clone)]
+pub struct AnimatedInstance {
+    /// Keyframes, sorted by ascending time.
+    keyframes: Vec<(f32, Transform)>,
+    pub mode: AnimationMode,
+}
+
+impl AnimatedInstance {
+    pub fn new(mode: AnimationMode) -> Self {
+        Self {
+            keyframes: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Adds a keyframe at `time`, given in seconds.
+    pub fn add_keyframe(&mut self, time: f32, transform: Transform) {
+        let index = self
+            .keyframes
+            .iter()
+            .position(|(keyframe_time, _)| *keyframe_time > time)
+            .unwrap_or(self.keyframes.len());
+
+        self.keyframes.insert(index, (time, transform));
+    }
+
+    pub fn keyframes(&self) -> &[(f32, Transform)] {
+        &self.keyframes
+    }
+
+    /// Time between the first and the last keyframe.
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some((start, _)), Some((end, _))) => end - start,
+            _ => 0.0,
+        }
+    }
+
+    /// Interpolates the transform at `time`. Without keyframes, this is the
+    /// identity.
+    pub fn sample(&self, time: f32) -> Transform {
+        let (start_time, end_time) = match (self.keyframes.first(), self.keyframes.last()) {
+            (Some((start, _)), Some((end, _))) => (*start, *end),
+            _ => return Transform::default(),
+        };
+
+        let time = match self.mode {
+            AnimationMode::Loop if end_time > start_time => {
+                start_time + (time - start_time).rem_euclid(end_time - start_time)
+            }
+            _ => time.max(start_time).min(end_time),
+        };
+
+        // Find the first keyframe that is after `time`
+        let next = self
+            .keyframes
+            .iter()
+            .position(|(keyframe_time, _)| {
+                keyframe_time.partial_cmp(&time) == Some(Ordering::Greater)
+            })
+            .unwrap_or(self.keyframes.len() - 1)
+            .max(1)
+            .min(self.keyframes.len() - 1);
+
+        if next == 0 {
+            // There is only one keyframe
+            return self.keyframes[0].1.clone();
+        }
+
+        let (time_a, a) = &self.keyframes[next - 1];
+        let (time_b, b) = &self.keyframes[next];
+
+        let t = if time_b > time_a {
+            ((time - time_a) / (time_b - time_a)).max(0.0).min(1.0)
+        } else {
+            1.0
+        };
+
+        let rotation = a
+            .rotation()
+            .try_slerp(b.rotation(), t, std::f32::EPSILON)
+            .unwrap_or_else(|| a.rotation().nlerp(b.rotation(), t));
+
+        Transform::new(
+            a.translation() + (b.translation() - a.translation()) * t,
+            rotation,
+            a.scale() + (b.scale() - a.scale()) * t,
+        )
+    }
+}
@@ -0,0 +1,133 @@
+//! Quads that always face the camera, e.g. for particles, foliage or labels.
+//!
+//! Billboards are drawn with the mesh of `BasicObj::Quad`. The orientation
+//! of each quad is taken from the camera's view matrix in the vertex shader,
+//! so instances only need a center and a size. As with `textured`, all
+//! instances share one texture atlas, from which each instance selects a
+//! region, so that sprites can be drawn in a single instanced call.
+
+use nalgebra as na;
+
+use glium::uniforms::Sampler;
+
+use crate::scene::SceneCore;
+use crate::shader::ToUniform;
+use crate::{basic_obj, shader, Context, SamplerConfig};
+
+#[derive(Clone)]
+pub struct Params<'a> {
+    pub atlas: &'a glium::texture::SrgbTexture2d,
+    pub sampler: SamplerConfig,
+}
+
+impl_uniform_input!(
+    Params<'a>,
+    self => {
+        billboard_atlas: Sampler<'a, glium::texture::SrgbTexture2d> = self.sampler.sampler(self.atlas),
+    },
+);
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    /// Center of the quad in world space.
+    pub center: na::Point3<f32>,
+
+    /// Width and height of the quad in world space.
+    pub size: na::Vector2<f32>,
+
+    /// Offset of the quad's center from `center` along the camera's right
+    /// and up axes, in world space, e.g. for the glyphs of a text label.
+    pub offset: na::Vector2<f32>,
+
+    /// Color that the texture is multiplied with.
+    pub color: na::Vector4<f32>,
+
+    /// Region of the atlas that is mapped onto the quad, given as the
+    /// lower-left corner followed by the size, in texture coordinates.
+    pub atlas_region: na::Vector4<f32>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            center: na::Point3::origin(),
+            size: na::Vector2::new(1.0, 1.0),
+            offset: na::Vector2::zeros(),
+            color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            atlas_region: na::Vector4::new(0.0, 0.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl_instance_input!(
+    Instance,
+    self => {
+        instance_center: [f32; 3] = self.center.to_uniform(),
+        instance_size: [f32; 2] = self.size,
+        instance_offset: [f32; 2] = self.offset,
+        instance_color: [f32; 4] = self.color,
+        instance_atlas_region: [f32; 4] = self.atlas_region,
+    },
+);
+
+pub struct Core;
+
+impl SceneCore for Core {
+    type Params = Params<'static>;
+    type Instance = Instance;
+    type Vertex = basic_obj::Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, Self::Params), Instance, basic_obj::Vertex> {
+        // The rows of the view matrix are the camera's axes in world space.
+        let vertex = shader::VertexCore::empty()
+            .with_body(
+                "
+                vec3 camera_right = vec3(
+                    context_camera_view[0][0],
+                    context_camera_view[1][0],
+                    context_camera_view[2][0]
+                );
+                vec3 camera_up = vec3(
+                    context_camera_view[0][1],
+                    context_camera_view[1][1],
+                    context_camera_view[2][1]
+                );
+                vec3 camera_back = vec3(
+                    context_camera_view[0][2],
+                    context_camera_view[1][2],
+                    context_camera_view[2][2]
+                );
+
+                // The quad spans [0, 1] in x and y, so move it to the center.
+                vec2 offset = instance_offset + (position.xy - 0.5) * instance_size;
+                ",
+            )
+            .with_out(shader::defs::V_WORLD_NORMAL, "camera_back")
+            .with_out(
+                shader::defs::V_WORLD_POS,
+                "vec4(
+                    instance_center + offset.x * camera_right + offset.y * camera_up,
+                    1.0
+                )",
+            )
+            .with_out(shader::defs::V_COLOR, "instance_color * color")
+            .with_out(
+                shader::defs::V_TEX_COORD,
+                "instance_atlas_region.xy + tex_coord * instance_atlas_region.zw",
+            )
+            .with_out(
+                shader::defs::V_POS,
+                "context_camera_projection * context_camera_view * v_world_pos",
+            );
+
+        let fragment = shader::FragmentCore::empty()
+            .with_in_def(shader::defs::V_COLOR)
+            .with_in_def(shader::defs::V_TEX_COORD)
+            .with_out(
+                shader::defs::F_COLOR,
+                "v_color * texture(billboard_atlas, v_tex_coord)",
+            );
+
+        shader::Core { vertex, fragment }
+    }
+}
@@ -0,0 +1,538 @@
+//! Instances of `basic_obj::Instance`, stored as separate arrays per field.
+//!
+//! A `RenderList` keeps each instance as one struct, and uploads all of them
+//! whenever any instance changes. `ColumnList` keeps the transforms apart
+//! from the remaining fields, and `ColumnInstancing` uploads them into
+//! separate per-instance buffers, so that e.g. moving instances only
+//! uploads their transforms.
+
+use std::ops::{Index, IndexMut};
+
+use nalgebra as na;
+
+use glium::implement_vertex;
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::basic_obj::{BasicObj, Instance, Resources, Vertex, NUM_TYPES};
+use crate::render_list::Slots;
+use crate::shader::{InstancingMode, ToUniform, ToUniforms};
+use crate::{CreationError, DrawError, DrawStats, Drawable, InstanceId, Material};
+
+#[derive(Copy, Clone, Debug)]
+struct TransformVertex {
+    instance_transform: [[f32; 4]; 4],
+}
+
+implement_vertex!(TransformVertex, instance_transform);
+
+#[derive(Copy, Clone, Debug)]
+struct ParamsVertex {
+    instance_color: [f32; 4],
+    instance_ambient: f32,
+    instance_diffuse: f32,
+    instance_specular: [f32; 3],
+    instance_shininess: f32,
+    instance_tint: [f32; 4],
+}
+
+implement_vertex!(
+    ParamsVertex,
+    instance_color,
+    instance_ambient,
+    instance_diffuse,
+    instance_specular,
+    instance_shininess,
+    instance_tint
+);
+
+/// Instances of one object, with one array per field of `Instance`.
+///
+/// Ids work like those of `RenderList`: they stay valid while other
+/// instances are added or removed, and removing an instance moves the last
+/// one into its place.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnList {
+    transforms: Vec<na::Matrix4<f32>>,
+    colors: Vec<na::Vector4<f32>>,
+    materials: Vec<Material>,
+    tints: Vec<na::Vector4<f32>>,
+
+    slots: Slots,
+
+    /// Whether `transforms` have changed since they were last uploaded.
+    transforms_dirty: bool,
+
+    /// Whether `colors`, `materials` or `tints` have changed since they were
+    /// last uploaded.
+    params_dirty: bool,
+}
+
+impl ColumnList {
+    /// Creates an empty list with space for at least `capacity` instances
+    /// before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ColumnList {
+            transforms: Vec::with_capacity(capacity),
+            colors: Vec::with_capacity(capacity),
+            materials: Vec::with_capacity(capacity),
+            tints: Vec::with_capacity(capacity),
+            slots: Slots::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Removes all instances, invalidating their ids.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.transforms.clear();
+        self.colors.clear();
+        self.materials.clear();
+        self.tints.clear();
+        self.mark_dirty();
+    }
+
+    /// Adds an instance, returning an id for updating or removing it later.
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        self.transforms.push(instance.transform);
+        self.colors.push(instance.color);
+        self.materials.push(instance.material);
+        self.tints.push(instance.tint);
+        self.mark_dirty();
+
+        self.slots.push()
+    }
+
+    /// Replaces the instance with the given id, returning false if the id is
+    /// no longer valid.
+    pub fn update(&mut self, id: InstanceId, instance: Instance) -> bool {
+        if let Some(index) = self.index_of(id) {
+            self.transforms[index] = instance.transform;
+            self.colors[index] = instance.color;
+            self.materials[index] = instance.material;
+            self.tints[index] = instance.tint;
+            self.mark_dirty();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replaces only the transform of the instance with the given id,
+    /// returning false if the id is no longer valid. The other fields are
+    /// not uploaded again.
+    pub fn set_transform(&mut self, id: InstanceId, transform: na::Matrix4<f32>) -> bool {
+        if let Some(index) = self.index_of(id) {
+            self.transforms[index] = transform;
+            self.transforms_dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replaces only the color of the instance with the given id, returning
+    /// false if the id is no longer valid. Transforms are not uploaded
+    /// again.
+    pub fn set_color(&mut self, id: InstanceId, color: na::Vector4<f32>) -> bool {
+        if let Some(index) = self.index_of(id) {
+            self.colors[index] = color;
+            self.params_dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the instance with the given id, returning it if the id was
+    /// still valid.
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let index = self.slots.swap_remove(id)?;
+
+        let instance = Instance {
+            transform: self.transforms.swap_remove(index),
+            color: self.colors.swap_remove(index),
+            material: self.materials.swap_remove(index),
+            tint: self.tints.swap_remove(index),
+        };
+        self.mark_dirty();
+
+        Some(instance)
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<Instance> {
+        self.index_of(id).map(|index| self.instance_at(index))
+    }
+
+    /// The instance at position `index`, assembled from the arrays.
+    pub fn instance_at(&self, index: usize) -> Instance {
+        Instance {
+            transform: self.transforms[index],
+            color: self.colors[index],
+            material: self.materials[index],
+            tint: self.tints[index],
+        }
+    }
+
+    /// Position of the instance with the given id in the arrays.
+    pub fn index_of(&self, id: InstanceId) -> Option<usize> {
+        self.slots.index_of(id)
+    }
+
+    /// Id of the instance at position `index` in the arrays.
+    pub fn id_at(&self, index: usize) -> Option<InstanceId> {
+        self.slots.id_at(index)
+    }
+
+    pub fn transforms(&self) -> &[na::Matrix4<f32>] {
+        &self.transforms
+    }
+
+    pub fn colors(&self) -> &[na::Vector4<f32>] {
+        &self.colors
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn tints(&self) -> &[na::Vector4<f32>] {
+        &self.tints
+    }
+
+    /// Forces the next `ColumnInstancing::update` to upload all arrays.
+    pub fn mark_dirty(&mut self) {
+        self.transforms_dirty = true;
+        self.params_dirty = true;
+    }
+
+    fn transform_vertices(&self) -> Vec<TransformVertex> {
+        self.transforms
+            .iter()
+            .map(|transform| TransformVertex {
+                instance_transform: transform.to_uniform(),
+            })
+            .collect()
+    }
+
+    fn params_vertices(&self) -> Vec<ParamsVertex> {
+        self.colors
+            .iter()
+            .zip(self.materials.iter())
+            .zip(self.tints.iter())
+            .map(|((color, material), tint)| ParamsVertex {
+                instance_color: color.to_uniform(),
+                instance_ambient: material.ambient,
+                instance_diffuse: material.diffuse,
+                instance_specular: material.specular.to_uniform(),
+                instance_shininess: material.shininess,
+                instance_tint: tint.to_uniform(),
+            })
+            .collect()
+    }
+}
+
+/// Instances of `Instance` for every object, like `basic_obj::RenderList`,
+/// but stored with `ColumnList`.
+#[derive(Debug, Clone)]
+pub struct ColumnRenderList(Vec<ColumnList>);
+
+impl Default for ColumnRenderList {
+    fn default() -> Self {
+        Self(vec![Default::default(); NUM_TYPES])
+    }
+}
+
+impl ColumnRenderList {
+    pub fn clear(&mut self) {
+        for list in self.0.iter_mut() {
+            list.clear();
+        }
+    }
+
+    /// Draws each instance separately with uniforms, for backends without
+    /// instancing support. Otherwise, use `ColumnInstancing`.
+    pub fn as_drawable<'a>(
+        &'a self,
+        resources: &'a Resources,
+    ) -> impl Drawable<Instance, Vertex> + 'a {
+        ColumnRenderListDrawableImpl(self, resources)
+    }
+}
+
+impl Index<BasicObj> for ColumnRenderList {
+    type Output = ColumnList;
+
+    fn index(&self, object: BasicObj) -> &ColumnList {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        &self.0[object.to_usize().unwrap()]
+    }
+}
+
+impl IndexMut<BasicObj> for ColumnRenderList {
+    fn index_mut(&mut self, object: BasicObj) -> &mut ColumnList {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        &mut self.0[object.to_usize().unwrap()]
+    }
+}
+
+/// Per-instance buffers of one `ColumnList`. Buffers are `None` while the
+/// list is empty.
+#[derive(Default)]
+struct ColumnBuffers {
+    transforms: Option<glium::VertexBuffer<TransformVertex>>,
+    params: Option<glium::VertexBuffer<ParamsVertex>>,
+}
+
+fn upload<F, V>(
+    facade: &F,
+    buffer: &mut Option<glium::VertexBuffer<V>>,
+    vertices: &[V],
+) -> Result<(), CreationError>
+where
+    F: glium::backend::Facade,
+    V: glium::vertex::Vertex,
+{
+    let same_len = buffer
+        .as_ref()
+        .map_or(false, |buffer| buffer.len() == vertices.len());
+
+    if vertices.is_empty() {
+        *buffer = None;
+    } else if same_len {
+        // Safe to unwrap, since `same_len` is only true for existing buffers.
+        buffer.as_ref().unwrap().write(vertices);
+    } else {
+        *buffer = Some(glium::VertexBuffer::dynamic(facade, vertices)?);
+    }
+
+    Ok(())
+}
+
+impl ColumnBuffers {
+    fn update<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        list: &mut ColumnList,
+    ) -> Result<(), CreationError> {
+        if list.transforms_dirty {
+            upload(facade, &mut self.transforms, &list.transform_vertices())?;
+            list.transforms_dirty = false;
+        }
+
+        if list.params_dirty {
+            upload(facade, &mut self.params, &list.params_vertices())?;
+            list.params_dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+/// Instance buffers for a `ColumnRenderList`, with one buffer for the
+/// transforms and one for the remaining fields of each object.
+///
+/// This needs instancing support, i.e. programs built with
+/// `InstancingMode::Vertex`.
+pub struct ColumnInstancing(Vec<ColumnBuffers>);
+
+impl Default for ColumnInstancing {
+    fn default() -> Self {
+        Self((0..NUM_TYPES).map(|_| ColumnBuffers::default()).collect())
+    }
+}
+
+impl ColumnInstancing {
+    /// Uploads the arrays of `render_list` that have changed since the last
+    /// call.
+    ///
+    /// Since the dirty flags are kept in `render_list`, each list should be
+    /// updated into its own `ColumnInstancing`.
+    pub fn update<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &mut ColumnRenderList,
+    ) -> Result<(), CreationError> {
+        for (buffers, list) in self.0.iter_mut().zip(render_list.0.iter_mut()) {
+            buffers.update(facade, list)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn as_drawable<'a>(
+        &'a self,
+        resources: &'a Resources,
+    ) -> impl Drawable<Instance, Vertex> + 'a {
+        ColumnInstancingDrawableImpl(self, resources)
+    }
+}
+
+struct ColumnInstancingDrawableImpl<'a>(&'a ColumnInstancing, &'a Resources);
+
+impl<'a> Drawable<Instance, Vertex> for ColumnInstancingDrawableImpl<'a> {
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Vertex
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let uniforms = uniforms.to_uniforms();
+        let mut stats = DrawStats::default();
+
+        for (i, buffers) in (self.0).0.iter().enumerate() {
+            let mesh = match self.1.meshes[i].as_ref() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let (transforms, params) = match (buffers.transforms.as_ref(), buffers.params.as_ref())
+            {
+                (Some(transforms), Some(params)) => (transforms, params),
+                _ => continue,
+            };
+
+            let transforms_per_instance = transforms
+                .per_instance()
+                .map_err(|_| DrawError::InstancingNotSupported)?;
+            let params_per_instance = params
+                .per_instance()
+                .map_err(|_| DrawError::InstancingNotSupported)?;
+
+            target
+                .draw(
+                    (
+                        &mesh.vertex_buffer,
+                        transforms_per_instance,
+                        params_per_instance,
+                    ),
+                    &mesh.index_buffer,
+                    program,
+                    &uniforms,
+                    &mesh.draw_params(draw_params),
+                )
+                .map_err(|error| DrawError::BasicObj {
+                    // Safe to unwrap here, since we iterate within the range
+                    object: FromPrimitive::from_usize(i).unwrap(),
+                    error: Box::new(DrawError::from(error)),
+                })?;
+
+            stats.draw_calls += 1;
+            stats.instances += transforms.len();
+        }
+
+        Ok(stats)
+    }
+}
+
+struct ColumnRenderListDrawableImpl<'a>(&'a ColumnRenderList, &'a Resources);
+
+impl<'a> Drawable<Instance, Vertex> for ColumnRenderListDrawableImpl<'a> {
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let mut stats = DrawStats::default();
+
+        for (i, list) in (self.0).0.iter().enumerate() {
+            let mesh = match self.1.meshes[i].as_ref() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            for index in 0..list.len() {
+                let instance = list.instance_at(index);
+
+                target
+                    .draw(
+                        &mesh.vertex_buffer,
+                        &mesh.index_buffer,
+                        program,
+                        &(uniforms, &instance).to_uniforms(),
+                        &mesh.draw_params(draw_params),
+                    )
+                    .map_err(|error| DrawError::BasicObj {
+                        // Safe to unwrap here, since we iterate within the range
+                        object: FromPrimitive::from_usize(i).unwrap(),
+                        error: Box::new(DrawError::Instance { index, error }),
+                    })?;
+            }
+
+            stats.draw_calls += list.len();
+            stats.instances += list.len();
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(x: f32) -> Instance {
+        Instance {
+            transform: na::Matrix4::new_translation(&na::Vector3::new(x, 0.0, 0.0)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn arrays_stay_parallel_after_remove() {
+        let mut list = ColumnList::default();
+        let a = list.add(instance(1.0));
+        let b = list.add(instance(2.0));
+        let c = list.add(instance(3.0));
+
+        list.set_color(c, na::Vector4::new(1.0, 0.0, 0.0, 1.0));
+        assert!(list.remove(a).is_some());
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.colors().len(), 2);
+        assert_eq!(list.index_of(c), Some(0));
+        assert_eq!(
+            list.get(c).unwrap().color,
+            na::Vector4::new(1.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(list.get(b).unwrap().transform, instance(2.0).transform);
+        assert!(list.get(a).is_none());
+    }
+
+    #[test]
+    fn set_transform_only_dirties_transforms() {
+        let mut list = ColumnList::default();
+        let id = list.add(instance(1.0));
+        list.transforms_dirty = false;
+        list.params_dirty = false;
+
+        assert!(list.set_transform(id, instance(5.0).transform));
+        assert!(list.transforms_dirty);
+        assert!(!list.params_dirty);
+    }
+}
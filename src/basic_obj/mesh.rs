@@ -1,10 +1,14 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
 use log::info;
 
+use nalgebra as na;
+
 use crate::basic_obj::{BasicObj, Vertex};
-use crate::mesh::{IndexBuffer, Mesh};
-use crate::CreationError;
+use crate::mesh::{triangles_from_indices, Mesh, MeshData, PRIMITIVE_RESTART_INDEX};
+use crate::{Aabb, CreationError};
 
 pub fn mesh_from_slices<F: glium::backend::Facade>(
     facade: &F,
@@ -13,68 +17,753 @@ pub fn mesh_from_slices<F: glium::backend::Facade>(
     normals: &[[f32; 3]],
     indices: &[u32],
 ) -> Result<Mesh<Vertex>, CreationError> {
-    let vertices = positions
+    mesh_from_slices_with_colors(facade, primitive_type, positions, normals, &[], indices)
+}
+
+/// Creates a mesh with per-vertex colors, which are multiplied with the
+/// instance color. If `colors` is empty, all vertices are white.
+pub fn mesh_from_slices_with_colors<F: glium::backend::Facade>(
+    facade: &F,
+    primitive_type: glium::index::PrimitiveType,
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    colors: &[[f32; 4]],
+    indices: &[u32],
+) -> Result<Mesh<Vertex>, CreationError> {
+    let mut vertices = positions
         .iter()
         .zip(normals.iter())
-        .map(|(&p, &n)| Vertex {
+        .enumerate()
+        .map(|(i, (&p, &n))| Vertex {
             position: p,
             normal: n,
+            tex_coord: [0.0, 0.0],
+            color: colors.get(i).copied().unwrap_or(WHITE),
+            tangent: [0.0, 0.0, 0.0, 0.0],
         })
         .collect::<Vec<_>>();
 
+    generate_box_tex_coords(&mut vertices);
+
+    let triangles = triangles_from_indices(primitive_type, indices)
+        .iter()
+        .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+        .collect::<Vec<_>>();
+    generate_tangents(&mut vertices, &triangles);
+
     Mesh::create_with_indices(facade, primitive_type, &vertices, indices)
 }
 
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Assigns texture coordinates by projecting each vertex onto the axis-aligned
+/// plane that its normal is most aligned with, scaled to the bounds of the
+/// mesh. This way, each face of a box is covered by the whole texture.
+fn generate_box_tex_coords(vertices: &mut [Vertex]) {
+    let mut min = na::Vector3::repeat(std::f32::INFINITY);
+    let mut max = na::Vector3::repeat(std::f32::NEG_INFINITY);
+
+    for vertex in vertices.iter() {
+        let position = na::Vector3::from(vertex.position);
+
+        min = min.inf(&position);
+        max = max.sup(&position);
+    }
+
+    // Avoid division by zero for flat meshes
+    let size = (max - min).map(|x| x.max(std::f32::EPSILON));
+
+    for vertex in vertices.iter_mut() {
+        let p = (na::Vector3::from(vertex.position) - min).component_div(&size);
+        let n = na::Vector3::from(vertex.normal).abs();
+
+        vertex.tex_coord = if n.x >= n.y && n.x >= n.z {
+            [p.y, p.z]
+        } else if n.y >= n.z {
+            [p.x, p.z]
+        } else {
+            [p.x, p.y]
+        };
+    }
+}
+
+/// Computes tangents from positions and texture coordinates, for normal
+/// mapping. The w component holds the handedness of the tangent frame, so
+/// that the bitangent is `cross(normal, tangent.xyz) * tangent.w`.
+///
+/// Vertices that are not part of any of the given triangles, or whose texture
+/// coordinates are degenerate, get an arbitrary tangent that is orthogonal to
+/// the normal.
+fn generate_tangents(vertices: &mut [Vertex], triangles: &[[usize; 3]]) {
+    // For reference: http://www.terathon.com/code/tangent.html
+    let mut tangents = vec![na::Vector3::zeros(); vertices.len()];
+    let mut bitangents = vec![na::Vector3::zeros(); vertices.len()];
+
+    for triangle in triangles {
+        let p = |k: usize| na::Vector3::from(vertices[triangle[k]].position);
+        let uv = |k: usize| na::Vector2::from(vertices[triangle[k]].tex_coord);
+
+        let e_1 = p(1) - p(0);
+        let e_2 = p(2) - p(0);
+        let d_1 = uv(1) - uv(0);
+        let d_2 = uv(2) - uv(0);
+
+        let det = d_1.x * d_2.y - d_2.x * d_1.y;
+        if det.abs() < std::f32::EPSILON {
+            continue;
+        }
+
+        let tangent = (e_1 * d_2.y - e_2 * d_1.y) / det;
+        let bitangent = (e_2 * d_1.x - e_1 * d_2.x) / det;
+
+        for &i in triangle {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = na::Vector3::from(vertex.normal);
+
+        // Gram-Schmidt orthogonalization
+        let tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+        let tangent = tangent.try_normalize(std::f32::EPSILON).unwrap_or_else(|| {
+            let axis = if normal.x.abs() < 0.9 {
+                na::Vector3::x()
+            } else {
+                na::Vector3::y()
+            };
+
+            normal.cross(&axis).normalize()
+        });
+
+        let handedness = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+}
+
+/// Mesh data of a basic object on the CPU side, before it is uploaded into
+/// buffers.
+pub struct ObjectData {
+    pub primitive_type: glium::index::PrimitiveType,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+
+    /// Per-vertex colors. May be empty, in which case vertices are white.
+    pub colors: Vec<[f32; 4]>,
+
+    pub indices: Vec<u32>,
+}
+
+impl ObjectData {
+    pub fn create_mesh<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+    ) -> Result<Mesh<Vertex>, CreationError> {
+        mesh_from_slices_with_colors(
+            facade,
+            self.primitive_type,
+            &self.positions,
+            &self.normals,
+            &self.colors,
+            &self.indices,
+        )
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_points(self.positions.iter().map(|&p| na::Point3::from(p)))
+    }
+
+    /// Radius of the smallest sphere around the origin that contains all
+    /// positions.
+    pub fn bounding_radius(&self) -> f32 {
+        self.positions
+            .iter()
+            .map(|&p| na::Vector3::from(p).norm())
+            .fold(0.0, f32::max)
+    }
+}
+
 pub fn load_wavefront<F: glium::backend::Facade>(
     facade: &F,
     path: &Path,
 ) -> Result<Mesh<Vertex>, CreationError> {
+    parse_wavefront(path)?.create_mesh(facade)
+}
+
+/// Loads a mesh from Wavefront .OBJ data. See `parse_wavefront_from_reader`.
+pub fn load_wavefront_from_reader<F: glium::backend::Facade, R: BufRead>(
+    facade: &F,
+    reader: &mut R,
+) -> Result<Mesh<Vertex>, CreationError> {
+    parse_wavefront_from_reader(reader)?.create_mesh(facade)
+}
+
+/// Parses a Wavefront .OBJ file into mesh data on the CPU side.
+///
+/// This does not touch the GPU, so it can run on a background thread, e.g.
+/// for loading large meshes without stalling rendering. The result can then
+/// be sent to the main thread and uploaded with `MeshData::create_mesh`.
+pub fn parse_wavefront(path: &Path) -> Result<MeshData<Vertex>, CreationError> {
     info!("Loading Wavefront .OBJ file: `{}'", path.display());
 
+    let mut reader = BufReader::new(File::open(path)?);
+
+    parse_wavefront_from_reader(&mut reader)
+}
+
+/// Parses Wavefront .OBJ data into mesh data on the CPU side. See
+/// `parse_wavefront`.
+///
+/// Quads are split into two triangles. Vertices that do not specify a normal
+/// get the normal of their triangle. If the data contains no texture
+/// coordinates at all, box-projected coordinates are generated. Vertex colors
+/// are not read, so all vertices are white.
+pub fn parse_wavefront_from_reader<R: BufRead>(
+    reader: &mut R,
+) -> Result<MeshData<Vertex>, CreationError> {
     // As in:
     // https://github.com/glium/glium/blob/master/examples/support/mod.rs
 
-    let data = obj::Obj::load(path).unwrap();
+    let data = obj::Obj::load_buf(reader)?;
 
     let mut vertices = Vec::new();
 
     for object in data.objects.iter() {
         for polygon in object.groups.iter().flat_map(|g| g.polys.iter()) {
             match polygon {
-                genmesh::Polygon::PolyTri(genmesh::Triangle {
-                    x: v1,
-                    y: v2,
-                    z: v3,
-                }) => {
-                    for v in [v1, v2, v3].iter() {
-                        let position = data.position[v.0];
-                        let normal = v.2.map(|index| data.normal[index]);
-
-                        let normal = normal.unwrap_or([0.0, 0.0, 0.0]);
-
-                        vertices.push(Vertex { position, normal })
-                    }
+                genmesh::Polygon::PolyTri(genmesh::Triangle { x, y, z }) => {
+                    push_wavefront_triangle(
+                        &data.position,
+                        &data.normal,
+                        &data.texture,
+                        [x, y, z],
+                        &mut vertices,
+                    )?;
+                }
+                genmesh::Polygon::PolyQuad(genmesh::Quad { x, y, z, w }) => {
+                    push_wavefront_triangle(
+                        &data.position,
+                        &data.normal,
+                        &data.texture,
+                        [x, y, z],
+                        &mut vertices,
+                    )?;
+                    push_wavefront_triangle(
+                        &data.position,
+                        &data.normal,
+                        &data.texture,
+                        [x, z, w],
+                        &mut vertices,
+                    )?;
                 }
-                _ => unimplemented!(),
             }
         }
     }
 
-    let vertex_buffer = glium::VertexBuffer::new(facade, &vertices).unwrap();
-    let primitive_type = glium::index::PrimitiveType::TrianglesList;
-    let index_buffer = IndexBuffer::NoIndices(glium::index::NoIndices(primitive_type));
+    if data.texture.is_empty() {
+        generate_box_tex_coords(&mut vertices);
+    }
 
-    Ok(Mesh {
-        vertex_buffer,
-        index_buffer,
+    let triangles = (0..vertices.len() / 3)
+        .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+        .collect::<Vec<_>>();
+    generate_tangents(&mut vertices, &triangles);
+
+    Ok(MeshData {
+        primitive_type: glium::index::PrimitiveType::TrianglesList,
+        vertices,
+        indices: None,
     })
 }
 
-#[rustfmt::skip]
+fn push_wavefront_triangle(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    triangle: [&obj::IndexTuple; 3],
+    vertices: &mut Vec<Vertex>,
+) -> Result<(), CreationError> {
+    let position = |index: &obj::IndexTuple| {
+        positions.get(index.0).copied().ok_or_else(|| {
+            CreationError::InvalidMesh(format!("Position index {} out of range", index.0))
+        })
+    };
+
+    let corners = [
+        position(triangle[0])?,
+        position(triangle[1])?,
+        position(triangle[2])?,
+    ];
+
+    let face_normal = {
+        let a = na::Vector3::from(corners[1]) - na::Vector3::from(corners[0]);
+        let b = na::Vector3::from(corners[2]) - na::Vector3::from(corners[0]);
+        let normal = a
+            .cross(&b)
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(na::Vector3::zeros);
+
+        [normal.x, normal.y, normal.z]
+    };
+
+    for (index, &position) in triangle.iter().zip(corners.iter()) {
+        let normal = match index.2 {
+            Some(normal_index) => normals.get(normal_index).copied().ok_or_else(|| {
+                CreationError::InvalidMesh(format!("Normal index {} out of range", normal_index))
+            })?,
+            None => face_normal,
+        };
+        let tex_coord = match index.1 {
+            Some(tex_coord_index) => tex_coords.get(tex_coord_index).copied().ok_or_else(|| {
+                CreationError::InvalidMesh(format!(
+                    "Texture coordinate index {} out of range",
+                    tex_coord_index
+                ))
+            })?,
+            None => [0.0, 0.0],
+        };
+
+        vertices.push(Vertex {
+            position,
+            normal,
+            tex_coord,
+            color: WHITE,
+            tangent: [0.0, 0.0, 0.0, 0.0],
+        });
+    }
+
+    Ok(())
+}
+
+/// Creates a UV sphere of radius 0.5.
+///
+/// Higher `num_stacks` (along the z axis) and `num_sectors` (around the z
+/// axis) give a smoother sphere at the cost of more triangles. At least two
+/// stacks and three sectors are used.
+pub fn sphere_mesh<F: glium::backend::Facade>(
+    facade: &F,
+    num_stacks: u32,
+    num_sectors: u32,
+) -> Result<Mesh<Vertex>, CreationError> {
+    sphere_data(num_stacks, num_sectors).create_mesh(facade)
+}
+
+fn sphere_data(num_stacks: u32, num_sectors: u32) -> ObjectData {
+    // For reference: http://www.songho.ca/opengl/gl_sphere.html
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    let radius = 0.5;
+
+    // Fewer would degenerate the sphere and divide by zero below
+    let num_stacks = num_stacks.max(2);
+    let num_sectors = num_sectors.max(3);
+
+    let sector_step = 2.0 * std::f32::consts::PI / num_sectors as f32;
+    let stack_step = std::f32::consts::PI / num_stacks as f32;
+
+    for i in 0..=num_stacks {
+        // Phi goes from pi/2 (top) to -pi/2 (bottom)
+        let phi = std::f32::consts::PI / 2.0 - i as f32 * stack_step;
+
+        for j in 0..=num_sectors {
+            // Theta goes from 0 to 2*pi (around the sphere)
+            let theta = j as f32 * sector_step;
+
+            let x = phi.cos() * theta.cos();
+            let y = phi.cos() * theta.sin();
+            let z = phi.sin();
+
+            positions.push([x * radius, y * radius, z * radius]);
+            normals.push([x, y, z]);
+        }
+    }
+
+    // One strip per stack, zigzagging between its upper and lower ring. The
+    // triangles at the poles are degenerate, which is cheaper than handling
+    // the poles separately.
+    for i in 0..num_stacks {
+        if i != 0 {
+            indices.push(PRIMITIVE_RESTART_INDEX);
+        }
+
+        // Beginning of current stack
+        let k_1 = i * (num_sectors + 1);
+
+        // Beginning of next stack
+        let k_2 = k_1 + (num_sectors + 1);
+
+        for j in 0..=num_sectors {
+            indices.push(k_1 + j);
+            indices.push(k_2 + j);
+        }
+    }
+
+    ObjectData {
+        primitive_type: glium::index::PrimitiveType::TriangleStrip,
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
+/// Creates a torus around the z axis.
+///
+/// `major_radius` is the distance from the center to the middle of the tube,
+/// and `minor_radius` is the radius of the tube. `num_rings` is the number of
+/// subdivisions around the z axis, `num_sides` the number of subdivisions
+/// around the tube.
+pub fn torus_mesh<F: glium::backend::Facade>(
+    facade: &F,
+    major_radius: f32,
+    minor_radius: f32,
+    num_rings: u32,
+    num_sides: u32,
+) -> Result<Mesh<Vertex>, CreationError> {
+    torus_data(major_radius, minor_radius, num_rings, num_sides).create_mesh(facade)
+}
+
+fn torus_data(major_radius: f32, minor_radius: f32, num_rings: u32, num_sides: u32) -> ObjectData {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    let ring_step = 2.0 * std::f32::consts::PI / num_rings as f32;
+    let side_step = 2.0 * std::f32::consts::PI / num_sides as f32;
+
+    for i in 0..=num_rings {
+        // Angle around the z axis
+        let u = i as f32 * ring_step;
+
+        for j in 0..=num_sides {
+            // Angle around the tube
+            let v = j as f32 * side_step;
+
+            let normal = [v.cos() * u.cos(), v.cos() * u.sin(), v.sin()];
+            let distance = major_radius + minor_radius * v.cos();
+
+            positions.push([
+                distance * u.cos(),
+                distance * u.sin(),
+                minor_radius * v.sin(),
+            ]);
+            normals.push(normal);
+        }
+    }
+
+    for i in 0..num_rings {
+        // Beginning of current ring
+        let k_1 = i * (num_sides + 1);
+
+        // Beginning of next ring
+        let k_2 = k_1 + (num_sides + 1);
+
+        for j in 0..num_sides {
+            indices.push(k_1 + j);
+            indices.push(k_2 + j);
+            indices.push(k_2 + j + 1);
+
+            indices.push(k_1 + j);
+            indices.push(k_2 + j + 1);
+            indices.push(k_1 + j + 1);
+        }
+    }
+
+    ObjectData {
+        primitive_type: glium::index::PrimitiveType::TrianglesList,
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
+/// Creates a closed cylinder of radius 0.5 and height 1 along the z axis.
+///
+/// The side has smooth normals, while the caps have flat normals.
+/// `num_segments` is the number of subdivisions around the z axis, of which
+/// at least three are used.
+pub fn cylinder_mesh<F: glium::backend::Facade>(
+    facade: &F,
+    num_segments: u32,
+) -> Result<Mesh<Vertex>, CreationError> {
+    cylinder_data(num_segments).create_mesh(facade)
+}
+
+fn cylinder_data(num_segments: u32) -> ObjectData {
+    // Fewer would degenerate the cylinder and divide by zero below
+    let num_segments = num_segments.max(3);
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    let radius = 0.5;
+    let step = 2.0 * std::f32::consts::PI / num_segments as f32;
+
+    // Side
+    for j in 0..=num_segments {
+        let theta = j as f32 * step;
+        let (x, y) = (theta.cos(), theta.sin());
+
+        positions.push([x * radius, y * radius, -0.5]);
+        normals.push([x, y, 0.0]);
+
+        positions.push([x * radius, y * radius, 0.5]);
+        normals.push([x, y, 0.0]);
+    }
+
+    // One strip around the cylinder, starting at the top so that the
+    // triangles are counter-clockwise from outside
+    for j in 0..=num_segments {
+        indices.extend_from_slice(&[2 * j + 1, 2 * j]);
+    }
+
+    // Caps
+    add_cap_strip(
+        &mut positions,
+        &mut normals,
+        &mut indices,
+        num_segments,
+        0.5,
+    );
+    add_cap_strip(
+        &mut positions,
+        &mut normals,
+        &mut indices,
+        num_segments,
+        -0.5,
+    );
+
+    ObjectData {
+        primitive_type: glium::index::PrimitiveType::TriangleStrip,
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
+/// Creates a cone of radius 0.5 and height 1 along the z axis, with the apex
+/// pointing up.
+///
+/// The side has smooth normals, while the base has flat normals.
+/// `num_segments` is the number of subdivisions around the z axis, of which
+/// at least three are used.
+pub fn cone_mesh<F: glium::backend::Facade>(
+    facade: &F,
+    num_segments: u32,
+) -> Result<Mesh<Vertex>, CreationError> {
+    cone_data(num_segments).create_mesh(facade)
+}
+
+fn cone_data(num_segments: u32) -> ObjectData {
+    // Fewer would degenerate the cone and divide by zero below
+    let num_segments = num_segments.max(3);
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    let radius = 0.5;
+    let height = 1.0;
+    let step = 2.0 * std::f32::consts::PI / num_segments as f32;
+
+    let side_normal = |theta: f32| {
+        let normal = na::Vector3::new(height * theta.cos(), height * theta.sin(), radius);
+        let normal = normal.normalize();
+
+        [normal.x, normal.y, normal.z]
+    };
+
+    // Side. The apex is duplicated for each segment, so that it can get the
+    // normal of the segment's center.
+    for j in 0..=num_segments {
+        let theta = j as f32 * step;
+
+        positions.push([theta.cos() * radius, theta.sin() * radius, -0.5]);
+        normals.push(side_normal(theta));
+    }
+
+    for j in 0..num_segments {
+        let apex = positions.len() as u32;
+
+        positions.push([0.0, 0.0, 0.5]);
+        normals.push(side_normal((j as f32 + 0.5) * step));
+
+        indices.extend_from_slice(&[j, j + 1, apex]);
+    }
+
+    // Base
+    add_cap(
+        &mut positions,
+        &mut normals,
+        &mut indices,
+        num_segments,
+        -0.5,
+    );
+
+    ObjectData {
+        primitive_type: glium::index::PrimitiveType::TrianglesList,
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
+/// Adds a flat disk of radius 0.5 at height `z`, facing up if `z` is
+/// positive and down otherwise.
+fn add_cap(
+    positions: &mut Vec<[f32; 3]>,
+    normals: &mut Vec<[f32; 3]>,
+    indices: &mut Vec<u32>,
+    num_segments: u32,
+    z: f32,
+) {
+    let radius = 0.5;
+    let step = 2.0 * std::f32::consts::PI / num_segments as f32;
+    let normal = [0.0, 0.0, z.signum()];
+
+    let center = positions.len() as u32;
+    positions.push([0.0, 0.0, z]);
+    normals.push(normal);
+
+    for j in 0..=num_segments {
+        let theta = j as f32 * step;
+
+        positions.push([theta.cos() * radius, theta.sin() * radius, z]);
+        normals.push(normal);
+    }
+
+    for j in 0..num_segments {
+        let ring_0 = center + 1 + j;
+        let ring_1 = center + 1 + j + 1;
+
+        // Keep counter-clockwise winding when looking at the cap from outside
+        if z > 0.0 {
+            indices.extend_from_slice(&[center, ring_0, ring_1]);
+        } else {
+            indices.extend_from_slice(&[center, ring_1, ring_0]);
+        }
+    }
+}
+
+/// Adds a flat disk of radius 0.5 at height `z` like `add_cap`, but as a
+/// triangle strip that is separated from the previous indices by
+/// `PRIMITIVE_RESTART_INDEX`.
+///
+/// The strip zigzags between both sides of the ring, so no center vertex is
+/// needed.
+fn add_cap_strip(
+    positions: &mut Vec<[f32; 3]>,
+    normals: &mut Vec<[f32; 3]>,
+    indices: &mut Vec<u32>,
+    num_segments: u32,
+    z: f32,
+) {
+    let radius = 0.5;
+    let step = 2.0 * std::f32::consts::PI / num_segments as f32;
+    let normal = [0.0, 0.0, z.signum()];
+
+    let start = positions.len() as u32;
+
+    for j in 0..num_segments {
+        let theta = j as f32 * step;
+
+        positions.push([theta.cos() * radius, theta.sin() * radius, z]);
+        normals.push(normal);
+    }
+
+    indices.push(PRIMITIVE_RESTART_INDEX);
+    indices.push(start);
+
+    // Keep counter-clockwise winding when looking at the cap from outside
+    let (mut forward, mut backward) = (1, num_segments - 1);
+    let mut forward_first = z > 0.0;
+
+    while forward <= backward {
+        if forward_first {
+            indices.push(start + forward);
+            forward += 1;
+        } else {
+            indices.push(start + backward);
+            backward -= 1;
+        }
+
+        forward_first = !forward_first;
+    }
+}
+
+/// A quad in the xy plane, spanning [0, 1] in x and y, which is subdivided
+/// into a grid of `num_subdivisions` by `num_subdivisions` cells.
+fn plane_data(num_subdivisions: u32) -> ObjectData {
+    let n = num_subdivisions;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    for i in 0..=n {
+        for j in 0..=n {
+            positions.push([j as f32 / n as f32, i as f32 / n as f32, 0.0]);
+            normals.push([0.0, 0.0, 1.0]);
+        }
+    }
+
+    for i in 0..n {
+        for j in 0..n {
+            let corner = i * (n + 1) + j;
+
+            indices.extend_from_slice(&[corner, corner + 1, corner + n + 2]);
+            indices.extend_from_slice(&[corner + n + 2, corner + n + 1, corner]);
+        }
+    }
+
+    ObjectData {
+        primitive_type: glium::index::PrimitiveType::TrianglesList,
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
 pub fn create_mesh<F: glium::backend::Facade>(
     object: BasicObj,
     facade: &F,
 ) -> Result<Mesh<Vertex>, CreationError> {
+    object_data(object).create_mesh(facade)
+}
+
+/// Like `object_data`, but with `detail` giving the number of segments of
+/// curved objects and the number of subdivisions of `BasicObj::Plane`. Other
+/// objects do not depend on `detail`.
+pub fn object_data_with_detail(object: BasicObj, detail: u32) -> ObjectData {
+    // Fewer segments would degenerate the curved objects
+    let detail = detail.max(3);
+
+    match object {
+        BasicObj::Sphere => sphere_data(detail, detail),
+        BasicObj::Cylinder => cylinder_data(detail),
+        BasicObj::Cone => cone_data(detail),
+        BasicObj::Torus => torus_data(0.35, 0.15, 2 * detail, detail),
+        BasicObj::Plane => plane_data(detail),
+        object => object_data(object),
+    }
+}
+
+#[rustfmt::skip]
+pub fn object_data(object: BasicObj) -> ObjectData {
     match object {
         BasicObj::Triangle => {
             let positions = vec![
@@ -91,13 +780,13 @@ pub fn create_mesh<F: glium::backend::Facade>(
 
             let indices = vec![0, 1, 2];
 
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &positions,
-                &normals,
-                &indices,
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::TrianglesList,
+                positions,
+                normals,
+                indices,
+                colors: Vec::new(),
+            }
         }
         BasicObj::Quad => {
             let positions = vec![
@@ -119,110 +808,58 @@ pub fn create_mesh<F: glium::backend::Facade>(
                 2, 3, 0,
             ];
 
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &positions,
-                &normals,
-                &indices,
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::TrianglesList,
+                positions,
+                normals,
+                indices,
+                colors: Vec::new(),
+            }
         }
         BasicObj::Cube => {
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                CUBE_POSITIONS,
-                CUBE_NORMALS,
-                CUBE_INDICES,
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::TrianglesList,
+                positions: CUBE_POSITIONS.to_vec(),
+                normals: CUBE_NORMALS.to_vec(),
+                indices: CUBE_INDICES.to_vec(),
+                colors: Vec::new(),
+            }
         }
         BasicObj::Sphere => {
-            // For reference: http://www.songho.ca/opengl/gl_sphere.html
-
-            let mut positions = Vec::new();
-            let mut normals = Vec::new();
-            let mut indices = Vec::new();
-
-            let radius = 0.5;
-            let num_stacks = 10;
-            let num_sectors = 10;
-
-            let sector_step = 2.0 * std::f32::consts::PI / num_sectors as f32;
-            let stack_step = std::f32::consts::PI / num_stacks as f32;
-
-            for i in 0..=num_stacks {
-                // Phi goes from pi/2 (top) to -pi/2 (bottom)
-                let phi = std::f32::consts::PI / 2.0 - i as f32 * stack_step;
-
-                for j in 0..=num_sectors {
-                    // Theta goes from 0 to 2*pi (around the sphere)
-                    let theta = j as f32 * sector_step;
-
-                    let x = phi.cos() * theta.cos();
-                    let y = phi.cos() * theta.sin();
-                    let z = phi.sin();
-
-                    positions.push([x * radius, y * radius, z * radius]);
-                    normals.push([x, y, z]);
-                }
-            }
-
-            for i in 0..num_stacks {
-                // Beginning of current stack
-                let k_1 = i * (num_sectors + 1);
-
-                // Beginning of next stack
-                let k_2 = k_1 + (num_sectors + 1);
-
-                for j in 0..num_sectors {
-                    if i != 0 {
-                        indices.push(k_1 + j);
-                        indices.push(k_2 + j);
-                        indices.push(k_1 + j + 1);
-                    }
-
-                    if i + 1 != num_stacks {
-                        indices.push(k_1 + j + 1);
-                        indices.push(k_2 + j);
-                        indices.push(k_2 + j + 1);
-                    }
-                }
-            }
-
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &positions,
-                &normals,
-                &indices,
-            )
+            sphere_data(10, 10)
+        }
+        BasicObj::Cylinder => {
+            cylinder_data(16)
+        }
+        BasicObj::Cone => {
+            cone_data(16)
         }
         BasicObj::LineX => {
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::LinesList,
-                &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
-                &[[0.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
-                &[0, 1],
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::LinesList,
+                positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+                normals: vec![[0.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+                indices: vec![0, 1],
+                colors: Vec::new(),
+            }
         }
         BasicObj::LineY => {
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::LinesList,
-                &[[0.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
-                &[[0.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
-                &[0, 1],
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::LinesList,
+                positions: vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                normals: vec![[0.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+                indices: vec![0, 1],
+                colors: Vec::new(),
+            }
         }
         BasicObj::LineZ => {
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::LinesList,
-                &[[0.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
-                &[[1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
-                &[0, 1],
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::LinesList,
+                positions: vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+                normals: vec![[1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+                indices: vec![0, 1],
+                colors: Vec::new(),
+            }
         }
         BasicObj::TessellatedCube => {
             let mut positions = Vec::new();
@@ -246,13 +883,13 @@ pub fn create_mesh<F: glium::backend::Facade>(
                 }
             }
 
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &positions,
-                &normals,
-                &indices,
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::TrianglesList,
+                positions,
+                normals,
+                indices,
+                colors: Vec::new(),
+            }
         }
         BasicObj::TessellatedCylinder => {
             let mut positions = Vec::new();
@@ -294,13 +931,19 @@ pub fn create_mesh<F: glium::backend::Facade>(
                 }
             }
 
-            mesh_from_slices(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &positions,
-                &normals,
-                &indices,
-            )
+            ObjectData {
+                primitive_type: glium::index::PrimitiveType::TrianglesList,
+                positions,
+                normals,
+                indices,
+                colors: Vec::new(),
+            }
+        }
+        BasicObj::Torus => {
+            torus_data(0.35, 0.15, 24, 12)
+        }
+        BasicObj::Plane => {
+            plane_data(64)
         }
     }
 }
@@ -403,3 +1046,38 @@ pub const CUBE_INDICES: &[u32] = &[
     // Bottom
     20, 21, 22, 20, 22, 23,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that all non-degenerate triangles of a convex object around the
+    /// origin are counter-clockwise when seen from outside.
+    fn assert_faces_outward(data: &ObjectData) {
+        let triangles = triangles_from_indices(data.primitive_type, &data.indices);
+        assert!(!triangles.is_empty());
+
+        for t in triangles {
+            let p = |k: usize| na::Vector3::from(data.positions[t[k] as usize]);
+            let normal = (p(1) - p(0)).cross(&(p(2) - p(0)));
+
+            if normal.norm() < 1e-6 {
+                continue;
+            }
+
+            let center = (p(0) + p(1) + p(2)) / 3.0;
+            assert!(normal.dot(&center) > 0.0, "triangle {:?} faces inward", t);
+        }
+    }
+
+    #[test]
+    fn sphere_faces_outward() {
+        assert_faces_outward(&sphere_data(5, 7));
+    }
+
+    #[test]
+    fn cylinder_faces_outward() {
+        assert_faces_outward(&cylinder_data(3));
+        assert_faces_outward(&cylinder_data(8));
+    }
+}
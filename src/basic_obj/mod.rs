@@ -1,15 +1,33 @@
+mod columns;
 mod mesh;
 mod scene;
 
+pub mod billboard;
+pub mod normal_mapped;
+pub mod terrain;
+pub mod textured;
+
+use std::cmp::Ordering;
 use std::ops::{Index, IndexMut};
 
+use nalgebra as na;
+
 use glium::implement_vertex;
 use num_traits::{FromPrimitive, ToPrimitive};
 
+use crate::pipeline::{self, shadow};
 use crate::shader::{InstanceInput, InstancingMode, ToUniforms};
-use crate::{CreationError, DrawError, Drawable, Mesh};
-
-pub use mesh::{load_wavefront, mesh_from_slices, CUBE_INDICES, CUBE_NORMALS, CUBE_POSITIONS};
+use crate::{
+    Aabb, Camera, Context, CreationError, DrawError, DrawStats, Drawable, Frustum, HasTransform,
+    HasTransformMut, InstanceId, Mesh,
+};
+
+pub use columns::{ColumnInstancing, ColumnList, ColumnRenderList};
+pub use mesh::{
+    cone_mesh, cylinder_mesh, load_wavefront, load_wavefront_from_reader, mesh_from_slices,
+    mesh_from_slices_with_colors, parse_wavefront, parse_wavefront_from_reader, sphere_mesh,
+    torus_mesh, CUBE_INDICES, CUBE_NORMALS, CUBE_POSITIONS,
+};
 pub use scene::{Core, Instance};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -18,6 +36,8 @@ pub enum BasicObj {
     Quad,
     Cube,
     Sphere,
+    Cylinder,
+    Cone,
 
     LineX,
     LineY,
@@ -25,41 +45,264 @@ pub enum BasicObj {
 
     TessellatedCube,
     TessellatedCylinder,
+
+    Torus,
+
+    /// A quad like `Quad`, subdivided into a grid, e.g. for terrain.
+    Plane,
 }
 
-pub const NUM_TYPES: usize = 9;
+pub const NUM_TYPES: usize = 13;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
+    pub tex_coord: [f32; 2],
+
+    /// Baked vertex color, which is multiplied with the instance color.
+    pub color: [f32; 4],
+
+    /// Tangent for normal mapping, with the handedness of the tangent frame
+    /// in the w component.
+    pub tangent: [f32; 4],
 }
 
-implement_vertex!(Vertex, position, normal);
+implement_vertex!(Vertex, position, normal, tex_coord, color, tangent);
 
-pub struct Resources {
-    pub meshes: Vec<Mesh<Vertex>>,
+/// Quality presets for constrained hardware, selecting the tessellation of
+/// curved objects and the resolution of shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
 }
 
-impl Resources {
-    pub fn create<F: glium::backend::Facade>(facade: &F) -> Result<Resources, CreationError> {
+impl Default for Quality {
+    fn default() -> Self {
+        Quality::Medium
+    }
+}
+
+impl Quality {
+    /// Detail of `object` for `Resources::regenerate_primitive`, or `None`
+    /// for the default mesh.
+    pub fn detail(self, object: BasicObj) -> Option<u32> {
+        match (self, object) {
+            (Quality::Medium, _) => None,
+            (Quality::Low, BasicObj::Plane) => Some(16),
+            (Quality::Low, _) => Some(8),
+            (Quality::High, BasicObj::Plane) => Some(128),
+            (Quality::High, _) => Some(32),
+        }
+    }
+
+    /// Shadow mapping configuration, where `Medium` is the default.
+    pub fn shadow_config(self) -> shadow::Config {
+        match self {
+            Quality::Low => shadow::Config {
+                shadow_map_size: na::Vector2::new(1024, 1024),
+                pcf_distance: 0,
+                ..Default::default()
+            },
+            Quality::Medium => shadow::Config::default(),
+            Quality::High => shadow::Config {
+                pcf_distance: 2,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Selects the objects for which `Resources` creates meshes, and the quality
+/// of rendering.
+///
+/// By default, all objects are enabled. Disabling unneeded objects saves
+/// memory and startup time, since no data is generated for them. Rendering
+/// effects such as shadow mapping are configured in `pipeline::Config`,
+/// which `pipeline_config` creates to match the builder.
+#[derive(Debug, Clone)]
+pub struct ResourcesBuilder {
+    objects: [bool; NUM_TYPES],
+    quality: Quality,
+    shadow_mapping: bool,
+}
+
+impl Default for ResourcesBuilder {
+    fn default() -> Self {
+        Self {
+            objects: [true; NUM_TYPES],
+            quality: Quality::default(),
+            shadow_mapping: true,
+        }
+    }
+}
+
+impl ResourcesBuilder {
+    /// Starts with no objects enabled.
+    pub fn empty() -> Self {
+        Self {
+            objects: [false; NUM_TYPES],
+            ..Default::default()
+        }
+    }
+
+    pub fn with_object(mut self, object: BasicObj, enabled: bool) -> Self {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        self.objects[object.to_usize().unwrap()] = enabled;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn with_shadow_mapping(mut self, shadow_mapping: bool) -> Self {
+        self.shadow_mapping = shadow_mapping;
+        self
+    }
+
+    /// A pipeline configuration with shadow mapping as selected in this
+    /// builder, at its quality.
+    pub fn pipeline_config(&self) -> pipeline::Config {
+        pipeline::Config {
+            shadow_mapping: if self.shadow_mapping {
+                Some(self.quality.shadow_config())
+            } else {
+                None
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn build<F: glium::backend::Facade>(&self, facade: &F) -> Result<Resources, CreationError> {
         // Unfortunately, it doesn't seem easy to use enum_map here,
         // since we need to check for errors in creating buffers
         let mut meshes = Vec::new();
+        let mut aabbs = Vec::new();
+        let mut bounding_radii = Vec::new();
 
         for i in 0..NUM_TYPES {
+            if !self.objects[i] {
+                meshes.push(None);
+                aabbs.push(None);
+                bounding_radii.push(None);
+                continue;
+            }
+
             // Safe to unwrap here, since we iterate within the range
             let object: BasicObj = FromPrimitive::from_usize(i).unwrap();
-
-            meshes.push(object.create_mesh(facade)?);
+            let data = match self.quality.detail(object) {
+                Some(detail) => mesh::object_data_with_detail(object, detail),
+                None => mesh::object_data(object),
+            };
+
+            meshes.push(Some(data.create_mesh(facade)?));
+            aabbs.push(Some(data.aabb()));
+            bounding_radii.push(Some(data.bounding_radius()));
         }
 
-        Ok(Resources { meshes })
+        Ok(Resources {
+            meshes,
+            aabbs,
+            bounding_radii,
+        })
     }
+}
+
+pub struct Resources {
+    /// Meshes of the objects, or `None` for objects that have been disabled
+    /// in `ResourcesBuilder`. Instances of objects without a mesh are not
+    /// drawn.
+    pub meshes: Vec<Option<Mesh<Vertex>>>,
+
+    /// Bounding boxes of the meshes in model space, or `None` for disabled
+    /// objects.
+    pub aabbs: Vec<Option<Aabb>>,
+
+    /// Radii of spheres around the origin that contain the meshes, or `None`
+    /// for disabled objects.
+    pub bounding_radii: Vec<Option<f32>>,
+}
 
-    pub fn mesh(&self, object: BasicObj) -> &Mesh<Vertex> {
+impl Resources {
+    /// Creates meshes for all objects.
+    pub fn create<F: glium::backend::Facade>(facade: &F) -> Result<Resources, CreationError> {
+        ResourcesBuilder::default().build(facade)
+    }
+
+    /// Mesh of the object, or `None` if the object has been disabled in
+    /// `ResourcesBuilder`.
+    pub fn mesh(&self, object: BasicObj) -> Option<&Mesh<Vertex>> {
         // Safe to unwrap since `BasicObj::to_usize()` never fails.
-        &self.meshes[object.to_usize().unwrap()]
+        self.meshes[object.to_usize().unwrap()].as_ref()
+    }
+
+    /// Bounding box of the object's mesh in model space, or `None` if the
+    /// object has been disabled in `ResourcesBuilder`.
+    ///
+    /// To get a box in world space, use `Aabb::transform` with the instance
+    /// transform.
+    pub fn aabb(&self, object: BasicObj) -> Option<Aabb> {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        self.aabbs[object.to_usize().unwrap()]
+    }
+
+    /// Radius of a sphere around the origin that contains the object's mesh,
+    /// e.g. for `HasTransform::bounding_sphere`.
+    ///
+    /// This is computed from the mesh itself, so it does not account for
+    /// displacement in the vertex shader, such as that of `terrain`. See
+    /// `terrain::bounding_radius` for the latter. Returns `None` if the
+    /// object has been disabled in `ResourcesBuilder`.
+    pub fn bounding_radius(&self, object: BasicObj) -> Option<f32> {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        self.bounding_radii[object.to_usize().unwrap()]
+    }
+
+    pub fn is_enabled(&self, object: BasicObj) -> bool {
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        self.meshes[object.to_usize().unwrap()].is_some()
+    }
+
+    /// Replaces the mesh of `object` by one with the given level of `detail`,
+    /// e.g. for a quality setting that can be changed at runtime.
+    ///
+    /// For `Sphere`, `Cylinder`, `Cone` and `Torus`, `detail` is the number of
+    /// segments around the object, and for `Plane` the number of subdivisions
+    /// along each axis. Other objects are not affected by `detail`. If the
+    /// object has been disabled in `ResourcesBuilder`, this enables it.
+    pub fn regenerate_primitive<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        object: BasicObj,
+        detail: u32,
+    ) -> Result<(), CreationError> {
+        let data = mesh::object_data_with_detail(object, detail);
+
+        // Safe to unwrap since `BasicObj::to_usize()` never fails.
+        let index = object.to_usize().unwrap();
+        self.meshes[index] = Some(data.create_mesh(facade)?);
+        self.aabbs[index] = Some(data.aabb());
+        self.bounding_radii[index] = Some(data.bounding_radius());
+
+        Ok(())
+    }
+
+    /// A `Drawable` for a single instance of `object`, e.g. for drawing a
+    /// gizmo without setting up a `RenderList`.
+    ///
+    /// It is drawn like an instance of a `RenderList`, so it can be used with
+    /// the same passes and draw parameters. If the object has been disabled
+    /// in `ResourcesBuilder`, nothing is drawn.
+    pub fn render_instance<'a, I: InstanceInput>(
+        &'a self,
+        object: BasicObj,
+        instance: &'a I,
+    ) -> impl Drawable<I, Vertex> + 'a {
+        InstanceDrawableImpl(self.mesh(object), instance)
     }
 }
 
@@ -82,11 +325,120 @@ impl<I: InstanceInput> RenderList<I> {
         }
     }
 
+    /// Moves the instances of every object in `other` to the end of the
+    /// respective list. See `crate::RenderList::append`.
+    ///
+    /// Returns the new ids of the instances of each object, indexed by
+    /// `BasicObj::to_usize`.
+    pub fn append(&mut self, other: RenderList<I>) -> Vec<Vec<InstanceId>> {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .map(|(list, other_list)| list.append(other_list))
+            .collect()
+    }
+
+    /// Copies the instances of every object in `other` to the end of the
+    /// respective list. See `crate::RenderList::extend`.
+    ///
+    /// Returns the ids of the instances of each object in this list, indexed
+    /// by `BasicObj::to_usize`.
+    pub fn extend(&mut self, other: &RenderList<I>) -> Vec<Vec<InstanceId>>
+    where
+        I: Clone,
+    {
+        self.0
+            .iter_mut()
+            .zip(other.0.iter())
+            .map(|(list, other_list)| list.extend(other_list))
+            .collect()
+    }
+
+    /// Marks the list of every object as dirty. See
+    /// `crate::RenderList::mark_dirty`.
+    pub fn mark_dirty(&mut self) {
+        for list in self.0.iter_mut() {
+            list.mark_dirty();
+        }
+    }
+
     pub fn as_drawable<'a>(&'a self, resources: &'a Resources) -> impl Drawable<I, Vertex> + 'a {
         RenderListDrawableImpl(self, resources)
     }
 }
 
+impl<I: InstanceInput + HasTransformMut + Clone> RenderList<I> {
+    /// Like `as_drawable`, but with each instance moved relative to the eye
+    /// if `Context::camera_relative` is enabled. See
+    /// `crate::RenderList::as_drawable_in`.
+    pub fn as_drawable_in<'a>(
+        &'a self,
+        resources: &'a Resources,
+        context: &'a Context,
+    ) -> impl Drawable<I, Vertex> + 'a {
+        ContextRenderListDrawableImpl(self, resources, context)
+    }
+}
+
+impl<I: InstanceInput + HasTransform> RenderList<I> {
+    /// Sorts the instances of each object by ascending distance from the
+    /// camera. See `crate::RenderList::sort_front_to_back`.
+    pub fn sort_front_to_back(&mut self, camera: &Camera) {
+        for list in self.0.iter_mut() {
+            list.sort_front_to_back(camera);
+        }
+    }
+
+    /// Sorts the instances of each object by descending distance from the
+    /// camera. See `crate::RenderList::sort_back_to_front`.
+    pub fn sort_back_to_front(&mut self, camera: &Camera) {
+        for list in self.0.iter_mut() {
+            list.sort_back_to_front(camera);
+        }
+    }
+
+    /// Removes instances that lie completely outside of `frustum`, returning
+    /// how many instances were removed. Instances are approximated by
+    /// `Resources::bounding_radius`. See `crate::RenderList::cull`.
+    ///
+    /// Instances of objects that are disabled in `resources` are kept, since
+    /// they are not drawn anyway.
+    pub fn cull(&mut self, resources: &Resources, frustum: &Frustum) -> usize {
+        let mut num_culled = 0;
+
+        for (list, radius) in self.0.iter_mut().zip(resources.bounding_radii.iter()) {
+            if let Some(radius) = radius {
+                num_culled += list.cull(frustum, *radius);
+            }
+        }
+
+        num_culled
+    }
+
+    /// Finds the instance that is hit first by a ray, returning its object,
+    /// index and the ray parameter `t` of the hit.
+    /// See `crate::RenderList::pick`.
+    pub fn pick(
+        &self,
+        resources: &Resources,
+        ray_origin: &na::Point3<f32>,
+        ray_direction: &na::Vector3<f32>,
+    ) -> Option<(BasicObj, usize, f32)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, list)| {
+                // Safe to unwrap here, since we iterate within the range
+                let object: BasicObj = FromPrimitive::from_usize(i).unwrap();
+                let aabb = resources.aabbs[i].as_ref()?;
+
+                list.pick(ray_origin, ray_direction, aabb)
+                    .map(|(index, t)| (object, index, t))
+            })
+            .min_by(|(_, _, t_a), (_, _, t_b)| t_a.partial_cmp(t_b).unwrap_or(Ordering::Equal))
+    }
+}
+
 impl<I: InstanceInput + Clone> Default for RenderList<I> {
     fn default() -> Self {
         Self(vec![Default::default(); NUM_TYPES])
@@ -121,13 +473,56 @@ impl<I: InstanceInput> Instancing<I> {
         Ok(Instancing(vec))
     }
 
+    /// The instancing mode that is used for drawing.
+    ///
+    /// Programs that are used with this `Instancing` need to be built with
+    /// this mode.
+    pub fn mode(&self) -> InstancingMode {
+        // All of our buffers have been created with the same facade, so they
+        // agree on the mode.
+        self.0[0].mode()
+    }
+
     pub fn update<F: glium::backend::Facade>(
         &mut self,
         facade: &F,
         render_list: &RenderList<I>,
     ) -> Result<(), CreationError> {
         for i in 0..NUM_TYPES {
-            self.0[i].update(facade, render_list.0[i].as_slice())?;
+            self.0[i].update_visible(facade, &render_list.0[i])?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the instances of each object, moved relative to the eye if
+    /// `Context::camera_relative` is enabled. See
+    /// `crate::Instancing::update_in`.
+    pub fn update_in<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &RenderList<I>,
+        context: &Context,
+    ) -> Result<(), CreationError>
+    where
+        I: HasTransformMut + Clone,
+    {
+        for i in 0..NUM_TYPES {
+            self.0[i].update_in(facade, &render_list.0[i], context)?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the instances of each object whose list has changed. See
+    /// `crate::Instancing::update_if_dirty`.
+    pub fn update_if_dirty<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &mut RenderList<I>,
+    ) -> Result<(), CreationError> {
+        for i in 0..NUM_TYPES {
+            self.0[i].update_if_dirty(facade, &mut render_list.0[i])?;
         }
 
         Ok(())
@@ -141,7 +536,9 @@ impl<I: InstanceInput> Instancing<I> {
 struct InstancingDrawableImpl<'a, I: InstanceInput>(&'a Instancing<I>, &'a Resources);
 
 impl<'a, I: InstanceInput> Drawable<I, Vertex> for InstancingDrawableImpl<'a, I> {
-    const INSTANCING_MODE: InstancingMode = InstancingMode::Vertex;
+    fn instancing_mode(&self) -> InstancingMode {
+        self.0.mode()
+    }
 
     fn draw<U, S>(
         &self,
@@ -149,28 +546,36 @@ impl<'a, I: InstanceInput> Drawable<I, Vertex> for InstancingDrawableImpl<'a, I>
         uniforms: &U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         U: ToUniforms,
         S: glium::Surface,
     {
+        let mut stats = DrawStats::default();
+
         for i in 0..NUM_TYPES {
-            (self.0).0[i].as_drawable(&self.1.meshes[i]).draw(
-                program,
-                uniforms,
-                draw_params,
-                target,
-            )?;
+            if let Some(mesh) = self.1.meshes[i].as_ref() {
+                stats += (self.0).0[i]
+                    .as_drawable(mesh)
+                    .draw(program, uniforms, draw_params, target)
+                    .map_err(|error| DrawError::BasicObj {
+                        // Safe to unwrap here, since we iterate within the range
+                        object: FromPrimitive::from_usize(i).unwrap(),
+                        error: Box::new(error),
+                    })?;
+            }
         }
 
-        Ok(())
+        Ok(stats)
     }
 }
 
 struct RenderListDrawableImpl<'a, I: InstanceInput>(&'a RenderList<I>, &'a Resources);
 
 impl<'a, I: InstanceInput> Drawable<I, Vertex> for RenderListDrawableImpl<'a, I> {
-    const INSTANCING_MODE: InstancingMode = InstancingMode::Uniforms;
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
 
     fn draw<U, S>(
         &self,
@@ -178,20 +583,111 @@ impl<'a, I: InstanceInput> Drawable<I, Vertex> for RenderListDrawableImpl<'a, I>
         uniforms: &U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         U: ToUniforms,
         S: glium::Surface,
     {
+        let mut stats = DrawStats::default();
+
         for i in 0..NUM_TYPES {
-            (self.0).0[i].as_drawable(&self.1.meshes[i]).draw(
-                program,
-                uniforms,
-                draw_params,
-                target,
-            )?;
+            if let Some(mesh) = self.1.meshes[i].as_ref() {
+                stats += (self.0).0[i]
+                    .as_drawable(mesh)
+                    .draw(program, uniforms, draw_params, target)
+                    .map_err(|error| DrawError::BasicObj {
+                        // Safe to unwrap here, since we iterate within the range
+                        object: FromPrimitive::from_usize(i).unwrap(),
+                        error: Box::new(error),
+                    })?;
+            }
         }
 
-        Ok(())
+        Ok(stats)
+    }
+}
+
+struct ContextRenderListDrawableImpl<'a, I: InstanceInput>(
+    &'a RenderList<I>,
+    &'a Resources,
+    &'a Context,
+);
+
+impl<'a, I> Drawable<I, Vertex> for ContextRenderListDrawableImpl<'a, I>
+where
+    I: InstanceInput + HasTransformMut + Clone,
+{
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let mut stats = DrawStats::default();
+
+        for i in 0..NUM_TYPES {
+            if let Some(mesh) = self.1.meshes[i].as_ref() {
+                stats += (self.0).0[i]
+                    .as_drawable_in(mesh, self.2)
+                    .draw(program, uniforms, draw_params, target)
+                    .map_err(|error| DrawError::BasicObj {
+                        // Safe to unwrap here, since we iterate within the range
+                        object: FromPrimitive::from_usize(i).unwrap(),
+                        error: Box::new(error),
+                    })?;
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+struct InstanceDrawableImpl<'a, I: InstanceInput>(Option<&'a Mesh<Vertex>>, &'a I);
+
+impl<'a, I: InstanceInput> Drawable<I, Vertex> for InstanceDrawableImpl<'a, I> {
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let mesh = match self.0 {
+            Some(mesh) => mesh,
+            None => return Ok(DrawStats::default()),
+        };
+
+        target
+            .draw(
+                &mesh.vertex_buffer,
+                &mesh.index_buffer,
+                program,
+                &(uniforms, self.1).to_uniforms(),
+                &mesh.draw_params(draw_params),
+            )
+            .map_err(|error| DrawError::Instance { index: 0, error })?;
+
+        Ok(DrawStats {
+            draw_calls: 1,
+            instances: 1,
+            ..Default::default()
+        })
     }
 }
@@ -0,0 +1,136 @@
+//! Basic objects with a texture and a tangent-space normal map.
+//!
+//! The normal map perturbs the interpolated normal in the fragment shader,
+//! adding surface detail without needing more triangles. The core outputs the
+//! perturbed normal as `f_normal`, which the lighting transforms then use in
+//! place of `v_world_normal`. Objects drawn with the other cores are lit with
+//! their interpolated normal, as before.
+//!
+//! As in `textured`, all instances share the textures given in `Params`.
+
+use nalgebra as na;
+
+use crate::scene::SceneCore;
+use crate::{basic_obj, shader, Context, HasTransform, HasTransformMut};
+
+#[derive(Clone)]
+pub struct Params<'a> {
+    pub texture: &'a glium::texture::SrgbTexture2d,
+
+    /// Tangent-space normals, with the components mapped from [-1, 1] to
+    /// [0, 1]. Must not be sRGB.
+    pub normal_map: &'a glium::texture::Texture2d,
+}
+
+impl_uniform_input!(
+    Params<'a>,
+    self => {
+        normal_mapped_texture: &'a glium::texture::SrgbTexture2d = self.texture,
+        normal_mapped_normal_map: &'a glium::texture::Texture2d = self.normal_map,
+    },
+);
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub transform: na::Matrix4<f32>,
+
+    /// Color that the texture is multiplied with.
+    pub color: na::Vector4<f32>,
+
+    /// Color that the lit color is multiplied with after lighting, e.g. for
+    /// color variants of one texture. See `basic_obj::Instance::tint`.
+    pub tint: na::Vector4<f32>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            transform: na::Matrix4::identity(),
+            color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            tint: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl_instance_input!(
+    Instance,
+    self => {
+        instance_transform: [[f32; 4]; 4] = self.transform,
+        instance_color: [f32; 4] = self.color,
+        instance_tint: [f32; 4] = self.tint,
+    },
+);
+
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
+const V_WORLD_TANGENT: (&str, shader::VertexOutDef) = (
+    "v_world_tangent",
+    shader::VertexOutDef(shader::Type::FloatVec4, shader::VertexOutQualifier::Smooth),
+);
+
+pub struct Core;
+
+impl SceneCore for Core {
+    type Params = Params<'static>;
+    type Instance = Instance;
+    type Vertex = basic_obj::Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, Self::Params), Instance, basic_obj::Vertex> {
+        let vertex = shader::VertexCore::empty()
+            .with_out(
+                shader::defs::V_WORLD_NORMAL,
+                "normalize(transpose(inverse(mat3(instance_transform))) * normal)",
+            )
+            .with_out(
+                V_WORLD_TANGENT,
+                "vec4(normalize(mat3(instance_transform) * tangent.xyz), tangent.w)",
+            )
+            .with_out(
+                shader::defs::V_WORLD_POS,
+                "instance_transform * vec4(position, 1.0)",
+            )
+            .with_out(shader::defs::V_COLOR, "instance_color * color")
+            .with_out(shader::defs::V_TINT, "instance_tint")
+            .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+            .with_out(
+                shader::defs::V_POS,
+                "context_camera_projection * context_camera_view * v_world_pos",
+            );
+
+        let fragment = shader::FragmentCore::empty()
+            .with_in_def(shader::defs::V_WORLD_NORMAL)
+            .with_in_def(V_WORLD_TANGENT)
+            .with_in_def(shader::defs::V_COLOR)
+            .with_in_def(shader::defs::V_TINT)
+            .with_in_def(shader::defs::V_TEX_COORD)
+            .with_body(
+                "
+                vec3 n = normalize(v_world_normal);
+                vec3 t = normalize(v_world_tangent.xyz - n * dot(n, v_world_tangent.xyz));
+                vec3 b = cross(n, t) * v_world_tangent.w;
+                mat3 tbn = mat3(t, b, n);
+
+                vec3 tangent_normal =
+                    texture(normal_mapped_normal_map, v_tex_coord).xyz * 2.0 - 1.0;
+                ",
+            )
+            .with_out(shader::defs::F_NORMAL, "normalize(tbn * tangent_normal)")
+            .with_out(
+                shader::defs::F_COLOR,
+                "v_color * texture(normal_mapped_texture, v_tex_coord)",
+            )
+            .with_out(shader::defs::F_TINT, "v_tint");
+
+        shader::Core { vertex, fragment }
+    }
+}
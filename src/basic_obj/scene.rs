@@ -1,12 +1,18 @@
 use nalgebra as na;
 
 use crate::scene::SceneCore;
-use crate::{basic_obj, shader, Context};
+use crate::{basic_obj, shader, Context, HasTransform, HasTransformMut, Material};
 
 #[derive(Clone, Debug)]
 pub struct Instance {
     pub transform: na::Matrix4<f32>,
     pub color: na::Vector4<f32>,
+    pub material: Material,
+
+    /// Color that the lit color is multiplied with after lighting, e.g. for
+    /// variants of shared objects such as team colors. Unlike `color`, this
+    /// also tints specular highlights. See `shader::defs::F_TINT`.
+    pub tint: na::Vector4<f32>,
 }
 
 impl Default for Instance {
@@ -14,6 +20,8 @@ impl Default for Instance {
         Self {
             transform: na::Matrix4::identity(),
             color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            material: Material::default(),
+            tint: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
         }
     }
 }
@@ -23,9 +31,54 @@ impl_instance_input!(
     self => {
         instance_transform: [[f32; 4]; 4] = self.transform,
         instance_color: [f32; 4] = self.color,
+        instance_ambient: f32 = self.material.ambient,
+        instance_diffuse: f32 = self.material.diffuse,
+        instance_specular: [f32; 3] = self.material.specular,
+        instance_shininess: f32 = self.material.shininess,
+        instance_tint: [f32; 4] = self.tint,
     },
 );
 
+impl Instance {
+    /// Transparent instances need to be drawn separately, see
+    /// `PlainScenePassStep::draw_transparent`.
+    pub fn is_transparent(&self) -> bool {
+        self.color.w * self.tint.w < 1.0
+    }
+}
+
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
+const V_AMBIENT: (&str, shader::VertexOutDef) = (
+    "v_ambient",
+    shader::VertexOutDef(shader::Type::Float, shader::VertexOutQualifier::Flat),
+);
+
+const V_DIFFUSE: (&str, shader::VertexOutDef) = (
+    "v_diffuse",
+    shader::VertexOutDef(shader::Type::Float, shader::VertexOutQualifier::Flat),
+);
+
+const V_SPECULAR: (&str, shader::VertexOutDef) = (
+    "v_specular",
+    shader::VertexOutDef(shader::Type::FloatVec3, shader::VertexOutQualifier::Flat),
+);
+
+const V_SHININESS: (&str, shader::VertexOutDef) = (
+    "v_shininess",
+    shader::VertexOutDef(shader::Type::Float, shader::VertexOutQualifier::Flat),
+);
+
 pub struct Core;
 
 impl SceneCore for Core {
@@ -44,7 +97,12 @@ impl SceneCore for Core {
                 shader::defs::V_WORLD_POS,
                 "instance_transform * vec4(position, 1.0)",
             )
-            .with_out(shader::defs::V_COLOR, "instance_color")
+            .with_out(shader::defs::V_COLOR, "instance_color * color")
+            .with_out(shader::defs::V_TINT, "instance_tint")
+            .with_out(V_AMBIENT, "instance_ambient")
+            .with_out(V_DIFFUSE, "instance_diffuse")
+            .with_out(V_SPECULAR, "instance_specular")
+            .with_out(V_SHININESS, "instance_shininess")
             .with_out(
                 shader::defs::V_POS,
                 "context_camera_projection * context_camera_view * v_world_pos",
@@ -52,7 +110,17 @@ impl SceneCore for Core {
 
         let fragment = shader::FragmentCore::empty()
             .with_in_def(shader::defs::V_COLOR)
-            .with_out(shader::defs::F_COLOR, "v_color");
+            .with_in_def(shader::defs::V_TINT)
+            .with_in_def(V_AMBIENT)
+            .with_in_def(V_DIFFUSE)
+            .with_in_def(V_SPECULAR)
+            .with_in_def(V_SHININESS)
+            .with_out(shader::defs::F_COLOR, "v_color")
+            .with_out(shader::defs::F_TINT, "v_tint")
+            .with_out(shader::defs::F_AMBIENT, "v_ambient")
+            .with_out(shader::defs::F_DIFFUSE, "v_diffuse")
+            .with_out(shader::defs::F_SPECULAR, "v_specular")
+            .with_out(shader::defs::F_SHININESS, "v_shininess");
 
         shader::Core { vertex, fragment }
     }
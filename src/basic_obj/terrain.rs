@@ -0,0 +1,139 @@
+//! Terrain that is displaced by a heightmap on the GPU.
+//!
+//! Terrain is drawn with the mesh of `BasicObj::Plane`, which spans [0, 1]
+//! in x and y. In the vertex shader, each vertex is moved along z by the red
+//! channel of the heightmap, and normals are approximated from the slopes
+//! between neighboring texels. The instance transform then places and scales
+//! the terrain in the world.
+
+use nalgebra as na;
+
+use crate::scene::SceneCore;
+use crate::{basic_obj, shader, Aabb, Context, HasTransform, HasTransformMut};
+
+#[derive(Clone)]
+pub struct Params<'a> {
+    /// Heights in the red channel, from zero to one.
+    pub heightmap: &'a glium::texture::Texture2d,
+
+    /// Height of the terrain where the heightmap is one, in model space.
+    pub height_scale: f32,
+}
+
+impl_uniform_input!(
+    Params<'a>,
+    self => {
+        terrain_heightmap: &'a glium::texture::Texture2d = self.heightmap,
+        terrain_height_scale: f32 = self.height_scale,
+    },
+);
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub transform: na::Matrix4<f32>,
+    pub color: na::Vector4<f32>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            transform: na::Matrix4::identity(),
+            color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl_instance_input!(
+    Instance,
+    self => {
+        instance_transform: [[f32; 4]; 4] = self.transform,
+        instance_color: [f32; 4] = self.color,
+    },
+);
+
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
+/// Radius of a sphere around the origin that contains the terrain in model
+/// space, i.e. the plane of `resources` displaced by up to `height_scale`.
+/// Use this instead of `Resources::bounding_radius` for culling terrain.
+/// Returns `None` if the plane has been disabled in `ResourcesBuilder`.
+pub fn bounding_radius(resources: &basic_obj::Resources, height_scale: f32) -> Option<f32> {
+    let plane = resources.aabb(basic_obj::BasicObj::Plane)?;
+    let displaced = Aabb {
+        min: plane.min + na::Vector3::z() * height_scale.min(0.0),
+        max: plane.max + na::Vector3::z() * height_scale.max(0.0),
+    };
+
+    let radius = displaced
+        .corners()
+        .iter()
+        .map(|corner| corner.coords.norm())
+        .fold(0.0, f32::max);
+
+    Some(radius)
+}
+
+const TERRAIN_DEFS: &str = "
+    float terrain_height(vec2 uv) {
+        return textureLod(terrain_heightmap, uv, 0.0).r * terrain_height_scale;
+    }
+";
+
+pub struct Core;
+
+impl SceneCore for Core {
+    type Params = Params<'static>;
+    type Instance = Instance;
+    type Vertex = basic_obj::Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, Self::Params), Instance, basic_obj::Vertex> {
+        // The plane's texture coordinates equal its x and y coordinates, so
+        // slopes in texture space are slopes in model space.
+        let vertex = shader::VertexCore::empty()
+            .with_defs(TERRAIN_DEFS)
+            .with_body(
+                "
+                vec2 texel = 1.0 / vec2(textureSize(terrain_heightmap, 0));
+                vec2 dx = vec2(texel.x, 0.0);
+                vec2 dy = vec2(0.0, texel.y);
+
+                float slope_x = (terrain_height(tex_coord + dx) - terrain_height(tex_coord - dx))
+                    / (2.0 * texel.x);
+                float slope_y = (terrain_height(tex_coord + dy) - terrain_height(tex_coord - dy))
+                    / (2.0 * texel.y);
+
+                vec3 terrain_normal = vec3(-slope_x, -slope_y, 1.0);
+                vec3 terrain_position = vec3(position.xy, terrain_height(tex_coord));
+                ",
+            )
+            .with_out(
+                shader::defs::V_WORLD_NORMAL,
+                "normalize(transpose(inverse(mat3(instance_transform))) * terrain_normal)",
+            )
+            .with_out(
+                shader::defs::V_WORLD_POS,
+                "instance_transform * vec4(terrain_position, 1.0)",
+            )
+            .with_out(shader::defs::V_COLOR, "instance_color * color")
+            .with_out(
+                shader::defs::V_POS,
+                "context_camera_projection * context_camera_view * v_world_pos",
+            );
+
+        let fragment = shader::FragmentCore::empty()
+            .with_in_def(shader::defs::V_COLOR)
+            .with_out(shader::defs::F_COLOR, "v_color");
+
+        shader::Core { vertex, fragment }
+    }
+}
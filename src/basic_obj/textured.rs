@@ -0,0 +1,221 @@
+//! Basic objects with a texture from an atlas.
+//!
+//! All instances share one texture atlas, which is given in `Params`. Each
+//! instance selects its own region of the atlas, so that differently textured
+//! objects can still be drawn in a single instanced call.
+
+use nalgebra as na;
+
+use glium::texture::{RawImage2d, SrgbTexture2d, Texture2d};
+use glium::uniforms::{AsUniformValue, SamplerBehavior, UniformType, UniformValue};
+
+use crate::scene::SceneCore;
+use crate::shader::input::StaticUniformType;
+use crate::{
+    basic_obj, shader, Context, CreationError, HasTransform, HasTransformMut, SamplerConfig,
+};
+
+/// The texture of an atlas, together with the color space it is stored in.
+#[derive(Clone, Copy, Debug)]
+pub enum Atlas<'a> {
+    /// Colors in sRGB space, e.g. from an image editor, which are converted
+    /// to linear space when sampling. This is right for most color textures.
+    Srgb(&'a SrgbTexture2d),
+
+    /// Values that are sampled as they are, e.g. for textures that already
+    /// are in linear space or that hold data.
+    Linear(&'a Texture2d),
+}
+
+#[derive(Clone)]
+pub struct Params<'a> {
+    pub atlas: Atlas<'a>,
+    pub sampler: SamplerConfig,
+}
+
+impl_uniform_input!(
+    Params<'a>,
+    self => {
+        textured_atlas: AtlasSampler<'a> = AtlasSampler(self.atlas, self.sampler.into()),
+    },
+);
+
+/// Uniform value for sampling an `Atlas` of either color space.
+#[derive(Clone, Copy, Debug)]
+struct AtlasSampler<'a>(Atlas<'a>, SamplerBehavior);
+
+impl<'a> AsUniformValue for AtlasSampler<'a> {
+    fn as_uniform_value(&self) -> UniformValue {
+        match self.0 {
+            Atlas::Srgb(texture) => UniformValue::SrgbTexture2d(texture, Some(self.1)),
+            Atlas::Linear(texture) => UniformValue::Texture2d(texture, Some(self.1)),
+        }
+    }
+}
+
+impl<'a> StaticUniformType for AtlasSampler<'a> {
+    const TYPE: UniformType = UniformType::Sampler2d;
+}
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub transform: na::Matrix4<f32>,
+
+    /// Color that the texture is multiplied with.
+    pub color: na::Vector4<f32>,
+
+    /// Color that the lit color is multiplied with after lighting, e.g. for
+    /// color variants of one texture. See `basic_obj::Instance::tint`.
+    pub tint: na::Vector4<f32>,
+
+    /// Region of the atlas that is mapped onto the object, given as the
+    /// lower-left corner followed by the size, in texture coordinates.
+    pub atlas_region: na::Vector4<f32>,
+
+    /// Velocity in texture coordinates per second, by which the texture
+    /// scrolls over the object with `Context::elapsed_time`. The texture
+    /// wraps around within its atlas region.
+    pub texture_scroll: na::Vector2<f32>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            transform: na::Matrix4::identity(),
+            color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            tint: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            atlas_region: na::Vector4::new(0.0, 0.0, 1.0, 1.0),
+            texture_scroll: na::Vector2::zeros(),
+        }
+    }
+}
+
+impl_instance_input!(
+    Instance,
+    self => {
+        instance_transform: [[f32; 4]; 4] = self.transform,
+        instance_color: [f32; 4] = self.color,
+        instance_tint: [f32; 4] = self.tint,
+        instance_atlas_region: [f32; 4] = self.atlas_region,
+        instance_texture_scroll: [f32; 2] = self.texture_scroll,
+    },
+);
+
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
+const V_ATLAS_REGION: (&str, shader::VertexOutDef) = (
+    "v_atlas_region",
+    shader::VertexOutDef(shader::Type::FloatVec4, shader::VertexOutQualifier::Flat),
+);
+
+pub struct Core;
+
+impl SceneCore for Core {
+    type Params = Params<'static>;
+    type Instance = Instance;
+    type Vertex = basic_obj::Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, Self::Params), Instance, basic_obj::Vertex> {
+        let vertex = shader::VertexCore::empty()
+            .with_out(
+                shader::defs::V_WORLD_NORMAL,
+                "normalize(transpose(inverse(mat3(instance_transform))) * normal)",
+            )
+            .with_out(
+                shader::defs::V_WORLD_POS,
+                "instance_transform * vec4(position, 1.0)",
+            )
+            .with_out(shader::defs::V_COLOR, "instance_color * color")
+            .with_out(shader::defs::V_TINT, "instance_tint")
+            .with_out(
+                shader::defs::V_TEX_COORD,
+                "tex_coord + context_elapsed_time * instance_texture_scroll",
+            )
+            .with_out(V_ATLAS_REGION, "instance_atlas_region")
+            .with_out(
+                shader::defs::V_POS,
+                "context_camera_projection * context_camera_view * v_world_pos",
+            );
+
+        let fragment = shader::FragmentCore::empty()
+            .with_in_def(shader::defs::V_COLOR)
+            .with_in_def(shader::defs::V_TINT)
+            .with_in_def(shader::defs::V_TEX_COORD)
+            .with_in_def(V_ATLAS_REGION)
+            .with_out(
+                shader::defs::F_COLOR,
+                "v_color * texture(
+                    textured_atlas,
+                    v_atlas_region.xy + fract(v_tex_coord) * v_atlas_region.zw
+                )",
+            )
+            .with_out(shader::defs::F_TINT, "v_tint");
+
+        shader::Core { vertex, fragment }
+    }
+}
+
+/// Procedural patterns for `debug_texture`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugPattern {
+    /// Alternating dark and light cells, with `num_cells` cells per side.
+    /// Light cells are tinted by their texture coordinates, so that
+    /// flipped or rotated mappings are visible.
+    Checkerboard { num_cells: u32 },
+
+    /// Texture coordinates as colors, with u in red and v in green.
+    UvGradient,
+}
+
+/// Creates a square texture of `size` texels with a debug pattern, for
+/// checking texture coordinates without needing image files.
+///
+/// The texture holds linear colors, so it is used as `Atlas::Linear`.
+pub fn debug_texture<F: glium::backend::Facade>(
+    facade: &F,
+    size: u32,
+    pattern: DebugPattern,
+) -> Result<Texture2d, CreationError> {
+    let size = size.max(1);
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+
+    // Rows are stored from the bottom up, i.e. in the direction of v
+    for y in 0..size {
+        for x in 0..size {
+            let u = (x as f32 + 0.5) / size as f32;
+            let v = (y as f32 + 0.5) / size as f32;
+
+            let color = match pattern {
+                DebugPattern::Checkerboard { num_cells } => {
+                    let num_cells = num_cells.max(1);
+                    let cell_x = x * num_cells / size;
+                    let cell_y = y * num_cells / size;
+
+                    if (cell_x + cell_y) % 2 == 0 {
+                        [0.2, 0.2, 0.2]
+                    } else {
+                        [0.5 + 0.5 * u, 0.5 + 0.5 * v, 0.5]
+                    }
+                }
+                DebugPattern::UvGradient => [u, v, 0.0],
+            };
+
+            data.extend(color.iter().map(|c| (c * 255.0).round() as u8));
+            data.push(255);
+        }
+    }
+
+    let image = RawImage2d::from_raw_rgba(data, (size, size));
+
+    Ok(Texture2d::new(facade, image)?)
+}
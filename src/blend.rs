@@ -0,0 +1,62 @@
+use glium::{Blend, BlendingFunction, LinearBlendingFactor};
+
+/// How the colors of an instance are combined with what has been drawn
+/// before.
+///
+/// Blending applies per draw, so instances with different modes need to be
+/// kept in separate render lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite the target.
+    Opaque,
+
+    /// Interpolate by the alpha of the source color, which is given
+    /// independently of alpha.
+    Alpha,
+
+    /// Like `Alpha`, but the source color has already been multiplied by its
+    /// alpha. This avoids dark fringes at soft edges of textures that are
+    /// filtered.
+    PremultipliedAlpha,
+
+    /// Add the source color, weighted by its alpha, e.g. for fire or glow.
+    /// The order of drawing does not matter.
+    Additive,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+impl BlendMode {
+    pub fn to_blend(self) -> Blend {
+        match self {
+            BlendMode::Opaque => Blend::default(),
+            BlendMode::Alpha => Blend::alpha_blending(),
+            BlendMode::PremultipliedAlpha => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::OneMinusSourceAlpha,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::OneMinusSourceAlpha,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            BlendMode::Additive => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+        }
+    }
+}
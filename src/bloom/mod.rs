@@ -0,0 +1,258 @@
+//! Bloom as a full-screen postprocessing effect.
+//!
+//! Bright parts of the input are extracted, blurred at a few successively
+//! halved resolutions and then added back onto the input. Blurring the
+//! smaller levels gives a wide glow at little cost.
+
+mod shaders;
+
+use log::info;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{uniform, Program, Surface, Texture2d};
+
+use crate::pipeline::glow;
+use crate::{shader, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Minimal luminance of pixels that contribute to bloom.
+    pub threshold: f32,
+
+    /// Factor for the blurred bright pixels when adding them to the input.
+    pub intensity: f32,
+
+    /// Number of downsampled levels, starting at half resolution.
+    pub num_levels: usize,
+
+    /// Number of horizontal and vertical blur passes per level.
+    pub num_blur_passes: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 1.0,
+            num_levels: 4,
+            num_blur_passes: 1,
+        }
+    }
+}
+
+/// A level holds the blurred texture and a back texture for blurring.
+struct Level {
+    texture: Texture2d,
+    texture_back: Texture2d,
+}
+
+pub struct Bloom {
+    config: Config,
+    levels: Vec<Level>,
+
+    extract_program: Program,
+    blur_program: Program,
+    scaled_copy_program: Program,
+
+    screen_quad: ScreenQuad,
+}
+
+impl Bloom {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        config: &Config,
+        target_size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        info!("Creating bloom textures");
+        let levels = Self::create_levels(facade, config, target_size)?;
+
+        info!("Creating bloom programs");
+        let extract_program =
+            shaders::extract_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+        let blur_program =
+            glow::shaders::blur_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+        let scaled_copy_program =
+            shaders::scaled_copy_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        info!("Creating screen quad");
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(Bloom {
+            config: config.clone(),
+            levels,
+            extract_program,
+            blur_program,
+            scaled_copy_program,
+            screen_quad,
+        })
+    }
+
+    pub fn on_target_resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        self.levels = Self::create_levels(facade, &self.config, target_size)?;
+
+        Ok(())
+    }
+
+    /// Draws `texture` with bloom onto the whole `target`.
+    ///
+    /// `texture` should contain linear colors, e.g. the `color_texture` of a
+    /// `RenderTarget`. In order for only emissive objects to bloom, their
+    /// colors need to exceed `Config::threshold`, which requires a float
+    /// texture.
+    pub fn draw<F: glium::backend::Facade, S: Surface>(
+        &self,
+        facade: &F,
+        texture: &Texture2d,
+        target: &mut S,
+    ) -> Result<(), DrawError> {
+        if let Some(first_level) = self.levels.first() {
+            let mut framebuffer = SimpleFrameBuffer::new(facade, &first_level.texture)?;
+            framebuffer.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                &self.extract_program,
+                &uniform! {
+                    input_texture: Self::sampler(texture),
+                    threshold: self.config.threshold,
+                },
+                &Default::default(),
+            )?;
+        }
+
+        for (i, level) in self.levels.iter().enumerate() {
+            if i > 0 {
+                self.draw_scaled_copy(
+                    &self.levels[i - 1].texture,
+                    1.0,
+                    &Default::default(),
+                    &mut SimpleFrameBuffer::new(facade, &level.texture)?,
+                )?;
+            }
+
+            self.blur_level(facade, level)?;
+        }
+
+        self.draw_scaled_copy(texture, 1.0, &Default::default(), target)?;
+
+        let additive = glium::DrawParameters {
+            blend: glium::Blend {
+                color: glium::BlendingFunction::Addition {
+                    source: glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::One,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let factor = self.config.intensity / self.levels.len().max(1) as f32;
+
+        for level in self.levels.iter() {
+            self.draw_scaled_copy(&level.texture, factor, &additive, target)?;
+        }
+
+        Ok(())
+    }
+
+    fn blur_level<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+        level: &Level,
+    ) -> Result<(), DrawError> {
+        let mut buffer = SimpleFrameBuffer::new(facade, &level.texture)?;
+        let mut buffer_back = SimpleFrameBuffer::new(facade, &level.texture_back)?;
+
+        for _ in 0..self.config.num_blur_passes {
+            buffer_back.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                &self.blur_program,
+                &uniform! {
+                    horizontal: false,
+                    glow_texture: Self::sampler(&level.texture),
+                },
+                &Default::default(),
+            )?;
+
+            buffer.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                &self.blur_program,
+                &uniform! {
+                    horizontal: true,
+                    glow_texture: Self::sampler(&level.texture_back),
+                },
+                &Default::default(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_scaled_copy<S: Surface>(
+        &self,
+        texture: &Texture2d,
+        factor: f32,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<(), DrawError> {
+        target.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.scaled_copy_program,
+            &uniform! {
+                input_texture: Self::sampler(texture),
+                factor: factor,
+            },
+            draw_params,
+        )?;
+
+        Ok(())
+    }
+
+    fn sampler(texture: &Texture2d) -> Sampler<Texture2d> {
+        Sampler::new(texture)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp)
+    }
+
+    fn create_levels<F: glium::backend::Facade>(
+        facade: &F,
+        config: &Config,
+        target_size: (u32, u32),
+    ) -> Result<Vec<Level>, CreationError> {
+        let mut levels = Vec::new();
+        let mut size = target_size;
+
+        for _ in 0..config.num_levels {
+            size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+
+            levels.push(Level {
+                texture: Self::create_texture(facade, size)?,
+                texture_back: Self::create_texture(facade, size)?,
+            });
+        }
+
+        Ok(levels)
+    }
+
+    fn create_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::F16F16F16F16,
+            glium::texture::MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+}
@@ -0,0 +1,53 @@
+use glium::uniforms::UniformType;
+
+use crate::{screen_quad, shader};
+
+fn screen_quad_vertex_core() -> shader::VertexCore<(), (), screen_quad::Vertex> {
+    shader::VertexCore::empty()
+        .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+        .with_out(shader::defs::V_POS, "position")
+}
+
+/// Shader core for extracting the parts of `input_texture` whose luminance is
+/// above `threshold`.
+pub fn extract_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("input_texture", UniformType::Sampler2d)
+        .with_extra_uniform("threshold", UniformType::Float)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_body(
+            "
+            vec3 color = texture(input_texture, v_tex_coord).rgb;
+            float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+            ",
+        )
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(color * step(threshold, luminance), 1.0)",
+        );
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
+
+/// Shader core for copying `input_texture`, scaled by `factor`.
+///
+/// This is used both for downsampling and, with additive blending, for
+/// compositing the blurred levels onto the target.
+pub fn scaled_copy_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("input_texture", UniformType::Sampler2d)
+        .with_extra_uniform("factor", UniformType::Float)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(factor * texture(input_texture, v_tex_coord).rgb, 1.0)",
+        );
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
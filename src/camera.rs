@@ -1,9 +1,54 @@
 use nalgebra as na;
 
+use crate::Frustum;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fovy: f32,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    },
+
+    /// An arbitrary projection matrix.
+    Custom(na::Matrix4<f32>),
+}
+
+impl Projection {
+    pub fn to_matrix(&self) -> na::Matrix4<f32> {
+        match *self {
+            Projection::Perspective {
+                fovy,
+                aspect,
+                near,
+                far,
+            } => na::Perspective3::new(aspect, fovy, near, far).to_homogeneous(),
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => na::Matrix4::new_orthographic(left, right, bottom, top, near, far),
+            Projection::Custom(matrix) => matrix,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub viewport_size: na::Vector2<f32>,
-    pub projection: na::Matrix4<f32>,
+    pub projection: Projection,
     pub view: na::Matrix4<f32>,
 }
 
@@ -11,13 +56,13 @@ impl_uniform_input!(
     Camera,
     self => {
         camera_viewport_size: [f32; 2] = self.viewport_size,
-        camera_projection: [[f32; 4]; 4] = self.projection,
+        camera_projection: [[f32; 4]; 4] = self.projection_matrix(),
         camera_view: [[f32; 4]; 4] = self.view,
     },
 );
 
 impl Camera {
-    pub fn new(viewport_size: na::Vector2<f32>, projection: na::Matrix4<f32>) -> Camera {
+    pub fn new(viewport_size: na::Vector2<f32>, projection: Projection) -> Camera {
         Camera {
             viewport_size,
             projection,
@@ -25,8 +70,175 @@ impl Camera {
         }
     }
 
+    /// Creates a camera with a perspective projection whose aspect ratio
+    /// matches the viewport.
+    pub fn perspective(viewport_size: na::Vector2<f32>, fovy: f32, near: f32, far: f32) -> Camera {
+        Camera::new(
+            viewport_size,
+            Projection::Perspective {
+                fovy,
+                aspect: viewport_size.x / viewport_size.y,
+                near,
+                far,
+            },
+        )
+    }
+
+    pub fn orthographic(
+        viewport_size: na::Vector2<f32>,
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Camera {
+        Camera::new(
+            viewport_size,
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            },
+        )
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    /// Sets the viewport size, adjusting the aspect ratio of a perspective
+    /// projection to match. Other projections are kept as they are.
+    ///
+    /// Call this when the window is resized, e.g. on
+    /// `glutin::WindowEvent::Resized`, or each frame with the dimensions of
+    /// the target. The pipeline itself resizes its buffers automatically in
+    /// `Pipeline::start_frame`.
+    pub fn set_viewport_size(&mut self, viewport_size: na::Vector2<f32>) {
+        self.viewport_size = viewport_size;
+
+        if let Projection::Perspective { aspect, .. } = &mut self.projection {
+            *aspect = viewport_size.x / viewport_size.y;
+        }
+    }
+
+    /// Sets the distances of the near and far clipping planes. Custom
+    /// projections are kept as they are.
+    ///
+    /// Keeping `far / near` small improves depth precision. For scenes that
+    /// span huge distances, see also `Context::log_depth`.
+    pub fn set_clip_planes(&mut self, new_near: f32, new_far: f32) {
+        match &mut self.projection {
+            Projection::Perspective { near, far, .. }
+            | Projection::Orthographic { near, far, .. } => {
+                *near = new_near;
+                *far = new_far;
+            }
+            Projection::Custom(_) => (),
+        }
+    }
+
+    /// Distance of the near clipping plane, if the projection is not custom.
+    pub fn near(&self) -> Option<f32> {
+        match self.projection {
+            Projection::Perspective { near, .. } | Projection::Orthographic { near, .. } => {
+                Some(near)
+            }
+            Projection::Custom(_) => None,
+        }
+    }
+
+    /// Distance of the far clipping plane, if the projection is not custom.
+    pub fn far(&self) -> Option<f32> {
+        match self.projection {
+            Projection::Perspective { far, .. } | Projection::Orthographic { far, .. } => Some(far),
+            Projection::Custom(_) => None,
+        }
+    }
+
+    /// Sets the view matrix so that the camera is at `eye`, looking at
+    /// `target`.
+    pub fn look_at(
+        &mut self,
+        eye: &na::Point3<f32>,
+        target: &na::Point3<f32>,
+        up: &na::Vector3<f32>,
+    ) {
+        self.view = na::Matrix4::look_at_rh(eye, target, up);
+    }
+
+    pub fn projection_matrix(&self) -> na::Matrix4<f32> {
+        self.projection.to_matrix()
+    }
+
+    /// The volume that is visible through the camera, in world space.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&(self.projection_matrix() * self.view))
+    }
+
+    /// Returns false iff the sphere lies completely outside of the camera's
+    /// frustum, e.g. for visibility checks in gameplay code. See
+    /// `Frustum::intersects_sphere`.
+    ///
+    /// When checking many spheres, get the `frustum` once instead.
+    pub fn frustum_contains_sphere(&self, center: &na::Point3<f32>, radius: f32) -> bool {
+        self.frustum().intersects_sphere(center, radius)
+    }
+
+    /// Position of the camera in world space.
+    pub fn eye(&self) -> na::Point3<f32> {
+        self.view
+            .try_inverse()
+            .map_or_else(na::Point3::origin, |inverse| {
+                inverse.transform_point(&na::Point3::origin())
+            })
+    }
+
+    /// The same camera in a world that has been moved so that the eye is at
+    /// the origin. See `Context::relative_to_eye`.
+    pub fn relative_to_eye(&self) -> Camera {
+        Camera {
+            view: self.view * na::Matrix4::new_translation(&self.eye().coords),
+            ..self.clone()
+        }
+    }
+
+    /// Moves the model transformation `transform` of an instance into the
+    /// world of `relative_to_eye`, by subtracting the eye from its
+    /// translation.
+    pub fn transform_relative_to_eye(&self, transform: &na::Matrix4<f32>) -> na::Matrix4<f32> {
+        na::Matrix4::new_translation(&-self.eye().coords) * transform
+    }
+
+    /// Corners of the camera's frustum in world space. The four corners of
+    /// the near plane come first, followed by those of the far plane, each
+    /// in counter-clockwise order starting at the bottom left.
+    pub fn frustum_corners(&self) -> [na::Point3<f32>; 8] {
+        let transform = (self.projection_matrix() * self.view)
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::zeros);
+        let corner = |x: f32, y: f32, z: f32| {
+            let result = transform * na::Vector4::new(x, y, z, 1.0);
+            na::Point3::from(result.fixed_rows::<na::U3>(0) / result.w)
+        };
+
+        [
+            corner(-1.0, -1.0, -1.0),
+            corner(1.0, -1.0, -1.0),
+            corner(1.0, 1.0, -1.0),
+            corner(-1.0, 1.0, -1.0),
+            corner(-1.0, -1.0, 1.0),
+            corner(1.0, -1.0, 1.0),
+            corner(1.0, 1.0, 1.0),
+            corner(-1.0, 1.0, 1.0),
+        ]
+    }
+
     pub fn project_to_viewport(&self, p: &na::Point3<f32>) -> na::Point3<f32> {
-        let q = self.projection * self.view * na::Vector4::new(p.x, p.y, p.z, 1.0);
+        let q = self.projection_matrix() * self.view * na::Vector4::new(p.x, p.y, p.z, 1.0);
         let h = q.fixed_rows::<na::U3>(0) / q.w;
 
         na::Point3::new(
@@ -40,7 +252,7 @@ impl Camera {
         // As in:
         // https://www.nalgebra.org/rustdoc_glm/src/nalgebra_glm/ext/matrix_projection.rs.html#163
 
-        let transform = (self.projection * self.view)
+        let transform = (self.projection_matrix() * self.view)
             .try_inverse()
             .unwrap_or_else(na::Matrix4::zeros);
 
@@ -55,3 +267,225 @@ impl Camera {
         na::Point3::from(result.fixed_rows::<na::U3>(0) / result.w)
     }
 }
+
+/// Pitch is kept slightly below the poles, where the view would flip.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+fn clamp_pitch(pitch: f32) -> f32 {
+    pitch.max(-MAX_PITCH).min(MAX_PITCH)
+}
+
+/// Unit vector pointing in the direction given by `yaw` around the z axis
+/// and `pitch` above the xy plane.
+fn yaw_pitch_direction(yaw: f32, pitch: f32) -> na::Vector3<f32> {
+    na::Vector3::new(
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+    )
+}
+
+/// A camera that orbits around a focus point, e.g. for viewing objects with
+/// mouse dragging.
+///
+/// Yaw is measured around the z axis, starting at the x axis, and pitch is the
+/// angle above the xy plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitCamera {
+    pub focus: na::Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            focus: na::Point3::origin(),
+            yaw: 0.0,
+            pitch: std::f32::consts::PI / 4.0,
+            distance: 10.0,
+        }
+    }
+}
+
+impl OrbitCamera {
+    /// Changes yaw and pitch, keeping the camera away from the poles.
+    pub fn rotate(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.yaw += delta_yaw;
+        self.pitch = clamp_pitch(self.pitch + delta_pitch);
+    }
+
+    /// Multiplies the distance to the focus point by `factor`.
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance *= factor;
+    }
+
+    /// Position of the camera in world space.
+    pub fn eye(&self) -> na::Point3<f32> {
+        self.focus + yaw_pitch_direction(self.yaw, self.pitch) * self.distance
+    }
+
+    pub fn view(&self) -> na::Matrix4<f32> {
+        na::Matrix4::look_at_rh(&self.eye(), &self.focus, &na::Vector3::z())
+    }
+
+    /// Sets the view matrix of `camera` to look from our eye to the focus.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.look_at(&self.eye(), &self.focus, &na::Vector3::z());
+    }
+}
+
+/// A first-person camera that flies freely through the scene.
+///
+/// Uses the same conventions for yaw and pitch as `OrbitCamera`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyCamera {
+    pub position: na::Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            position: na::Point3::origin(),
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl FlyCamera {
+    /// Direction that the camera is looking in.
+    pub fn forward(&self) -> na::Vector3<f32> {
+        yaw_pitch_direction(self.yaw, self.pitch)
+    }
+
+    /// Direction to the right of the camera, parallel to the xy plane.
+    pub fn right(&self) -> na::Vector3<f32> {
+        na::Vector3::new(self.yaw.sin(), -self.yaw.cos(), 0.0)
+    }
+
+    pub fn move_forward(&mut self, delta: f32) {
+        self.position += self.forward() * delta;
+    }
+
+    /// Moves to the right, or to the left if `delta` is negative.
+    pub fn strafe(&mut self, delta: f32) {
+        self.position += self.right() * delta;
+    }
+
+    /// Changes yaw and pitch, keeping the camera away from the poles.
+    pub fn turn(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.yaw += delta_yaw;
+        self.pitch = clamp_pitch(self.pitch + delta_pitch);
+    }
+
+    pub fn view(&self) -> na::Matrix4<f32> {
+        na::Matrix4::look_at_rh(
+            &self.position,
+            &(self.position + self.forward()),
+            &na::Vector3::z(),
+        )
+    }
+
+    /// Sets the view matrix of `camera` to our view.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.view = self.view();
+    }
+}
+
+/// Smooths the movement of another camera, e.g. an `OrbitCamera`, and adds a
+/// decaying shake, e.g. for impacts in games.
+///
+/// Each frame, pass the eye and target of the other camera to `update`, and
+/// then `apply` the result to the `Camera` that is used for rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothCamera {
+    /// Rate of the exponential smoothing per second. Larger values follow
+    /// more closely.
+    pub smoothing: f32,
+
+    /// Rate per second at which the shake decays exponentially.
+    pub shake_decay: f32,
+
+    /// Offset of the eye and target in world space at full shake.
+    pub max_shake_offset: f32,
+
+    /// How quickly the shake changes direction, in oscillations per second.
+    pub shake_frequency: f32,
+
+    eye: na::Point3<f32>,
+    target: na::Point3<f32>,
+    shake: f32,
+    time: f32,
+}
+
+impl SmoothCamera {
+    /// Starts at rest, looking from `eye` at `target`.
+    pub fn new(eye: na::Point3<f32>, target: na::Point3<f32>) -> Self {
+        Self {
+            smoothing: 10.0,
+            shake_decay: 3.0,
+            max_shake_offset: 0.5,
+            shake_frequency: 15.0,
+            eye,
+            target,
+            shake: 0.0,
+            time: 0.0,
+        }
+    }
+
+    /// Adds `amount` to the shake, which is limited to one. The offset grows
+    /// with the square of the shake, so that small amounts stay subtle.
+    pub fn shake(&mut self, amount: f32) {
+        self.shake = (self.shake + amount).min(1.0).max(0.0);
+    }
+
+    /// Moves towards looking from `eye` at `target` and decays the shake,
+    /// after `dt` seconds have passed.
+    pub fn update(&mut self, dt: f32, eye: &na::Point3<f32>, target: &na::Point3<f32>) {
+        let factor = 1.0 - (-self.smoothing * dt).exp();
+
+        self.eye += (eye - self.eye) * factor;
+        self.target += (target - self.target) * factor;
+        self.shake *= (-self.shake_decay * dt).exp();
+        self.time += dt;
+    }
+
+    /// Position of the camera in world space, including the shake.
+    pub fn eye(&self) -> na::Point3<f32> {
+        self.eye + self.shake_offset()
+    }
+
+    /// The point that the camera looks at, including the shake.
+    pub fn target(&self) -> na::Point3<f32> {
+        self.target + self.shake_offset()
+    }
+
+    pub fn view(&self) -> na::Matrix4<f32> {
+        na::Matrix4::look_at_rh(&self.eye(), &self.target(), &na::Vector3::z())
+    }
+
+    /// Sets the view matrix of `camera` to our view.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.view = self.view();
+    }
+
+    fn shake_offset(&self) -> na::Vector3<f32> {
+        // Sums of sines with unrelated frequencies give a smooth signal that
+        // does not visibly repeat, without needing a noise function.
+        let t = self.time * self.shake_frequency * 2.0 * std::f32::consts::PI;
+        let noise = |a: f32, b: f32, phase: f32| {
+            ((a * t + phase).sin() + (b * t + 2.0 * phase).sin()) / 2.0
+        };
+
+        na::Vector3::new(
+            noise(1.0, 2.31, 0.0),
+            noise(1.13, 1.77, 1.7),
+            noise(0.91, 2.63, 3.1),
+        ) * self.max_shake_offset
+            * self.shake.powi(2)
+    }
+}
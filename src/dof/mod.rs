@@ -0,0 +1,207 @@
+//! Depth of field as a full-screen postprocessing effect.
+//!
+//! The input is blurred at half resolution and then mixed with the sharp
+//! input by the circle of confusion of each pixel, which grows with the
+//! distance of the pixel's depth from the focus.
+
+mod shaders;
+
+use log::info;
+
+use nalgebra as na;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::DepthTexture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{uniform, Program, Surface, Texture2d};
+
+use crate::shader::{self, ToUniform};
+use crate::{Context, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Distance from the camera at which the scene is sharpest.
+    pub focus_distance: f32,
+
+    /// Distance in front of and behind `focus_distance` that stays sharp.
+    /// The blur fades in over the same distance beyond that.
+    pub focus_range: f32,
+
+    /// Spacing of the blur samples in texels, where larger values give a
+    /// wider but coarser blur.
+    pub blur_strength: f32,
+
+    /// Number of horizontal and vertical blur passes.
+    pub num_blur_passes: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            focus_distance: 10.0,
+            focus_range: 5.0,
+            blur_strength: 1.5,
+            num_blur_passes: 2,
+        }
+    }
+}
+
+pub struct Dof {
+    /// The configuration can be changed between frames, e.g. to follow
+    /// the focus of the camera.
+    pub config: Config,
+
+    blur_texture: Texture2d,
+    blur_texture_back: Texture2d,
+
+    blur_program: Program,
+    composition_program: Program,
+
+    screen_quad: ScreenQuad,
+}
+
+impl Dof {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        config: &Config,
+        target_size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        info!("Creating depth of field textures");
+        let blur_texture = Self::create_texture(facade, target_size)?;
+        let blur_texture_back = Self::create_texture(facade, target_size)?;
+
+        info!("Creating depth of field programs");
+        let blur_program =
+            shaders::blur_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+        let composition_program =
+            shaders::composition_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        info!("Creating screen quad");
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(Dof {
+            config: config.clone(),
+            blur_texture,
+            blur_texture_back,
+            blur_program,
+            composition_program,
+            screen_quad,
+        })
+    }
+
+    pub fn on_target_resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        self.blur_texture = Self::create_texture(facade, target_size)?;
+        self.blur_texture_back = Self::create_texture(facade, target_size)?;
+
+        Ok(())
+    }
+
+    /// Draws `texture` with depth of field onto the whole `target`.
+    ///
+    /// `depth_texture` must hold the depth of the scene in `texture`, e.g.
+    /// the resolved `depth_texture` of a `RenderTarget`, as drawn with the
+    /// camera of `context`. Reverse-Z is taken into account, but logarithmic
+    /// depth is not supported.
+    pub fn draw<F: glium::backend::Facade, S: Surface>(
+        &self,
+        facade: &F,
+        texture: &Texture2d,
+        depth_texture: &DepthTexture2d,
+        context: &Context,
+        target: &mut S,
+    ) -> Result<(), DrawError> {
+        self.blur_pass(facade, texture)?;
+
+        let inverse_projection = context
+            .projection_matrix()
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+        let depth_map = Sampler::new(depth_texture)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .wrap_function(SamplerWrapFunction::Clamp);
+
+        target.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.composition_program,
+            &uniform! {
+                input_texture: Self::sampler(texture),
+                blurred_texture: Self::sampler(&self.blur_texture),
+                depth_texture: depth_map,
+                inverse_projection: inverse_projection.to_uniform(),
+                focus_distance: self.config.focus_distance,
+                focus_range: self.config.focus_range,
+            },
+            &Default::default(),
+        )?;
+
+        Ok(())
+    }
+
+    fn blur_pass<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+        texture: &Texture2d,
+    ) -> Result<(), DrawError> {
+        let mut buffer = SimpleFrameBuffer::new(facade, &self.blur_texture)?;
+        let mut buffer_back = SimpleFrameBuffer::new(facade, &self.blur_texture_back)?;
+
+        for i in 0..self.config.num_blur_passes.max(1) {
+            // The first pass downsamples the input into the blur textures
+            let input = if i == 0 { texture } else { &self.blur_texture };
+
+            buffer_back.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                &self.blur_program,
+                &uniform! {
+                    horizontal: false,
+                    spread: self.config.blur_strength,
+                    input_texture: Self::sampler(input),
+                },
+                &Default::default(),
+            )?;
+
+            buffer.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                &self.blur_program,
+                &uniform! {
+                    horizontal: true,
+                    spread: self.config.blur_strength,
+                    input_texture: Self::sampler(&self.blur_texture_back),
+                },
+                &Default::default(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn sampler(texture: &Texture2d) -> Sampler<Texture2d> {
+        Sampler::new(texture)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp)
+    }
+
+    fn create_texture<F: glium::backend::Facade>(
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::F16F16F16F16,
+            glium::texture::MipmapsOption::NoMipmap,
+            (target_size.0 / 2).max(1),
+            (target_size.1 / 2).max(1),
+        )?)
+    }
+}
@@ -0,0 +1,90 @@
+use glium::uniforms::UniformType;
+
+use crate::{screen_quad, shader};
+
+fn screen_quad_vertex_core() -> shader::VertexCore<(), (), screen_quad::Vertex> {
+    shader::VertexCore::empty()
+        .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+        .with_out(shader::defs::V_POS, "position")
+}
+
+/// Shader core for a separable Gaussian blur of `input_texture`, with samples
+/// that are `spread` texels apart.
+pub fn blur_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let defs = "
+        float weight[5] = float[] (0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    ";
+
+    let body = "
+        vec2 texel_size = 1.0 / textureSize(input_texture, 0);
+        vec2 step_size = spread * texel_size * (horizontal ? vec2(1.0, 0.0) : vec2(0.0, 1.0));
+
+        vec3 blur_result = texture(input_texture, v_tex_coord).rgb * weight[0];
+
+        for (int i = 1; i < 5; ++i) {
+            blur_result += texture(input_texture, v_tex_coord + step_size * i).rgb * weight[i];
+            blur_result += texture(input_texture, v_tex_coord - step_size * i).rgb * weight[i];
+        }
+    ";
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("horizontal", UniformType::Bool)
+        .with_extra_uniform("spread", UniformType::Float)
+        .with_extra_uniform("input_texture", UniformType::Sampler2d)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_defs(defs)
+        .with_body(body)
+        .with_out(shader::defs::F_COLOR, "vec4(blur_result, 1.0)");
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
+
+/// Shader core for mixing `input_texture` with its blurred copy by the
+/// circle of confusion, which is computed from the view-space depth of each
+/// fragment.
+pub fn composition_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let defs = "
+        float view_depth(vec2 tex_coord) {
+            float depth = texture(depth_texture, tex_coord).r;
+            vec4 ndc_pos = vec4(tex_coord * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+            vec4 view_pos = inverse_projection * ndc_pos;
+
+            return -view_pos.z / view_pos.w;
+        }
+    ";
+
+    let body = "
+        float distance_to_focus = abs(view_depth(v_tex_coord) - focus_distance);
+        float coc = clamp(
+            (distance_to_focus - focus_range) / max(focus_range, 0.0001),
+            0.0,
+            1.0
+        );
+
+        vec3 sharp_color = texture(input_texture, v_tex_coord).rgb;
+        vec3 blurred_color = texture(blurred_texture, v_tex_coord).rgb;
+    ";
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("input_texture", UniformType::Sampler2d)
+        .with_extra_uniform("blurred_texture", UniformType::Sampler2d)
+        .with_extra_uniform("depth_texture", UniformType::Sampler2d)
+        .with_extra_uniform("inverse_projection", UniformType::FloatMat4)
+        .with_extra_uniform("focus_distance", UniformType::Float)
+        .with_extra_uniform("focus_range", UniformType::Float)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_defs(defs)
+        .with_body(body)
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(mix(sharp_color, blurred_color, coc), 1.0)",
+        );
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
@@ -1,6 +1,27 @@
+use std::ops::AddAssign;
+
 use crate::shader::{InstancingMode, ToUniforms};
 use crate::DrawError;
 
+/// Counts of what has been drawn, e.g. for displaying profiling information.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: usize,
+    pub instances: usize,
+
+    /// Instances that were not drawn, since they had been removed from their
+    /// list by `RenderList::cull`.
+    pub instances_culled: usize,
+}
+
+impl AddAssign for DrawStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.instances += other.instances;
+        self.instances_culled += other.instances_culled;
+    }
+}
+
 pub trait Drawable<I, V>
 where
     V: glium::vertex::Vertex,
@@ -8,16 +29,19 @@ where
     /// The instancing mode supported by this `Drawable`.
     ///
     /// We use this to check that the supplied `glium::Program` is compatible
-    /// with the `Drawable`.
-    const INSTANCING_MODE: InstancingMode;
+    /// with the `Drawable`. This is not a constant, since some `Drawable`s
+    /// fall back to uniform-based rendering when the backend does not
+    /// support instancing.
+    fn instancing_mode(&self) -> InstancingMode;
 
+    /// Draws all instances, returning how many draw calls were issued.
     fn draw<U, S>(
         &self,
         program: &glium::Program,
         uniforms: &U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         U: ToUniforms,
         S: glium::Surface;
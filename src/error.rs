@@ -1,4 +1,4 @@
-use crate::shader;
+use crate::{shader, BasicObj};
 
 #[derive(Debug)]
 pub enum CreationError {
@@ -7,7 +7,13 @@ pub enum CreationError {
     Program(glium::program::ProgramCreationError),
     VertexBuffer(glium::vertex::BufferCreationError),
     IndexBuffer(glium::index::BufferCreationError),
+    UniformBuffer(glium::buffer::BufferCreationError),
+    RenderBuffer(glium::framebuffer::RenderBufferCreationError),
     IO(std::io::Error),
+    InvalidMesh(String),
+
+    /// A configuration value is out of its supported range.
+    InvalidConfig(String),
 }
 
 impl From<shader::BuildError> for CreationError {
@@ -40,6 +46,18 @@ impl From<glium::index::BufferCreationError> for CreationError {
     }
 }
 
+impl From<glium::buffer::BufferCreationError> for CreationError {
+    fn from(err: glium::buffer::BufferCreationError) -> CreationError {
+        CreationError::UniformBuffer(err)
+    }
+}
+
+impl From<glium::framebuffer::RenderBufferCreationError> for CreationError {
+    fn from(err: glium::framebuffer::RenderBufferCreationError) -> CreationError {
+        CreationError::RenderBuffer(err)
+    }
+}
+
 impl From<std::io::Error> for CreationError {
     fn from(err: std::io::Error) -> CreationError {
         CreationError::IO(err)
@@ -50,6 +68,19 @@ impl From<std::io::Error> for CreationError {
 pub enum DrawError {
     Creation(CreationError),
     Draw(glium::DrawError),
+
+    /// Drawing the instance at `index` of a list failed.
+    Instance {
+        index: usize,
+        error: glium::DrawError,
+    },
+
+    /// Drawing the instances of `object` failed.
+    BasicObj {
+        object: BasicObj,
+        error: Box<DrawError>,
+    },
+
     FramebufferValidation(glium::framebuffer::ValidationError),
     InstancingNotSupported,
 }
@@ -0,0 +1,199 @@
+use nalgebra as na;
+
+/// The volume that is visible through a camera, bounded by six planes.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    /// Planes as `(normal, distance)`, with normals pointing inwards.
+    planes: [na::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a combined projection and view
+    /// matrix.
+    pub fn from_matrix(matrix: &na::Matrix4<f32>) -> Frustum {
+        // As in:
+        // http://www.cs.otago.ac.nz/postgrads/alexis/planeExtraction.pdf
+
+        let row = |i: usize| matrix.row(i).transpose();
+
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ];
+
+        Frustum {
+            planes: [
+                Self::normalize_plane(planes[0]),
+                Self::normalize_plane(planes[1]),
+                Self::normalize_plane(planes[2]),
+                Self::normalize_plane(planes[3]),
+                Self::normalize_plane(planes[4]),
+                Self::normalize_plane(planes[5]),
+            ],
+        }
+    }
+
+    /// Returns false iff the sphere lies completely outside of the frustum.
+    ///
+    /// This is conservative: some spheres near the corners of the frustum
+    /// are reported as intersecting even though they are not visible.
+    pub fn intersects_sphere(&self, center: &na::Point3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = na::Vector3::new(plane.x, plane.y, plane.z);
+
+            normal.dot(&center.coords) + plane.w >= -radius
+        })
+    }
+
+    /// Checks a sphere of `radius` around the origin in model space, after
+    /// placing it in the world with `transform`.
+    ///
+    /// The radius is scaled by the largest scaling factor of `transform`.
+    pub fn intersects_transformed_sphere(&self, transform: &na::Matrix4<f32>, radius: f32) -> bool {
+        let (center, radius) = transformed_sphere(transform, radius);
+
+        self.intersects_sphere(&center, radius)
+    }
+
+    fn normalize_plane(plane: na::Vector4<f32>) -> na::Vector4<f32> {
+        let length = na::Vector3::new(plane.x, plane.y, plane.z).norm();
+
+        plane / length
+    }
+}
+
+/// Places a sphere of `radius` around the origin in the world with
+/// `transform`, returning its center and radius. The radius is scaled by the
+/// largest scaling factor of `transform`, so that the sphere still contains
+/// everything that it contained before with non-uniform scaling.
+pub(crate) fn transformed_sphere(
+    transform: &na::Matrix4<f32>,
+    radius: f32,
+) -> (na::Point3<f32>, f32) {
+    let center = transform.transform_point(&na::Point3::origin());
+    let scale = (0..3)
+        .map(|i| transform.fixed_slice::<na::U3, na::U1>(0, i).norm())
+        .fold(0.0, f32::max);
+
+    (center, radius * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::HasTransform;
+
+    const EPSILON: f32 = 1e-5;
+
+    struct Instance(na::Matrix4<f32>);
+
+    impl HasTransform for Instance {
+        fn transform(&self) -> &na::Matrix4<f32> {
+            &self.0
+        }
+    }
+
+    fn assert_sphere_eq(
+        (center, radius): (na::Point3<f32>, f32),
+        expected_center: na::Point3<f32>,
+        expected_radius: f32,
+    ) {
+        assert!(
+            (center - expected_center).norm() < EPSILON,
+            "center {} != {}",
+            center,
+            expected_center
+        );
+        assert!(
+            (radius - expected_radius).abs() < EPSILON,
+            "radius {} != {}",
+            radius,
+            expected_radius
+        );
+    }
+
+    #[test]
+    fn transformed_sphere_uses_largest_scale() {
+        let transform = na::Matrix4::new_translation(&na::Vector3::new(1.0, 2.0, 3.0))
+            * na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(1.0, 3.0, 2.0));
+
+        assert_sphere_eq(
+            transformed_sphere(&transform, 0.5),
+            na::Point3::new(1.0, 2.0, 3.0),
+            1.5,
+        );
+    }
+
+    #[test]
+    fn transformed_sphere_ignores_rotation() {
+        let rotation =
+            na::Rotation3::from_axis_angle(&na::Vector3::z_axis(), std::f32::consts::FRAC_PI_3)
+                .to_homogeneous();
+        let scaling = na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(2.0, 0.5, 4.0));
+
+        assert_sphere_eq(
+            transformed_sphere(&(rotation * scaling), 1.0),
+            na::Point3::origin(),
+            4.0,
+        );
+        assert_sphere_eq(
+            transformed_sphere(&(scaling * rotation), 1.0),
+            na::Point3::origin(),
+            4.0,
+        );
+    }
+
+    #[test]
+    fn transformed_sphere_contains_scaled_points() {
+        let transform = na::Matrix4::new_translation(&na::Vector3::new(-2.0, 0.0, 1.0))
+            * na::Rotation3::from_euler_angles(0.3, -1.2, 2.0).to_homogeneous()
+            * na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(0.5, 2.5, 1.5));
+        let (center, radius) = transformed_sphere(&transform, 1.0);
+
+        for axis in 0..3 {
+            for &sign in &[-1.0, 1.0] {
+                let mut point = na::Point3::origin();
+                point[axis] = sign;
+
+                let distance = (transform.transform_point(&point) - center).norm();
+                assert!(distance <= radius + EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn bounding_sphere_matches_transformed_sphere() {
+        let transform = na::Matrix4::new_translation(&na::Vector3::new(0.0, -1.0, 5.0))
+            * na::Rotation3::from_axis_angle(&na::Vector3::x_axis(), 0.7).to_homogeneous()
+            * na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(3.0, 1.0, 1.0));
+        let instance = Instance(transform);
+
+        assert_sphere_eq(
+            instance.bounding_sphere(2.0),
+            na::Point3::new(0.0, -1.0, 5.0),
+            6.0,
+        );
+        assert_sphere_eq(
+            instance.bounding_sphere(2.0),
+            instance.position(),
+            transformed_sphere(&transform, 2.0).1,
+        );
+    }
+
+    #[test]
+    fn intersects_transformed_sphere_with_scale() {
+        let frustum = Frustum::from_matrix(
+            &na::Orthographic3::new(-1.0, 1.0, -1.0, 1.0, 0.1, 10.0).to_homogeneous(),
+        );
+        let translation = na::Matrix4::new_translation(&na::Vector3::new(1.5, 0.0, -5.0));
+        let scaling = na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(1.0, 3.0, 1.0));
+
+        assert!(!frustum.intersects_transformed_sphere(&translation, 0.2));
+        assert!(frustum.intersects_transformed_sphere(&(translation * scaling), 0.2));
+    }
+}
@@ -39,6 +39,11 @@ impl Default for Config {
     }
 }
 
+/// Fast approximate antialiasing as a full-screen postprocessing pass.
+///
+/// The `Pipeline` applies this automatically if `Config::fxaa` is set. It can
+/// also be used on its own, e.g. with the `color_texture` of a
+/// `RenderTarget`.
 pub struct FXAA {
     program: Program,
     screen_quad: ScreenQuad,
@@ -63,6 +68,10 @@ impl FXAA {
         })
     }
 
+    /// Draws an antialiased version of `texture` onto the whole `target`.
+    ///
+    /// `texture` should contain gamma-corrected colors, since edges are
+    /// detected based on perceived luminance.
     pub fn draw<S: Surface>(&self, texture: &Texture2d, target: &mut S) -> Result<(), DrawError> {
         let texture_map = Sampler::new(texture)
             .magnify_filter(MagnifySamplerFilter::Linear)
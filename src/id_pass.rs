@@ -0,0 +1,188 @@
+//! Picking instances on the GPU, by rendering their ids into a texture.
+//!
+//! Compared to `RenderList::pick`, this is exact for arbitrary meshes, since
+//! the rasterized triangles are used instead of bounding boxes.
+
+use std::marker::PhantomData;
+
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::UniformType;
+use glium::Surface;
+
+use crate::scene::SceneCore;
+use crate::shader::{self, ToUniforms};
+use crate::{Context, CreationError, DrawError, DrawStats, InstanceId, Mesh, RenderList};
+
+/// Renders the instances of a `RenderList` with their index encoded as color
+/// into an offscreen target, so that the instance under a pixel can be read
+/// back with `read_id_at`.
+pub struct IdPass<C: SceneCore> {
+    program: glium::Program,
+    size: (u32, u32),
+    id_texture: Texture2d,
+    depth_buffer: DepthRenderBuffer,
+
+    /// Ids of the instances that were drawn last, by index.
+    ids: Vec<InstanceId>,
+
+    phantom: PhantomData<C>,
+}
+
+impl<C: SceneCore> IdPass<C> {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        scene_core: C,
+        size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        let core = scene_core.scene_core();
+
+        // Indices are offset by one, so that zero is left for the background.
+        // Floats represent integers exactly up to 2^24, which is plenty.
+        let id_core = shader::Core {
+            vertex: core.vertex,
+            fragment: shader::FragmentCore::empty()
+                .with_extra_uniform("id_pass_index", UniformType::Int)
+                .with_out(
+                    shader::defs::F_COLOR,
+                    "vec4(float(id_pass_index + 1), 0.0, 0.0, 1.0)",
+                ),
+        };
+
+        Ok(Self {
+            program: id_core.build_program(facade, shader::InstancingMode::Uniforms)?,
+            size,
+            id_texture: Self::create_id_texture(facade, size)?,
+            depth_buffer: DepthRenderBuffer::new(facade, DepthFormat::F32, size.0, size.1)?,
+            ids: Vec::new(),
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Recreates the target if `size` differs from the current size. This
+    /// should be kept in sync with the size of the window that is picked in.
+    pub fn resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        if size != self.size {
+            self.size = size;
+            self.id_texture = Self::create_id_texture(facade, size)?;
+            self.depth_buffer = DepthRenderBuffer::new(facade, DepthFormat::F32, size.0, size.1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the target and renders the ids of all instances in
+    /// `render_list`.
+    pub fn draw<F, P, V>(
+        &mut self,
+        facade: &F,
+        render_list: &RenderList<C::Instance>,
+        mesh: &Mesh<V>,
+        params: (&Context, &P),
+        draw_params: &glium::DrawParameters,
+    ) -> Result<DrawStats, DrawError>
+    where
+        F: glium::backend::Facade,
+        P: shader::input::CompatibleWith<C::Params>,
+        V: glium::vertex::Vertex,
+    {
+        let mut framebuffer =
+            SimpleFrameBuffer::with_depth_buffer(facade, &self.id_texture, &self.depth_buffer)?;
+        framebuffer.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), params.0.clear_depth());
+
+        let draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: params.0.depth_test(glium::DepthTest::IfLess),
+                write: true,
+                ..Default::default()
+            },
+            ..draw_params.clone()
+        };
+
+        self.ids.clear();
+
+        // Hidden instances are skipped, so `id_pass_index` refers to `ids`
+        // rather than to positions in the list.
+        for (index, instance) in render_list.visible_instances() {
+            let id_uniforms = plain_uniforms! {
+                id_pass_index: self.ids.len() as i32,
+            };
+
+            framebuffer
+                .draw(
+                    &mesh.vertex_buffer,
+                    &mesh.index_buffer,
+                    &self.program,
+                    &(params, instance, id_uniforms).to_uniforms(),
+                    &mesh.draw_params(&render_list.depth_at(index).draw_params(&draw_params)),
+                )
+                .map_err(|error| DrawError::Instance { index, error })?;
+
+            // Safe to unwrap, since `index` is within the list
+            self.ids.push(render_list.id_at(index).unwrap());
+        }
+
+        Ok(DrawStats {
+            draw_calls: self.ids.len(),
+            instances: self.ids.len(),
+            instances_culled: render_list.num_culled(),
+        })
+    }
+
+    /// Returns the id of the instance that was drawn last at pixel `(x, y)`,
+    /// with the origin in the upper-left corner as in window coordinates.
+    ///
+    /// Only the pixel itself is read back from the GPU, but this still stalls
+    /// until drawing has finished, so it should only be called when needed,
+    /// e.g. on a click.
+    pub fn read_id_at(&self, x: u32, y: u32) -> Option<InstanceId> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+
+        // Rows are stored from the bottom up
+        let rect = glium::Rect {
+            left: x,
+            bottom: self.size.1 - 1 - y,
+            width: 1,
+            height: 1,
+        };
+
+        // Safe to unwrap, since a `Texture2d` has exactly one layer
+        let image = self
+            .id_texture
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap();
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = image.raw_read(&rect);
+        let value = pixels[0][0].0;
+
+        if value >= 1.0 {
+            self.ids.get(value as usize - 1).copied()
+        } else {
+            None
+        }
+    }
+
+    fn create_id_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+}
@@ -1,7 +1,7 @@
 use log::info;
 
 use crate::shader::{InstanceInput, InstancingMode, ToUniforms};
-use crate::{Drawable, Mesh};
+use crate::{Context, DrawStats, Drawable, HasTransformMut, InstanceDepth, Mesh, RenderList};
 
 pub use crate::error::{CreationError, DrawError};
 
@@ -56,22 +56,170 @@ where
     }
 }
 
-pub struct Instancing<V: InstanceInput> {
-    buffers: Vec<Buffer<V::Vertex>>,
+pub struct Instancing<I: InstanceInput> {
+    mode: InstancingMode,
+    buffers: Vec<Buffer<I::Vertex>>,
+
+    /// Instance data of the last update. This is drawn with uniforms when the
+    /// backend does not support instancing.
+    vertices: Vec<I::Vertex>,
+
+    /// Depth settings of each instance in `vertices`.
+    depths: Vec<InstanceDepth>,
+
+    /// Instances whose depth settings differ from the defaults, grouped by
+    /// their settings. These are drawn in separate calls after `buffers`.
+    override_buffers: Vec<(InstanceDepth, glium::VertexBuffer<I::Vertex>)>,
+
+    /// `RenderList::num_culled` of the list of the last update.
+    num_culled: usize,
 }
 
 impl<I: InstanceInput> Instancing<I> {
     pub fn create<F: glium::backend::Facade>(facade: &F) -> Result<Self, CreationError> {
         let buffers = vec![Buffer::create(facade)?];
 
-        Ok(Self { buffers })
+        // glium checks for instancing support when we request per-instance
+        // data from a vertex buffer, so this is a cheap way to find out if
+        // we need to fall back to uniforms.
+        let mode = if buffers[0].buffer.per_instance().is_ok() {
+            InstancingMode::Vertex
+        } else {
+            info!(
+                "Instancing not supported, falling back to uniforms for `I={}`",
+                std::any::type_name::<I>()
+            );
+
+            InstancingMode::Uniforms
+        };
+
+        Ok(Self {
+            mode,
+            buffers,
+            vertices: Vec::new(),
+            depths: Vec::new(),
+            override_buffers: Vec::new(),
+            num_culled: 0,
+        })
+    }
+
+    /// The instancing mode that is used for drawing.
+    ///
+    /// Programs that are used with this `Instancing` need to be built with
+    /// this mode.
+    pub fn mode(&self) -> InstancingMode {
+        self.mode
     }
 
     pub fn update<F: glium::backend::Facade>(
         &mut self,
         facade: &F,
-        mut instances: &[I::Vertex],
+        instances: &[I],
+    ) -> Result<(), CreationError> {
+        self.num_culled = 0;
+        self.update_iter(
+            facade,
+            instances
+                .iter()
+                .map(|instance| (instance, InstanceDepth::default())),
+        )
+    }
+
+    /// Uploads the visible instances of `render_list`, skipping those hidden
+    /// with `RenderList::set_visible`. Instances with depth settings of
+    /// `RenderList::set_depth` are kept in separate buffers.
+    pub fn update_visible<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &RenderList<I>,
     ) -> Result<(), CreationError> {
+        self.num_culled = render_list.num_culled();
+        self.update_iter(
+            facade,
+            render_list
+                .visible_instances()
+                .map(|(index, instance)| (instance, render_list.depth_at(index))),
+        )
+    }
+
+    /// Uploads the visible instances of `render_list` like `update_visible`,
+    /// but moved relative to the eye if `Context::camera_relative` is enabled
+    /// in `context`. Since the instances depend on the camera then, this
+    /// needs to be called in every frame in which the camera moves.
+    pub fn update_in<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &RenderList<I>,
+        context: &Context,
+    ) -> Result<(), CreationError>
+    where
+        I: HasTransformMut + Clone,
+    {
+        if !context.camera_relative {
+            return self.update_visible(facade, render_list);
+        }
+
+        let instances = render_list
+            .visible_instances()
+            .map(|(index, instance)| {
+                (
+                    instance.in_context(context).into_owned(),
+                    render_list.depth_at(index),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.num_culled = render_list.num_culled();
+        self.update_iter(
+            facade,
+            instances.iter().map(|(instance, depth)| (instance, *depth)),
+        )
+    }
+
+    fn update_iter<'a, F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        instances: impl Iterator<Item = (&'a I, InstanceDepth)>,
+    ) -> Result<(), CreationError>
+    where
+        I: 'a,
+    {
+        self.vertices.clear();
+        self.depths.clear();
+        self.override_buffers.clear();
+
+        for (instance, depth) in instances {
+            self.vertices.push(instance.to_vertex());
+            self.depths.push(depth);
+        }
+
+        if self.mode == InstancingMode::Uniforms {
+            return Ok(());
+        }
+
+        let has_overrides = self
+            .depths
+            .iter()
+            .any(|&depth| depth != InstanceDepth::default());
+
+        let default_vertices;
+        let mut vertices = if has_overrides {
+            for &depth in self.depths.iter() {
+                if depth != InstanceDepth::default()
+                    && !self.override_buffers.iter().any(|(d, _)| *d == depth)
+                {
+                    let group = self.vertices_with_depth(depth);
+                    self.override_buffers
+                        .push((depth, glium::VertexBuffer::dynamic(facade, &group)?));
+                }
+            }
+
+            default_vertices = self.vertices_with_depth(InstanceDepth::default());
+            &default_vertices[..]
+        } else {
+            &self.vertices[..]
+        };
+
         // Write instance data into vertex buffers. We move through the buffers
         // that we have, filling them up sequentially.
         for buffer in &mut self.buffers {
@@ -80,9 +228,9 @@ impl<I: InstanceInput> Instancing<I> {
 
         let mut cur_buffer = 0;
 
-        while !instances.is_empty() {
+        while !vertices.is_empty() {
             // Write as much as possible into the current buffer.
-            let num_written = self.buffers[cur_buffer].append(instances);
+            let num_written = self.buffers[cur_buffer].append(vertices);
 
             if num_written == 0 {
                 // We had instance data to write, but nothing was written
@@ -102,13 +250,40 @@ impl<I: InstanceInput> Instancing<I> {
             } else {
                 // We have written something into the buffer, reduce slice
                 // accordingly.
-                instances = &instances[num_written..];
+                vertices = &vertices[num_written..];
             }
         }
 
         Ok(())
     }
 
+    /// Uploads the instances of `render_list`, but only if they have changed
+    /// since the last call. This saves the upload for mostly static scenes.
+    ///
+    /// Since the dirty flag is kept in `render_list`, each list should be
+    /// updated into its own `Instancing`.
+    pub fn update_if_dirty<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        render_list: &mut RenderList<I>,
+    ) -> Result<(), CreationError> {
+        if render_list.is_dirty() {
+            self.update_visible(facade, render_list)?;
+            render_list.mark_clean();
+        }
+
+        Ok(())
+    }
+
+    fn vertices_with_depth(&self, depth: InstanceDepth) -> Vec<I::Vertex> {
+        self.vertices
+            .iter()
+            .zip(self.depths.iter())
+            .filter(|&(_, &d)| d == depth)
+            .map(|(vertex, _)| *vertex)
+            .collect()
+    }
+
     pub fn as_drawable<'a, V: glium::vertex::Vertex>(
         &'a self,
         mesh: &'a Mesh<V>,
@@ -124,7 +299,9 @@ where
     I: InstanceInput,
     V: glium::vertex::Vertex,
 {
-    const INSTANCING_MODE: InstancingMode = InstancingMode::Vertex;
+    fn instancing_mode(&self) -> InstancingMode {
+        self.0.mode
+    }
 
     fn draw<U, S>(
         &self,
@@ -132,23 +309,48 @@ where
         uniforms: &U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         U: ToUniforms,
         S: glium::Surface,
     {
+        if self.0.mode == InstancingMode::Uniforms {
+            for (index, instance) in self.0.vertices.iter().enumerate() {
+                target
+                    .draw(
+                        &self.1.vertex_buffer,
+                        &self.1.index_buffer,
+                        program,
+                        &(uniforms, instance).to_uniforms(),
+                        &self
+                            .1
+                            .draw_params(&self.0.depths[index].draw_params(draw_params)),
+                    )
+                    .map_err(|error| DrawError::Instance { index, error })?;
+            }
+
+            return Ok(DrawStats {
+                draw_calls: self.0.vertices.len(),
+                instances: self.0.vertices.len(),
+                instances_culled: self.0.num_culled,
+            });
+        }
+
         let uniforms = uniforms.to_uniforms();
+        let mut stats = DrawStats {
+            instances_culled: self.0.num_culled,
+            ..Default::default()
+        };
 
         for buffer in self.0.buffers.iter() {
             if buffer.num_used == 0 {
                 // Buffers are filled sequentially, so we can exit early here.
-                return Ok(());
+                break;
             }
 
             // Safe to unwrap here, since we assure that `num_used < buffer.len()`.
             let instances = buffer.buffer.slice(0..buffer.num_used).unwrap();
 
-            // TODO: Fall back to non-instanced rendering if not supported?
             let per_instance = instances
                 .per_instance()
                 .map_err(|_| DrawError::InstancingNotSupported)?;
@@ -158,10 +360,30 @@ where
                 &self.1.index_buffer,
                 program,
                 &uniforms,
-                draw_params,
+                &self.1.draw_params(draw_params),
             )?;
+
+            stats.draw_calls += 1;
+            stats.instances += buffer.num_used;
         }
 
-        Ok(())
+        for (depth, buffer) in self.0.override_buffers.iter() {
+            let per_instance = buffer
+                .per_instance()
+                .map_err(|_| DrawError::InstancingNotSupported)?;
+
+            target.draw(
+                (&self.1.vertex_buffer, per_instance),
+                &self.1.index_buffer,
+                program,
+                &uniforms,
+                &self.1.draw_params(&depth.draw_params(draw_params)),
+            )?;
+
+            stats.draw_calls += 1;
+            stats.instances += buffer.len();
+        }
+
+        Ok(stats)
     }
 }
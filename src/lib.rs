@@ -1,32 +1,64 @@
 #[macro_use]
 pub mod shader;
 
+mod aabb;
+mod animation;
+mod blend;
 mod camera;
 mod drawable;
 mod error;
+mod frustum;
+mod id_pass;
 mod instancing;
+mod lod;
+mod material;
 mod mesh;
+mod outline;
 mod render_list;
+mod render_target;
+mod sampler;
 mod scene;
 mod stage;
+mod transform;
 
 pub mod basic_obj;
+pub mod bloom;
+pub mod dof;
 pub mod fxaa;
 pub mod line;
+pub mod motion_blur;
+pub mod particle;
 pub mod pipeline;
+pub mod primitives;
 pub mod screen_quad;
+pub mod skybox;
+pub mod text;
 
+pub use aabb::Aabb;
+pub use animation::{AnimatedInstance, AnimationMode};
 pub use basic_obj::BasicObj;
-pub use camera::Camera;
-pub use drawable::Drawable;
+pub use blend::BlendMode;
+pub use camera::{Camera, FlyCamera, OrbitCamera, Projection, SmoothCamera};
+pub use drawable::{DrawStats, Drawable};
 pub use error::{CreationError, DrawError};
+pub use frustum::Frustum;
+pub use id_pass::IdPass;
 pub use instancing::Instancing;
-pub use mesh::Mesh;
+pub use lod::LodSet;
+pub use material::Material;
+pub use mesh::{triangles_from_indices, Mesh, MeshData, PRIMITIVE_RESTART_INDEX};
+pub use outline::{Outline, OutlinePass};
+pub use particle::ParticleSystem;
 pub use pipeline::{
-    Config, Pipeline, PlainScenePass, ShadedScenePass, ShadedScenePassSetup, ShadowPass,
+    Config, DepthConfig, DepthPrePass, NormalSpace, Pipeline, PlainScenePass, PolygonOffset,
+    ShadedScenePass, ShadedScenePassSetup, ShadowPass, TransparentPass, WireframePass,
 };
-pub use render_list::RenderList;
-pub use scene::SceneCore;
+pub use render_list::{HasTransform, HasTransformMut, InstanceDepth, InstanceId, RenderList};
+pub use render_target::RenderTarget;
+pub use sampler::SamplerConfig;
+pub use scene::{FileSceneCore, SceneCore, ShaderFiles};
 pub use screen_quad::ScreenQuad;
 pub use shader::InstancingMode;
-pub use stage::{Context, Light};
+pub use skybox::Skybox;
+pub use stage::{Context, ContextBlock, Fog, FogMode, Light, LightKind};
+pub use transform::Transform;
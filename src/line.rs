@@ -16,7 +16,10 @@ use nalgebra as na;
 
 use glium::implement_vertex;
 
-use crate::{shader, Context, CreationError, Mesh, SceneCore};
+use crate::{
+    shader, Camera, Context, CreationError, HasTransform, HasTransformMut, Mesh, RenderList,
+    SceneCore,
+};
 
 #[derive(Clone, Debug)]
 pub struct Params {
@@ -46,6 +49,18 @@ impl_instance_input!(
     },
 );
 
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Point {
     pub prev_pos: [f32; 3],
@@ -79,6 +94,109 @@ impl Instance {
     }
 }
 
+/// A reference grid on the ground plane (z = 0), centered at the origin.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    /// Number of cells along each axis.
+    pub num_cells: usize,
+
+    /// Distance between two neighboring lines.
+    pub spacing: f32,
+
+    pub color: na::Vector4<f32>,
+    pub thickness: f32,
+
+    /// Draw the lines through the origin in red (x axis) and green (y axis).
+    pub highlight_axes: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            num_cells: 20,
+            spacing: 1.0,
+            color: na::Vector4::new(0.5, 0.5, 0.5, 1.0),
+            thickness: 1.0,
+            highlight_axes: true,
+        }
+    }
+}
+
+impl Grid {
+    /// Adds line instances for the grid to `render_list`.
+    pub fn add_to(&self, render_list: &mut RenderList<Instance>) {
+        let half_extent = self.num_cells as f32 * self.spacing / 2.0;
+
+        for i in 0..=self.num_cells {
+            let offset = i as f32 * self.spacing - half_extent;
+            let is_axis = self.highlight_axes && 2 * i == self.num_cells;
+
+            // Line parallel to the x axis
+            render_list.add(Instance::from_start_end(
+                &na::Point3::new(-half_extent, offset, 0.0),
+                &na::Point3::new(half_extent, offset, 0.0),
+                if is_axis {
+                    &na::Vector4::new(1.0, 0.0, 0.0, 1.0)
+                } else {
+                    &self.color
+                },
+                self.thickness,
+            ));
+
+            // Line parallel to the y axis
+            render_list.add(Instance::from_start_end(
+                &na::Point3::new(offset, -half_extent, 0.0),
+                &na::Point3::new(offset, half_extent, 0.0),
+                if is_axis {
+                    &na::Vector4::new(0.0, 1.0, 0.0, 1.0)
+                } else {
+                    &self.color
+                },
+                self.thickness,
+            ));
+        }
+    }
+}
+
+/// The edges of a camera's frustum, e.g. for debugging culling by looking at
+/// the frustum through another camera.
+#[derive(Clone, Debug)]
+pub struct FrustumLines {
+    pub color: na::Vector4<f32>,
+    pub thickness: f32,
+}
+
+impl Default for FrustumLines {
+    fn default() -> Self {
+        Self {
+            color: na::Vector4::new(1.0, 1.0, 0.0, 1.0),
+            thickness: 1.0,
+        }
+    }
+}
+
+impl FrustumLines {
+    /// Adds line instances for the edges of the frustum of `camera` to
+    /// `render_list`, i.e. the rectangles at the near and far planes and the
+    /// edges that connect them.
+    pub fn add_to(&self, camera: &Camera, render_list: &mut RenderList<Instance>) {
+        let corners = camera.frustum_corners();
+
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+
+            for &(start, end) in &[(i, j), (i + 4, j + 4), (i, i + 4)] {
+                render_list.add(Instance::from_start_end(
+                    &corners[start],
+                    &corners[end],
+                    &self.color,
+                    self.thickness,
+                ));
+            }
+        }
+    }
+}
+
 pub fn create_mesh<F: glium::backend::Facade>(facade: &F) -> Result<Mesh<Point>, CreationError> {
     let points = vec![
         Point {
@@ -0,0 +1,127 @@
+use nalgebra as na;
+
+use crate::shader::{InstanceInput, InstancingMode, ToUniforms};
+use crate::{DrawError, DrawStats, Drawable, HasTransform, Mesh, RenderList};
+
+/// Meshes of one object at different levels of detail, so that distant
+/// instances can be drawn with fewer triangles.
+pub struct LodSet<V: Copy> {
+    /// Levels as `(max_distance, mesh)`, sorted by ascending distance.
+    /// Instances that are further away than the last level's distance use the
+    /// last level.
+    levels: Vec<(f32, Mesh<V>)>,
+}
+
+impl<V: glium::vertex::Vertex> LodSet<V> {
+    /// Creates a set from levels given as `(max_distance, mesh)`, with the
+    /// most detailed mesh for the smallest distance.
+    ///
+    /// Panics if `levels` is empty.
+    pub fn new(mut levels: Vec<(f32, Mesh<V>)>) -> Self {
+        assert!(!levels.is_empty(), "LodSet needs at least one level");
+
+        levels.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> &[(f32, Mesh<V>)] {
+        &self.levels
+    }
+
+    /// Index of the level that is used at `distance` from the camera.
+    pub fn level_index(&self, distance: f32) -> usize {
+        self.levels
+            .iter()
+            .position(|(max_distance, _)| distance <= *max_distance)
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    pub fn mesh(&self, distance: f32) -> &Mesh<V> {
+        &self.levels[self.level_index(distance)].1
+    }
+
+    /// Splits `render_list` into one list per level, by the distance of each
+    /// instance from `eye`. The lists can be used with `Instancing`, drawing
+    /// each with the mesh of its level. Hidden instances are left out.
+    pub fn split<I>(&self, render_list: &RenderList<I>, eye: &na::Point3<f32>) -> Vec<RenderList<I>>
+    where
+        I: InstanceInput + HasTransform + Clone,
+    {
+        let mut lists = vec![RenderList::default(); self.levels.len()];
+
+        for (index, instance) in render_list.visible_instances() {
+            let distance = na::distance(eye, &instance.position());
+            let list = &mut lists[self.level_index(distance)];
+            let id = list.add(instance.clone());
+            list.set_depth(id, render_list.depth_at(index));
+        }
+
+        lists
+    }
+
+    /// Draws the instances of `render_list`, choosing the mesh of each
+    /// instance by its distance from `eye`.
+    pub fn as_drawable<'a, I>(
+        &'a self,
+        render_list: &'a RenderList<I>,
+        eye: na::Point3<f32>,
+    ) -> impl Drawable<I, V> + 'a
+    where
+        I: InstanceInput + HasTransform,
+    {
+        DrawableImpl(self, render_list, eye)
+    }
+}
+
+struct DrawableImpl<'a, I: InstanceInput, V: Copy>(
+    &'a LodSet<V>,
+    &'a RenderList<I>,
+    na::Point3<f32>,
+);
+
+impl<'a, I, V> Drawable<I, V> for DrawableImpl<'a, I, V>
+where
+    I: InstanceInput + HasTransform,
+    V: glium::vertex::Vertex,
+{
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let mut num_instances = 0;
+
+        for (index, instance) in self.1.visible_instances() {
+            let mesh = self.0.mesh(na::distance(&self.2, &instance.position()));
+
+            target
+                .draw(
+                    &mesh.vertex_buffer,
+                    &mesh.index_buffer,
+                    program,
+                    &(uniforms, instance).to_uniforms(),
+                    &mesh.draw_params(&self.1.depth_at(index).draw_params(draw_params)),
+                )
+                .map_err(|error| DrawError::Instance { index, error })?;
+
+            num_instances += 1;
+        }
+
+        Ok(DrawStats {
+            draw_calls: num_instances,
+            instances: num_instances,
+            instances_culled: self.1.num_culled(),
+        })
+    }
+}
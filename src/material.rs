@@ -0,0 +1,34 @@
+use nalgebra as na;
+
+/// Coefficients that determine how a surface reacts to light.
+///
+/// Scene cores pass these on through the `F_AMBIENT`, `F_DIFFUSE`,
+/// `F_SPECULAR` and `F_SHININESS` fragment outputs. Outputs that a core does
+/// not provide take the values of `Material::default()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Factor for the ambient light.
+    pub ambient: f32,
+
+    /// Factor for diffuse lighting.
+    pub diffuse: f32,
+
+    /// Color of specular highlights, which also gives their intensity. Gray
+    /// values give highlights in the color of the light, as for plastics,
+    /// while colored values suit metals.
+    pub specular: na::Vector3<f32>,
+
+    /// Blinn-Phong exponent. Highlights are disabled if this is zero.
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            ambient: 1.0,
+            diffuse: 1.0,
+            specular: na::Vector3::zeros(),
+            shininess: 0.0,
+        }
+    }
+}
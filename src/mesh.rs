@@ -1,10 +1,29 @@
+use glium::index::PrimitiveType;
+
+use crate::shader::{DynUniforms, InstancingMode, ToUniforms};
+use crate::{DrawError, DrawStats, Drawable};
+
 pub use crate::CreationError;
 
+/// Index that restarts strips and fans, so that several of them can be
+/// stored in one index buffer. Restarting needs to be enabled with
+/// `glium::DrawParameters::primitive_restart_index` when drawing.
+pub const PRIMITIVE_RESTART_INDEX: u32 = std::u32::MAX;
+
 pub enum IndexBuffer {
     IndexBuffer(glium::index::IndexBuffer<u32>),
     NoIndices(glium::index::NoIndices),
 }
 
+impl IndexBuffer {
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            IndexBuffer::IndexBuffer(buffer) => buffer.get_primitives_type(),
+            IndexBuffer::NoIndices(glium::index::NoIndices(primitive_type)) => *primitive_type,
+        }
+    }
+}
+
 impl<'a> From<&'a IndexBuffer> for glium::index::IndicesSource<'a> {
     fn from(buffer: &'a IndexBuffer) -> Self {
         match buffer {
@@ -14,15 +33,68 @@ impl<'a> From<&'a IndexBuffer> for glium::index::IndicesSource<'a> {
     }
 }
 
+/// Vertices and indices on the GPU, for any vertex type `V` that implements
+/// `glium::vertex::Vertex`.
+///
+/// Meshes with custom attributes, such as texture coordinates, colors or
+/// tangents, use their own vertex type here. Their instances are drawn with
+/// `RenderList::as_drawable` or `Instancing::as_drawable`, in a pass whose
+/// `SceneCore` declares the same vertex type, so that the attributes can be
+/// read in the shader.
 pub struct Mesh<V: Copy> {
     pub vertex_buffer: glium::VertexBuffer<V>,
     pub index_buffer: IndexBuffer,
 }
 
 impl<V: glium::vertex::Vertex> Mesh<V> {
+    /// Creates a mesh that is drawn with `indices` if they are given, and
+    /// with the vertices in order otherwise.
+    ///
+    /// Besides lists, `primitive_type` can be `TriangleStrip` or
+    /// `TriangleFan`, which need fewer indices. Indexed strips and fans can be
+    /// separated with `PRIMITIVE_RESTART_INDEX`, which needs drawing with
+    /// `draw_params`.
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        primitive_type: PrimitiveType,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+    ) -> Result<Self, CreationError> {
+        match indices {
+            Some(indices) => Self::create_with_indices(facade, primitive_type, vertices, indices),
+            None => Self::create_without_indices(facade, primitive_type, vertices),
+        }
+    }
+
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.index_buffer.primitive_type()
+    }
+
+    /// Returns `draw_params` with primitive restart enabled if this mesh has
+    /// indexed strips or fans, so that they are separated at
+    /// `PRIMITIVE_RESTART_INDEX`. The drawables of this crate use this for
+    /// all meshes.
+    pub fn draw_params<'p>(
+        &self,
+        draw_params: &glium::DrawParameters<'p>,
+    ) -> glium::DrawParameters<'p> {
+        let is_indexed_strip = match (&self.index_buffer, self.primitive_type()) {
+            (IndexBuffer::IndexBuffer(_), PrimitiveType::TriangleStrip)
+            | (IndexBuffer::IndexBuffer(_), PrimitiveType::TriangleFan)
+            | (IndexBuffer::IndexBuffer(_), PrimitiveType::LineStrip)
+            | (IndexBuffer::IndexBuffer(_), PrimitiveType::LineLoop) => true,
+            _ => false,
+        };
+
+        glium::DrawParameters {
+            primitive_restart_index: draw_params.primitive_restart_index || is_indexed_strip,
+            ..draw_params.clone()
+        }
+    }
+
     pub fn create_with_indices<F: glium::backend::Facade>(
         facade: &F,
-        primitive_type: glium::index::PrimitiveType,
+        primitive_type: PrimitiveType,
         vertices: &[V],
         indices: &[u32],
     ) -> Result<Self, CreationError> {
@@ -35,4 +107,203 @@ impl<V: glium::vertex::Vertex> Mesh<V> {
             )?),
         })
     }
+
+    pub fn create_without_indices<F: glium::backend::Facade>(
+        facade: &F,
+        primitive_type: PrimitiveType,
+        vertices: &[V],
+    ) -> Result<Self, CreationError> {
+        Ok(Mesh {
+            vertex_buffer: glium::VertexBuffer::new(facade, vertices)?,
+            index_buffer: IndexBuffer::NoIndices(glium::index::NoIndices(primitive_type)),
+        })
+    }
+
+    /// Draws this mesh once for each of `instances`, whose uniforms may be
+    /// of different types, e.g. for mixing instances of several kinds in one
+    /// `Vec` without an enum.
+    ///
+    /// Each instance is drawn in its own draw call with uniforms, so the
+    /// program needs to be built with `InstancingMode::Uniforms`, and it
+    /// needs to be compatible with the uniforms of every instance.
+    pub fn as_dyn_drawable<'a, 'b, I>(
+        &'a self,
+        instances: &'a [Box<dyn DynUniforms + 'b>],
+    ) -> impl Drawable<I, V> + 'a
+    where
+        'b: 'a,
+    {
+        DynDrawableImpl(self, instances)
+    }
+}
+
+struct DynDrawableImpl<'a, 'b, V: Copy>(&'a Mesh<V>, &'a [Box<dyn DynUniforms + 'b>]);
+
+impl<'a, 'b, I, V> Drawable<I, V> for DynDrawableImpl<'a, 'b, V>
+where
+    V: glium::vertex::Vertex,
+{
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        for (index, instance) in self.1.iter().enumerate() {
+            target
+                .draw(
+                    &self.0.vertex_buffer,
+                    &self.0.index_buffer,
+                    program,
+                    &(uniforms, instance).to_uniforms(),
+                    &self.0.draw_params(draw_params),
+                )
+                .map_err(|error| DrawError::Instance { index, error })?;
+        }
+
+        Ok(DrawStats {
+            draw_calls: self.1.len(),
+            instances: self.1.len(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Mesh data on the CPU side, e.g. for procedurally generated geometry.
+///
+/// This holds no GPU resources, so it can be created on a background thread
+/// and sent to the thread that owns the facade for `create_mesh`.
+#[derive(Debug, Clone)]
+pub struct MeshData<V> {
+    pub primitive_type: PrimitiveType,
+    pub vertices: Vec<V>,
+
+    /// Indices into `vertices`. If this is `None`, the vertices are drawn in
+    /// order.
+    pub indices: Option<Vec<u32>>,
+}
+
+impl<V: glium::vertex::Vertex> MeshData<V> {
+    pub fn create_mesh<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+    ) -> Result<Mesh<V>, CreationError> {
+        Mesh::create(
+            facade,
+            self.primitive_type,
+            &self.vertices,
+            self.indices.as_ref().map(Vec::as_slice),
+        )
+    }
+}
+
+/// Splits indices of the given primitive type into triangles, e.g. for
+/// computing per-triangle data on the CPU. Strips and fans may contain
+/// `PRIMITIVE_RESTART_INDEX`. Triangles of strips are oriented consistently
+/// with the first triangle. Returns no triangles for other primitive types.
+pub fn triangles_from_indices(primitive_type: PrimitiveType, indices: &[u32]) -> Vec<[u32; 3]> {
+    match primitive_type {
+        PrimitiveType::TrianglesList => indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+        PrimitiveType::TriangleStrip => indices
+            .split(|&index| index == PRIMITIVE_RESTART_INDEX)
+            .flat_map(|strip| {
+                strip.windows(3).enumerate().map(|(i, t)| {
+                    // Every second triangle of a strip has reversed winding
+                    if i % 2 == 0 {
+                        [t[0], t[1], t[2]]
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+            })
+            .collect(),
+        PrimitiveType::TriangleFan => indices
+            .split(|&index| index == PRIMITIVE_RESTART_INDEX)
+            .filter(|fan| !fan.is_empty())
+            .flat_map(|fan| fan[1..].windows(2).map(move |t| [fan[0], t[0], t[1]]))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: u32 = PRIMITIVE_RESTART_INDEX;
+
+    #[test]
+    fn lists_ignore_incomplete_triangles() {
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TrianglesList, &[0, 1, 2, 3, 4, 5, 6]),
+            vec![[0, 1, 2], [3, 4, 5]],
+        );
+    }
+
+    #[test]
+    fn strips_keep_winding() {
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TriangleStrip, &[0, 1, 2, 3, 4]),
+            vec![[0, 1, 2], [2, 1, 3], [2, 3, 4]],
+        );
+    }
+
+    #[test]
+    fn strips_restart() {
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TriangleStrip, &[0, 1, 2, 3, R, 4, 5, 6]),
+            vec![[0, 1, 2], [2, 1, 3], [4, 5, 6]],
+        );
+
+        // Winding starts over after a restart
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TriangleStrip, &[0, 1, 2, R, 3, 4, 5, 6]),
+            vec![[0, 1, 2], [3, 4, 5], [5, 4, 6]],
+        );
+    }
+
+    #[test]
+    fn strips_skip_short_and_empty_parts() {
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TriangleStrip, &[R, 0, 1, R, R, 2, 3, 4, R]),
+            vec![[2, 3, 4]],
+        );
+    }
+
+    #[test]
+    fn fans_share_first_index() {
+        assert_eq!(
+            triangles_from_indices(PrimitiveType::TriangleFan, &[0, 1, 2, 3, 4]),
+            vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]],
+        );
+    }
+
+    #[test]
+    fn fans_restart() {
+        assert_eq!(
+            triangles_from_indices(
+                PrimitiveType::TriangleFan,
+                &[0, 1, 2, 3, R, 4, 5, 6, R, R, 7]
+            ),
+            vec![[0, 1, 2], [0, 2, 3], [4, 5, 6]],
+        );
+    }
+
+    #[test]
+    fn other_primitives_have_no_triangles() {
+        assert!(triangles_from_indices(PrimitiveType::LinesList, &[0, 1, 2, 3, 4, 5]).is_empty());
+        assert!(triangles_from_indices(PrimitiveType::Points, &[0, 1, 2]).is_empty());
+    }
 }
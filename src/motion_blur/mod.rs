@@ -0,0 +1,126 @@
+//! Camera motion blur as a full-screen postprocessing effect.
+//!
+//! The world position of each pixel is reconstructed from depth and
+//! projected with the previous frame's camera, which gives the velocity of
+//! the pixel on the screen. The input is then sampled along the velocity.
+//!
+//! Only the motion of the camera is taken into account, not that of moving
+//! objects.
+
+mod shaders;
+
+use log::info;
+
+use nalgebra as na;
+
+use glium::texture::DepthTexture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{uniform, Program, Surface, Texture2d};
+
+use crate::shader::{self, ToUniform};
+use crate::{Context, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of samples along the velocity of each pixel.
+    pub num_samples: usize,
+
+    /// Factor for the length of the blur, where 1.0 blurs over the distance
+    /// that a pixel moved since the previous frame.
+    pub strength: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            num_samples: 8,
+            strength: 1.0,
+        }
+    }
+}
+
+pub struct MotionBlur {
+    config: Config,
+    program: Program,
+    screen_quad: ScreenQuad,
+
+    /// Projection-view matrix of the camera in the previous call to `draw`.
+    previous_projection_view: Option<na::Matrix4<f32>>,
+}
+
+impl MotionBlur {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        config: &Config,
+    ) -> Result<Self, CreationError> {
+        info!("Creating motion blur program");
+        let program = shaders::blur_core(config.num_samples)
+            .build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        info!("Creating screen quad");
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(MotionBlur {
+            config: config.clone(),
+            program,
+            screen_quad,
+            previous_projection_view: None,
+        })
+    }
+
+    /// Draws `texture` with motion blur onto the whole `target`, remembering
+    /// the camera of `context` for the next frame.
+    ///
+    /// `depth_texture` must hold the depth of the scene in `texture`, e.g.
+    /// the resolved `depth_texture` of a `RenderTarget`, as drawn with the
+    /// camera of `context`. Logarithmic depth is not supported.
+    pub fn draw<S: Surface>(
+        &mut self,
+        texture: &Texture2d,
+        depth_texture: &DepthTexture2d,
+        context: &Context,
+        target: &mut S,
+    ) -> Result<(), DrawError> {
+        let projection_view = context.projection_matrix() * context.camera.view;
+        let previous_projection_view = self
+            .previous_projection_view
+            .replace(projection_view)
+            .unwrap_or(projection_view);
+        let inverse_projection_view = projection_view
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+
+        let input_map = Sampler::new(texture)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp);
+        let depth_map = Sampler::new(depth_texture)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .wrap_function(SamplerWrapFunction::Clamp);
+
+        target.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.program,
+            &uniform! {
+                input_texture: input_map,
+                depth_texture: depth_map,
+                inverse_projection_view: inverse_projection_view.to_uniform(),
+                previous_projection_view: previous_projection_view.to_uniform(),
+                strength: self.config.strength,
+            },
+            &Default::default(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Forgets the previous camera, so that the next frame is not blurred,
+    /// e.g. after the camera was teleported.
+    pub fn reset(&mut self) {
+        self.previous_projection_view = None;
+    }
+}
@@ -0,0 +1,47 @@
+use glium::uniforms::UniformType;
+
+use crate::{screen_quad, shader};
+
+/// Shader core for blurring `input_texture` along the screen-space velocity
+/// of each pixel, which is found by reconstructing its world position from
+/// `depth_texture` and projecting it with the previous frame's matrix.
+pub fn blur_core(num_samples: usize) -> shader::Core<(), (), screen_quad::Vertex> {
+    let vertex = shader::VertexCore::empty()
+        .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+        .with_out(shader::defs::V_POS, "position");
+
+    let body = format!(
+        "
+        float depth = texture(depth_texture, v_tex_coord).r;
+        vec4 ndc_pos = vec4(v_tex_coord * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+        vec4 world_pos = inverse_projection_view * ndc_pos;
+        world_pos /= world_pos.w;
+
+        vec4 previous_pos = previous_projection_view * world_pos;
+        vec2 previous_tex_coord = (previous_pos.xy / previous_pos.w) * 0.5 + 0.5;
+        vec2 velocity = (v_tex_coord - previous_tex_coord) * strength;
+
+        vec3 blur_result = vec3(0.0);
+
+        for (int i = 0; i < {num_samples}; ++i) {{
+            float t = float(i) / float({num_samples}) - 0.5;
+            blur_result += texture(input_texture, v_tex_coord + velocity * t).rgb;
+        }}
+
+        blur_result /= float({num_samples});
+        ",
+        num_samples = num_samples.max(1),
+    );
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("input_texture", UniformType::Sampler2d)
+        .with_extra_uniform("depth_texture", UniformType::Sampler2d)
+        .with_extra_uniform("inverse_projection_view", UniformType::FloatMat4)
+        .with_extra_uniform("previous_projection_view", UniformType::FloatMat4)
+        .with_extra_uniform("strength", UniformType::Float)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_body(&body)
+        .with_out(shader::defs::F_COLOR, "vec4(blur_result, 1.0)");
+
+    shader::Core { vertex, fragment }
+}
@@ -0,0 +1,179 @@
+//! Outlines around selected instances, using the stencil buffer.
+//!
+//! The instance is first drawn into the stencil buffer only. Then it is drawn
+//! again with its surface pushed outwards along the normals, only where the
+//! stencil buffer has not been marked, which leaves the silhouette border.
+
+use std::marker::PhantomData;
+
+use nalgebra as na;
+
+use glium::draw_parameters::{Stencil, StencilOperation, StencilTest};
+use glium::uniforms::UniformType;
+use glium::Surface;
+
+use crate::scene::SceneCore;
+use crate::shader::{self, ToUniforms};
+use crate::{Context, CreationError, DrawError, DrawStats, Mesh};
+
+/// An instance that is to be outlined, as given by
+/// `RenderList::render_outline`.
+pub struct Outline<'a, I> {
+    pub instance: &'a I,
+    pub color: na::Vector4<f32>,
+
+    /// Width of the outline in world space.
+    pub thickness: f32,
+}
+
+struct OutlineParams {
+    color: na::Vector4<f32>,
+    thickness: f32,
+}
+
+impl_uniform_input!(
+    OutlineParams,
+    self => {
+        outline_color: [f32; 4] = self.color,
+        outline_thickness: f32 = self.thickness,
+    },
+);
+
+/// Programs for drawing outlines of instances of a `SceneCore`.
+///
+/// The target that outlines are drawn into needs a stencil buffer. Outlines
+/// are drawn without depth test, so that they stay visible behind other
+/// objects.
+pub struct OutlinePass<C: SceneCore> {
+    stencil_program: glium::Program,
+    outline_program: glium::Program,
+    phantom: PhantomData<C>,
+}
+
+impl<C: SceneCore> OutlinePass<C> {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        scene_core: C,
+    ) -> Result<Self, CreationError> {
+        let core = scene_core.scene_core();
+
+        assert!(
+            core.vertex.has_out_def(shader::defs::V_WORLD_POS),
+            "VertexCore needs V_WORLD_POS output for outlines"
+        );
+        assert!(
+            core.vertex.has_out_def(shader::defs::V_WORLD_NORMAL),
+            "VertexCore needs V_WORLD_NORMAL output for outlines"
+        );
+
+        let stencil_core = shader::Core {
+            vertex: core.vertex.clone(),
+            fragment: shader::FragmentCore::empty(),
+        };
+
+        let outline_core = shader::Core {
+            vertex: core
+                .vertex
+                .with_extra_uniform("outline_thickness", UniformType::Float)
+                .with_out_expr(
+                    "v_world_pos",
+                    "v_world_pos + outline_thickness * vec4(normalize(v_world_normal), 0.0)",
+                )
+                .with_position_expr(
+                    "context_camera_projection * context_camera_view * v_world_pos",
+                ),
+            fragment: shader::FragmentCore::empty()
+                .with_extra_uniform("outline_color", UniformType::FloatVec4)
+                .with_out(shader::defs::F_COLOR, "outline_color"),
+        };
+
+        Ok(Self {
+            stencil_program: stencil_core
+                .build_program(facade, shader::InstancingMode::Uniforms)?,
+            outline_program: outline_core
+                .build_program(facade, shader::InstancingMode::Uniforms)?,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Draws `outline` into `target`, replacing the contents of its stencil
+    /// buffer.
+    pub fn draw<P, V, S>(
+        &self,
+        outline: &Outline<C::Instance>,
+        mesh: &Mesh<V>,
+        params: (&Context, &P),
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        P: shader::input::CompatibleWith<C::Params>,
+        V: glium::vertex::Vertex,
+        S: Surface,
+    {
+        target.clear_stencil(0);
+
+        let outline_params = OutlineParams {
+            color: outline.color,
+            thickness: outline.thickness,
+        };
+
+        // Mark the instance's pixels in the stencil buffer
+        let stencil_draw_params = glium::DrawParameters {
+            color_mask: (false, false, false, false),
+            stencil: stencil(StencilTest::AlwaysPass),
+            ..Default::default()
+        };
+
+        target
+            .draw(
+                &mesh.vertex_buffer,
+                &mesh.index_buffer,
+                &self.stencil_program,
+                &(params, outline.instance).to_uniforms(),
+                &mesh.draw_params(&stencil_draw_params),
+            )
+            .map_err(|error| DrawError::Instance { index: 0, error })?;
+
+        // Draw the grown instance around the marked pixels
+        let outline_draw_params = glium::DrawParameters {
+            stencil: stencil(StencilTest::IfNotEqual { mask: 0xFF }),
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target
+            .draw(
+                &mesh.vertex_buffer,
+                &mesh.index_buffer,
+                &self.outline_program,
+                &(params, &outline_params, outline.instance).to_uniforms(),
+                &mesh.draw_params(&outline_draw_params),
+            )
+            .map_err(|error| DrawError::Instance { index: 0, error })?;
+
+        Ok(DrawStats {
+            draw_calls: 2,
+            instances: 1,
+            ..Default::default()
+        })
+    }
+}
+
+/// Stencil state that compares with and writes the reference value one, for
+/// both front and back faces.
+fn stencil(test: StencilTest) -> Stencil {
+    Stencil {
+        test_clockwise: test,
+        reference_value_clockwise: 1,
+        write_mask_clockwise: 0xFF,
+        fail_operation_clockwise: StencilOperation::Keep,
+        pass_depth_fail_operation_clockwise: StencilOperation::Keep,
+        depth_pass_operation_clockwise: StencilOperation::Replace,
+        test_counter_clockwise: test,
+        reference_value_counter_clockwise: 1,
+        write_mask_counter_clockwise: 0xFF,
+        fail_operation_counter_clockwise: StencilOperation::Keep,
+        pass_depth_fail_operation_counter_clockwise: StencilOperation::Keep,
+        depth_pass_operation_counter_clockwise: StencilOperation::Replace,
+    }
+}
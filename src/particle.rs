@@ -0,0 +1,174 @@
+//! Simple particle effects that are simulated on the CPU and drawn as
+//! billboards.
+
+use nalgebra as na;
+
+use crate::basic_obj::billboard;
+use crate::RenderList;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of particles that are spawned per second.
+    pub spawn_rate: f32,
+
+    /// Time in seconds until a particle disappears.
+    pub lifetime: f32,
+
+    /// Particles are not spawned while this many are alive.
+    pub max_particles: usize,
+
+    pub initial_velocity: na::Vector3<f32>,
+
+    /// Maximal random deviation of each component of the initial velocity.
+    pub velocity_spread: f32,
+
+    /// Acceleration that is applied to all particles.
+    pub gravity: na::Vector3<f32>,
+
+    pub size: na::Vector2<f32>,
+
+    /// Color at the start of a particle's lifetime, which blends linearly
+    /// into `end_color` until the end of its lifetime.
+    pub start_color: na::Vector4<f32>,
+    pub end_color: na::Vector4<f32>,
+
+    /// Region of the billboard atlas that is used for all particles.
+    pub atlas_region: na::Vector4<f32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 50.0,
+            lifetime: 2.0,
+            max_particles: 1000,
+            initial_velocity: na::Vector3::new(0.0, 0.0, 2.0),
+            velocity_spread: 0.5,
+            gravity: na::Vector3::new(0.0, 0.0, -9.81),
+            size: na::Vector2::new(0.1, 0.1),
+            start_color: na::Vector4::new(1.0, 1.0, 1.0, 1.0),
+            end_color: na::Vector4::new(1.0, 1.0, 1.0, 0.0),
+            atlas_region: na::Vector4::new(0.0, 0.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub position: na::Point3<f32>,
+    pub velocity: na::Vector3<f32>,
+
+    /// Time in seconds since the particle was spawned.
+    pub age: f32,
+}
+
+/// Spawns particles at `origin` and moves them.
+///
+/// The particles are not drawn by the system itself. Instead,
+/// `add_instances` converts them into instances of `basic_obj::billboard`.
+/// Since particles are usually transparent, they should be drawn after the
+/// opaque parts of the scene.
+#[derive(Debug, Clone)]
+pub struct ParticleSystem {
+    pub config: Config,
+    pub origin: na::Point3<f32>,
+
+    /// Whether new particles are spawned. Existing particles keep moving
+    /// until the end of their lifetime.
+    pub spawning: bool,
+
+    particles: Vec<Particle>,
+
+    /// Fractional number of particles that are due to be spawned.
+    spawn_budget: f32,
+
+    rng_state: u32,
+}
+
+impl ParticleSystem {
+    pub fn new(config: Config, origin: na::Point3<f32>) -> Self {
+        Self {
+            config,
+            origin,
+            spawning: true,
+            particles: Vec::new(),
+            spawn_budget: 0.0,
+            rng_state: 0x9e37_79b9,
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Removes all particles.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_budget = 0.0;
+    }
+
+    /// Advances the simulation by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let lifetime = self.config.lifetime;
+        self.particles
+            .retain(|particle| particle.age + dt < lifetime);
+
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+            particle.velocity += self.config.gravity * dt;
+            particle.position += particle.velocity * dt;
+        }
+
+        if self.spawning {
+            self.spawn_budget += self.config.spawn_rate * dt;
+        }
+
+        while self.spawn_budget >= 1.0 {
+            self.spawn_budget -= 1.0;
+
+            if self.particles.len() < self.config.max_particles {
+                self.spawn();
+            }
+        }
+    }
+
+    /// Adds a billboard instance for each particle to `render_list`.
+    pub fn add_instances(&self, render_list: &mut RenderList<billboard::Instance>) {
+        for particle in self.particles.iter() {
+            let t = if self.config.lifetime > 0.0 {
+                (particle.age / self.config.lifetime).min(1.0)
+            } else {
+                1.0
+            };
+
+            render_list.add(billboard::Instance {
+                center: particle.position,
+                size: self.config.size,
+                color: self.config.start_color
+                    + (self.config.end_color - self.config.start_color) * t,
+                atlas_region: self.config.atlas_region,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn spawn(&mut self) {
+        let spread = na::Vector3::new(self.random(), self.random(), self.random());
+
+        self.particles.push(Particle {
+            position: self.origin,
+            velocity: self.config.initial_velocity
+                + (spread * 2.0 - na::Vector3::repeat(1.0)) * self.config.velocity_spread,
+            age: 0.0,
+        });
+    }
+
+    /// Returns a pseudo-random number in `[0, 1)`, using xorshift.
+    fn random(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+
+        (self.rng_state >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
@@ -1,23 +1,27 @@
 use log::info;
 
 use crate::scene::SceneCore;
-use crate::shader::InstancingMode;
-use crate::{fxaa, screen_quad, shader, Context, DrawError, Drawable};
+use crate::shader::{InstancingMode, ToUniforms};
+use crate::{fxaa, screen_quad, shader, Context, DrawError, DrawStats, Drawable};
 
-use crate::pipeline::config::Config;
+use crate::pipeline::config::{Config, Transparency};
 use crate::pipeline::deferred::{self, DeferredShading};
 use crate::pipeline::glow::{self, Glow};
+use crate::pipeline::oit::{self, WeightedBlended};
 use crate::pipeline::render_pass::{
     CompositionPassComponent, RenderPassComponent, ScenePassComponent, ShadedScenePass,
     ShadedScenePassSetup, ShadowPass,
 };
 use crate::pipeline::shaders;
 use crate::pipeline::shadow::{self, ShadowMapping};
+use crate::pipeline::ssao::{self, Ssao};
 
 pub struct Components {
     pub shadow_mapping: Option<ShadowMapping>,
     pub deferred_shading: Option<DeferredShading>,
+    pub ssao: Option<Ssao>,
     pub glow: Option<Glow>,
+    pub weighted_blended: Option<WeightedBlended>,
 }
 
 impl Components {
@@ -42,6 +46,17 @@ impl Components {
             .transpose()
             .map_err(CreationError::DeferredShading)?;
 
+        if config.ssao.is_some() && deferred_shading.is_none() {
+            return Err(CreationError::SsaoRequiresDeferredShading);
+        }
+
+        let ssao = config
+            .ssao
+            .as_ref()
+            .map(|config| Ssao::create(facade, config, target_size))
+            .transpose()
+            .map_err(CreationError::Ssao)?;
+
         let glow = config
             .glow
             .as_ref()
@@ -49,10 +64,18 @@ impl Components {
             .transpose()
             .map_err(CreationError::Glow)?;
 
+        let weighted_blended = if config.transparency == Transparency::WeightedBlended {
+            Some(WeightedBlended::create(facade, target_size).map_err(CreationError::Oit)?)
+        } else {
+            None
+        };
+
         Ok(Self {
             shadow_mapping,
             deferred_shading,
+            ssao,
             glow,
+            weighted_blended,
         })
     }
 
@@ -100,7 +123,18 @@ impl Components {
     {
         info!("Creating scene pass for `C={}`", std::any::type_name::<C>());
 
-        let mut shader_core = scene_core.scene_core();
+        let mut shader_core =
+            shaders::invariant_position_core_transform(shaders::log_depth_core_transform(
+                shaders::context_block_core_transform(scene_core.scene_core()),
+            ));
+
+        if setup.linearize_colors {
+            shader_core = shaders::linearize_color_core_transform(shader_core);
+        }
+
+        if setup.two_sided_lighting {
+            shader_core = shaders::two_sided_lighting_core_transform(shader_core);
+        }
 
         if let Some(glow) = self.glow.as_ref() {
             if setup.draw_glowing {
@@ -118,9 +152,14 @@ impl Components {
         }
 
         if let Some(deferred_shading) = self.deferred_shading.as_ref() {
+            // Deferred lighting multiplies the color buffer, so tinting the
+            // color before lighting gives the same result.
+            shader_core = shaders::tint_core_transform(shader_core);
             shader_core = ScenePassComponent::core_transform(deferred_shading, shader_core);
         } else {
             shader_core = shaders::diffuse_scene_core_transform(shader_core);
+            shader_core = shaders::tint_core_transform(shader_core);
+            shader_core = shaders::fog_core_transform(shader_core);
         }
 
         let program = shader_core.build_program(facade, instancing_mode)?;
@@ -143,13 +182,20 @@ impl Components {
             shader_core = CompositionPassComponent::core_transform(deferred_shading, shader_core);
         }
 
+        if let Some(ssao) = self.ssao.as_ref() {
+            shader_core = CompositionPassComponent::core_transform(ssao, shader_core);
+        }
+
+        if self.deferred_shading.is_some() {
+            shader_core = shaders::fog_composition_core_transform(shader_core);
+        }
+
         if let Some(glow) = self.glow.as_ref() {
             shader_core = CompositionPassComponent::core_transform(glow, shader_core);
         }
 
-        if let Some(_) = config.hdr {
-            // TODO: Use factor
-            shader_core = shaders::hdr_composition_core_transform(shader_core);
+        if let Some(tone_map) = config.hdr.as_ref() {
+            shader_core = shaders::hdr_composition_core_transform(shader_core, tone_map);
         }
 
         if let Some(gamma) = config.gamma_correction {
@@ -168,30 +214,43 @@ impl Components {
             .as_ref()
             .map(|c| c.clear_buffers(facade))
             .transpose()?;
+        self.ssao
+            .as_ref()
+            .map(|c| c.clear_buffers(facade))
+            .transpose()?;
         self.glow
             .as_ref()
             .map(|c| c.clear_buffers(facade))
             .transpose()?;
+        self.weighted_blended
+            .as_ref()
+            .map(|c| c.clear_buffers(facade))
+            .transpose()?;
 
         Ok(())
     }
 
-    pub fn scene_pass<C, D, P, S>(
+    /// Draws with a program of `create_shaded_scene_pass`. The `Context` of
+    /// `params` is read by the components, while the program reads it from
+    /// `context_uniforms`, which bind the `ContextBlock`.
+    pub fn scene_pass<C, D, P, U, S>(
         &self,
         drawable: &D,
         program: &glium::Program,
         params: (&Context, &P),
+        context_uniforms: U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         C: SceneCore,
         D: Drawable<C::Instance, C::Vertex>,
         P: shader::input::CompatibleWith<C::Params>,
+        U: ToUniforms,
         S: glium::Surface,
     {
         let uniforms = (
-            params,
+            (context_uniforms, params.1),
             self.shadow_mapping
                 .as_ref()
                 .map(|c| ScenePassComponent::params(c, params.0)),
@@ -215,10 +274,18 @@ impl Components {
             deferred_shading.on_target_resize(facade, target_size)?;
         }
 
+        if let Some(ssao) = self.ssao.as_mut() {
+            ssao.on_target_resize(facade, target_size)?;
+        }
+
         if let Some(glow) = self.glow.as_mut() {
             glow.on_target_resize(facade, target_size)?;
         }
 
+        if let Some(weighted_blended) = self.weighted_blended.as_mut() {
+            weighted_blended.on_target_resize(facade, target_size)?;
+        }
+
         Ok(())
     }
 
@@ -248,7 +315,10 @@ impl Components {
 pub enum CreationError {
     ShadowMapping(shadow::CreationError),
     DeferredShading(deferred::CreationError),
+    Ssao(ssao::CreationError),
+    SsaoRequiresDeferredShading,
     Glow(glow::CreationError),
+    Oit(oit::CreationError),
     FXAA(fxaa::CreationError),
     CreationError(crate::CreationError),
 }
@@ -1,14 +1,67 @@
 use crate::fxaa;
-use crate::pipeline::{deferred, glow, shadow};
+use crate::pipeline::{deferred, glow, shadow, ssao};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    Reinhard,
+
+    /// Filmic curve, as approximated by Krzysztof Narkowicz.
+    Aces,
+}
+
+/// Maps HDR colors to the displayable range in the composition pass.
+#[derive(Debug, Clone)]
+pub struct ToneMap {
+    /// Factor that colors are multiplied with before tone mapping.
+    pub exposure: f32,
+    pub operator: ToneMapOperator,
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            operator: ToneMapOperator::Reinhard,
+        }
+    }
+}
+
+/// How transparent instances are blended in the plain scene pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    /// Blend instances in the order in which they are drawn, so they need to
+    /// be sorted back-to-front.
+    Sorted,
+
+    /// Weighted blended order-independent transparency, which needs no
+    /// sorting and handles intersecting surfaces, but only approximates the
+    /// result of blending in order. Needs float render targets.
+    WeightedBlended,
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Transparency::Sorted
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub shadow_mapping: Option<shadow::Config>,
+
+    /// Deferred shading is needed for lighting the scene with more than the
+    /// main light.
     pub deferred_shading: Option<deferred::Config>,
+
+    /// Screen-space ambient occlusion. Requires deferred shading.
+    pub ssao: Option<ssao::Config>,
     pub glow: Option<glow::Config>,
-    pub hdr: Option<f32>,
+    pub hdr: Option<ToneMap>,
     pub gamma_correction: Option<f32>,
     pub fxaa: Option<fxaa::Config>,
+
+    /// How `TransparentPass`es blend.
+    pub transparency: Transparency,
 }
 
 impl Default for Config {
@@ -16,10 +69,12 @@ impl Default for Config {
         Self {
             shadow_mapping: Some(Default::default()),
             deferred_shading: Some(Default::default()),
+            ssao: None,
             glow: Some(Default::default()),
             hdr: None,
             gamma_correction: Some(2.2),
             fxaa: Some(Default::default()),
+            transparency: Transparency::Sorted,
         }
     }
 }
@@ -1,5 +1,14 @@
 //! Deferred shading.
 //!
+//! Enable this with `Config::deferred_shading`. The shaded scene pass then
+//! writes world positions, world normals and material coefficients into
+//! separate textures, drawing the same drawables (e.g. a `RenderList`) as
+//! without deferred shading. In `ShadedScenePassStep::compose`, each light is
+//! accumulated per pixel by drawing its bounding sphere, or a screen quad for
+//! the main light and directional lights. The cost of a light is thus
+//! proportional to the number of pixels it covers, so that hundreds of point
+//! lights are feasible.
+//!
 //! Heavily inspired by:
 //! https://github.com/glium/glium/blob/master/examples/deferred.rs
 
@@ -11,10 +20,10 @@ use nalgebra as na;
 
 use glium::{uniform, Surface, Texture2d};
 
-use crate::shader::{self, InstanceInput, ToUniforms};
+use crate::shader::{self, ToUniform, ToUniforms};
 use crate::{
     basic_obj, screen_quad, BasicObj, Camera, Context, DrawError, Drawable, Instancing, Light,
-    Mesh, ScreenQuad,
+    Mesh, Projection, ScreenQuad,
 };
 
 use crate::pipeline::render_pass::{
@@ -37,7 +46,7 @@ impl Default for Config {
     }
 }
 
-const NUM_TEXTURES: usize = 2;
+const NUM_TEXTURES: usize = 4;
 
 pub struct DeferredShading {
     config: Config,
@@ -48,12 +57,13 @@ pub struct DeferredShading {
     light_texture: Texture2d,
 
     main_light_screen_quad_program: glium::Program,
+    light_screen_quad_program: glium::Program,
     light_object_program: glium::Program,
 
     screen_quad: ScreenQuad,
     sphere: Mesh<basic_obj::Vertex>,
 
-    light_instances: Vec<<Light as InstanceInput>::Vertex>,
+    light_instances: Vec<Light>,
     light_instancing: Instancing<Light>,
 }
 
@@ -86,6 +96,8 @@ impl ScenePassComponent for DeferredShading {
         let mut result = vec![
             ("f_world_pos", &self.scene_textures[0]),
             ("f_world_normal", &self.scene_textures[1]),
+            ("f_material", &self.scene_textures[2]),
+            ("f_specular_color", &self.scene_textures[3]),
         ];
 
         if let Some(shadow_texture) = self.shadow_texture.as_ref() {
@@ -100,14 +112,18 @@ impl ScenePassComponent for DeferredShading {
 
 pub struct CompositionPassParams<'a> {
     light_texture: &'a Texture2d,
+    position_texture: &'a Texture2d,
     normal_texture: &'a Texture2d,
+    material_texture: &'a Texture2d,
 }
 
 impl_uniform_input!(
     CompositionPassParams<'a>,
     self => {
         light_texture: &'a Texture2d = self.light_texture,
+        position_texture: &'a Texture2d = self.position_texture,
         normal_texture: &'a Texture2d = self.normal_texture,
+        material_texture: &'a Texture2d = self.material_texture,
     },
 );
 
@@ -126,7 +142,9 @@ impl CompositionPassComponent for DeferredShading {
     fn params(&self) -> CompositionPassParams {
         CompositionPassParams {
             light_texture: &self.light_texture,
+            position_texture: &self.scene_textures[0],
             normal_texture: &self.scene_textures[1],
+            material_texture: &self.scene_textures[2],
         }
     }
 }
@@ -142,6 +160,8 @@ impl DeferredShading {
         let scene_textures = [
             Self::create_texture(facade, target_size)?,
             Self::create_texture(facade, target_size)?,
+            Self::create_texture(facade, target_size)?,
+            Self::create_texture(facade, target_size)?,
         ];
         let shadow_texture = if have_shadows {
             Some(Self::create_shadow_texture(facade, target_size)?)
@@ -150,13 +170,19 @@ impl DeferredShading {
         };
         let light_texture = Self::create_texture(facade, target_size)?;
 
+        info!("Creating light buffers");
+        let light_instancing = Instancing::create(facade)?;
+
         info!("Creating deferred light programs");
-        let main_light_screen_quad_core = shaders::main_light_screen_quad_core(have_shadows);
+        let main_light_screen_quad_core = shaders::light_screen_quad_core(have_shadows);
         let main_light_screen_quad_program =
             main_light_screen_quad_core.build_program(facade, shader::InstancingMode::Uniforms)?;
+        let light_screen_quad_core = shaders::light_screen_quad_core(false);
+        let light_screen_quad_program =
+            light_screen_quad_core.build_program(facade, shader::InstancingMode::Uniforms)?;
         let light_object_core = shaders::light_object_core();
         let light_object_program =
-            light_object_core.build_program(facade, shader::InstancingMode::Vertex)?;
+            light_object_core.build_program(facade, light_instancing.mode())?;
 
         info!("Creating screen quad");
         let screen_quad = ScreenQuad::create(facade)?;
@@ -164,9 +190,6 @@ impl DeferredShading {
         info!("Creating sphere");
         let sphere = BasicObj::Sphere.create_mesh(facade)?;
 
-        info!("Creating light buffers");
-        let light_instancing = Instancing::create(facade)?;
-
         info!("Deferred shading initialized");
 
         Ok(DeferredShading {
@@ -175,6 +198,7 @@ impl DeferredShading {
             shadow_texture,
             light_texture,
             main_light_screen_quad_program,
+            light_screen_quad_program,
             light_object_program,
             screen_quad,
             sphere,
@@ -196,6 +220,8 @@ impl DeferredShading {
         self.scene_textures = [
             Self::create_texture(facade, target_size)?,
             Self::create_texture(facade, target_size)?,
+            Self::create_texture(facade, target_size)?,
+            Self::create_texture(facade, target_size)?,
         ];
 
         if let Some(shadow_texture) = self.shadow_texture.as_mut() {
@@ -207,6 +233,16 @@ impl DeferredShading {
         Ok(())
     }
 
+    /// World-space positions of the scene, as written in the scene pass.
+    pub fn position_texture(&self) -> &Texture2d {
+        &self.scene_textures[0]
+    }
+
+    /// World-space normals of the scene, as written in the scene pass.
+    pub fn normal_texture(&self) -> &Texture2d {
+        &self.scene_textures[1]
+    }
+
     pub fn light_pass<F: glium::backend::Facade>(
         &mut self,
         facade: &F,
@@ -238,6 +274,9 @@ impl DeferredShading {
             &uniform! {
                 position_texture: &self.scene_textures[0],
                 normal_texture: &self.scene_textures[1],
+                material_texture: &self.scene_textures[2],
+                specular_texture: &self.scene_textures[3],
+                eye_position: camera.eye().to_uniform(),
             },
             &self.shadow_texture.as_ref().map(|shadow_texture| {
                 plain_uniforms! {
@@ -248,7 +287,7 @@ impl DeferredShading {
 
         self.light_instances.clear();
         for light in lights {
-            if light.is_main {
+            if light.is_main || light.kind.is_directional() {
                 continue;
             }
 
@@ -264,32 +303,38 @@ impl DeferredShading {
                 ..light.clone()
             };
 
-            self.light_instances.push(light.to_vertex());
+            self.light_instances.push(light);
         }
 
         self.light_instancing
             .update(facade, &self.light_instances)?;
 
-        // Draw main light
+        // Draw main light and directional lights, which cover the whole screen
         for light in lights.iter() {
-            if light.is_main {
-                // Fragment shader uses viewport size, but we don't need view/projection
-                let no_camera = Camera {
-                    view: na::Matrix4::identity(),
-                    projection: na::Matrix4::identity(),
-                    viewport_size: camera.viewport_size,
-                };
-
-                let uniforms = (&textures, (no_camera, &light));
-
-                light_buffer.draw(
-                    &self.screen_quad.vertex_buffer,
-                    &self.screen_quad.index_buffer,
-                    &self.main_light_screen_quad_program,
-                    &uniforms.to_uniforms(),
-                    &draw_params,
-                )?;
-            }
+            let program = if light.is_main {
+                &self.main_light_screen_quad_program
+            } else if light.kind.is_directional() {
+                &self.light_screen_quad_program
+            } else {
+                continue;
+            };
+
+            // Fragment shader uses viewport size, but we don't need view/projection
+            let no_camera = Camera {
+                view: na::Matrix4::identity(),
+                projection: Projection::Custom(na::Matrix4::identity()),
+                viewport_size: camera.viewport_size,
+            };
+
+            let uniforms = (&textures, (no_camera, &light));
+
+            light_buffer.draw(
+                &self.screen_quad.vertex_buffer,
+                &self.screen_quad.index_buffer,
+                program,
+                &uniforms.to_uniforms(),
+                &draw_params,
+            )?;
         }
 
         // Draw additional light using instancing
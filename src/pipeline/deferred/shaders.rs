@@ -1,5 +1,6 @@
 use glium::uniforms::UniformType;
 
+use crate::pipeline::shaders::{material_exprs, normal_expr};
 use crate::pipeline::Light;
 use crate::{basic_obj, screen_quad, shader, Camera, Context};
 
@@ -13,6 +14,18 @@ pub const F_WORLD_NORMAL: (&str, shader::FragmentOutDef) = (
     shader::FragmentOutDef(shader::Type::FloatVec4, shader::FragmentOutQualifier::Yield),
 );
 
+/// Material coefficients in the order ambient, diffuse and shininess.
+pub const F_MATERIAL: (&str, shader::FragmentOutDef) = (
+    "f_material",
+    shader::FragmentOutDef(shader::Type::FloatVec4, shader::FragmentOutQualifier::Yield),
+);
+
+/// Specular color of the material.
+pub const F_SPECULAR_COLOR: (&str, shader::FragmentOutDef) = (
+    "f_specular_color",
+    shader::FragmentOutDef(shader::Type::FloatVec4, shader::FragmentOutQualifier::Yield),
+);
+
 /// Shader core transform for writing position/normal/color into separate
 /// buffers, so that they may be combined in a subsequent pass.
 pub fn scene_buffers_core_transform<P, I, V>(
@@ -32,12 +45,19 @@ pub fn scene_buffers_core_transform<P, I, V>(
         "FragmentCore needs F_COLOR output for deferred shading scene pass"
     );
 
+    let world_normal_expr = format!("vec4(normalize({}), 0.0)", normal_expr(&core.fragment));
+    let [ambient, diffuse, specular, shininess] = material_exprs(&core.fragment);
+    let material_expr = format!("vec4({}, {}, {}, 0.0)", ambient, diffuse, shininess);
+    let specular_expr = format!("vec4({}, 1.0)", specular);
+
     let mut fragment = core
         .fragment
         .with_in_def(shader::defs::V_WORLD_POS)
         .with_in_def(shader::defs::V_WORLD_NORMAL)
         .with_out(F_WORLD_POS, "v_world_pos")
-        .with_out(F_WORLD_NORMAL, "vec4(v_world_normal, 0.0)");
+        .with_out(F_WORLD_NORMAL, &world_normal_expr)
+        .with_out(F_MATERIAL, &material_expr)
+        .with_out(F_SPECULAR_COLOR, &specular_expr);
 
     // We may have the case that we want to attach an `f_shadow` output, but
     // the given `core` does not provide any shadow values (i.e. it wants to
@@ -59,6 +79,21 @@ pub fn scene_buffers_core_transform<P, I, V>(
     }
 }
 
+const V_LIGHT_KIND: (&str, shader::VertexOutDef) = (
+    "v_light_kind",
+    shader::VertexOutDef(shader::Type::Int, shader::VertexOutQualifier::Flat),
+);
+
+const V_LIGHT_DIRECTION: (&str, shader::VertexOutDef) = (
+    "v_light_direction",
+    shader::VertexOutDef(shader::Type::FloatVec3, shader::VertexOutQualifier::Flat),
+);
+
+const V_LIGHT_SPOT_ANGLES: (&str, shader::VertexOutDef) = (
+    "v_light_spot_angles",
+    shader::VertexOutDef(shader::Type::FloatVec2, shader::VertexOutQualifier::Flat),
+);
+
 const V_LIGHT_POS: (&str, shader::VertexOutDef) = (
     "v_light_pos",
     shader::VertexOutDef(shader::Type::FloatVec3, shader::VertexOutQualifier::Flat),
@@ -78,27 +113,75 @@ fn light_fragment_core() -> shader::FragmentCore<Camera> {
     shader::FragmentCore::empty()
         .with_extra_uniform("position_texture", UniformType::Sampler2d)
         .with_extra_uniform("normal_texture", UniformType::Sampler2d)
+        .with_extra_uniform("material_texture", UniformType::Sampler2d)
+        .with_extra_uniform("specular_texture", UniformType::Sampler2d)
+        .with_extra_uniform("eye_position", UniformType::FloatVec3)
+        .with_in_def(V_LIGHT_KIND)
+        .with_in_def(V_LIGHT_DIRECTION)
+        .with_in_def(V_LIGHT_SPOT_ANGLES)
         .with_in_def(V_LIGHT_POS)
         .with_in_def(V_LIGHT_COLOR)
         .with_in_def(V_LIGHT_ATTENUATION)
+        .with_defs(
+            "
+            // Must match `LightKind::index`.
+            const int LIGHT_KIND_POINT = 0;
+            const int LIGHT_KIND_DIRECTIONAL = 1;
+            const int LIGHT_KIND_SPOT = 2;
+            ",
+        )
         .with_body(
             "
             vec2 tex_coord = gl_FragCoord.xy / camera_viewport_size;
             vec3 position = texture(position_texture, tex_coord).xyz;
             vec3 normal = texture(normal_texture, tex_coord).xyz;
+            vec4 material = texture(material_texture, tex_coord);
 
-            vec3 light_vector = v_light_pos - position;
-            float light_distance_sq = dot(light_vector, light_vector);
-            float light_distance = sqrt(light_distance_sq);
+            vec3 specular_color = texture(specular_texture, tex_coord).rgb;
+            float shininess = material.z;
 
-            float diffuse = max(dot(normal, light_vector / light_distance), 0.0);
-            float attenuation = 1.0 / dot(
-                v_light_attenuation,
-                vec3(1, light_distance, light_distance_sq)
-            );
-            diffuse *= attenuation;
+            vec3 light_dir;
+            float attenuation;
+
+            if (v_light_kind == LIGHT_KIND_DIRECTIONAL) {
+                light_dir = -normalize(v_light_direction);
+                attenuation = 1.0;
+            } else {
+                vec3 light_vector = v_light_pos - position;
+                float light_distance_sq = dot(light_vector, light_vector);
+                float light_distance = sqrt(light_distance_sq);
+
+                light_dir = light_vector / light_distance;
+                attenuation = 1.0 / dot(
+                    v_light_attenuation,
+                    vec3(1, light_distance, light_distance_sq)
+                );
+
+                if (v_light_kind == LIGHT_KIND_SPOT) {
+                    float spot_cos = dot(-light_dir, normalize(v_light_direction));
+                    attenuation *= smoothstep(
+                        cos(v_light_spot_angles.y),
+                        cos(v_light_spot_angles.x),
+                        spot_cos
+                    );
+                }
+            }
+
+            float n_dot_l = max(dot(normal, light_dir), 0.0);
+            float diffuse = n_dot_l * attenuation * material.y;
+
+            // Blinn-Phong highlights
+            vec3 specular = vec3(0.0);
+            if (shininess > 0.0 && n_dot_l > 0.0) {
+                vec3 view_dir = normalize(eye_position - position);
+                vec3 half_dir = normalize(light_dir + view_dir);
+
+                specular = specular_color
+                    * pow(max(dot(normal, half_dir), 0.0), shininess)
+                    * attenuation;
+            }
 
-            float radiance = diffuse;
+            vec3 radiance = diffuse + specular;
             ",
         )
         .with_out(shader::defs::F_COLOR, "vec4(v_light_color * radiance, 1.0)")
@@ -106,10 +189,16 @@ fn light_fragment_core() -> shader::FragmentCore<Camera> {
 
 /// Shader core for rendering a light source, given the position/normal buffers
 /// from the scene pass.
-pub fn main_light_screen_quad_core(
+///
+/// This covers the whole screen, so it is used for the main light and for
+/// directional lights, which have no bounded volume.
+pub fn light_screen_quad_core(
     have_shadows: bool,
 ) -> shader::Core<Camera, Light, screen_quad::Vertex> {
     let vertex = shader::VertexCore::default()
+        .with_out(V_LIGHT_KIND, "light_kind")
+        .with_out(V_LIGHT_DIRECTION, "light_direction")
+        .with_out(V_LIGHT_SPOT_ANGLES, "light_spot_angles")
         .with_out(V_LIGHT_POS, "light_position")
         .with_out(V_LIGHT_COLOR, "light_color")
         .with_out(V_LIGHT_ATTENUATION, "light_attenuation")
@@ -131,6 +220,9 @@ pub fn main_light_screen_quad_core(
 
 pub fn light_object_core() -> shader::Core<Camera, Light, basic_obj::Vertex> {
     let vertex = shader::VertexCore::default()
+        .with_out(V_LIGHT_KIND, "light_kind")
+        .with_out(V_LIGHT_DIRECTION, "light_direction")
+        .with_out(V_LIGHT_SPOT_ANGLES, "light_spot_angles")
         .with_out(V_LIGHT_POS, "light_position")
         .with_out(V_LIGHT_COLOR, "light_color")
         .with_out(V_LIGHT_ATTENUATION, "light_attenuation")
@@ -166,12 +258,14 @@ pub fn composition_core_transform(
         .fragment
         .with_extra_uniform("light_texture", UniformType::Sampler2d)
         .with_extra_uniform("normal_texture", UniformType::Sampler2d)
+        .with_extra_uniform("material_texture", UniformType::Sampler2d)
         .with_body(
             "
             vec4 light_value = texture(light_texture, v_tex_coord);
             vec4 normal_value = texture(normal_texture, v_tex_coord);
+            float ambient = texture(material_texture, v_tex_coord).x;
 
-            vec4 lighting = vec4(light_value.rgb + context_ambient_light, 1.0);
+            vec4 lighting = vec4(light_value.rgb + ambient * context_ambient_light, 1.0);
 
             // Keep background color as-is.
             // TODO: There are definitely more efficient ways to do this,
@@ -1,31 +1,43 @@
 mod config;
 pub mod deferred;
 pub mod glow;
+pub mod oit;
 pub mod render_pass;
 pub mod shaders;
 pub mod shadow;
+pub mod ssao;
 
 mod components;
 
 use coarse_prof::profile;
 use log::info;
 
+use nalgebra as na;
+
 use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
 use glium::texture::{
     DepthFormat, DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat,
 };
+use glium::uniforms::UniformBuffer;
 use glium::{uniform, Program, Surface};
 
 use crate::fxaa::{self, FXAA};
 use crate::scene::SceneCore;
-use crate::shader::{InstancingMode, ToUniforms};
-use crate::{shader, Context, DrawError, Drawable, Light, ScreenQuad};
+use crate::shader::input::{MyEmptyUniforms, MyUniformsStorage};
+use crate::shader::{InstancingMode, ToUniform, ToUniforms};
+use crate::{
+    shader, BlendMode, Context, ContextBlock, DrawError, DrawStats, Drawable, Light, ScreenQuad,
+    Skybox,
+};
 
 use components::Components;
-use render_pass::CompositionPassComponent;
+use render_pass::{CompositionPassComponent, RenderPassComponent};
 
-pub use config::Config;
-pub use render_pass::{PlainScenePass, ShadedScenePass, ShadedScenePassSetup, ShadowPass};
+pub use config::{Config, ToneMap, ToneMapOperator, Transparency};
+pub use render_pass::{
+    DepthConfig, DepthPrePass, NormalSpace, PlainScenePass, PolygonOffset, ShadedScenePass,
+    ShadedScenePassSetup, ShadowPass, TransparentPass, WireframePass,
+};
 
 pub struct Pipeline {
     components: Components,
@@ -39,9 +51,15 @@ pub struct Pipeline {
     composition_program: Program,
     copy_texture_program: Program,
 
+    /// The `Context` of the current frame, which is read by the programs of
+    /// the scene passes.
+    context_buffer: UniformBuffer<ContextBlock>,
+
     fxaa: Option<FXAA>,
 
     screen_quad: ScreenQuad,
+
+    last_frame_stats: DrawStats,
 }
 
 struct StepContext<'a, F, S> {
@@ -50,11 +68,27 @@ struct StepContext<'a, F, S> {
     facade: &'a F,
     context: Context,
     target: &'a mut S,
+    stats: DrawStats,
+
+    /// Whether the scene depth buffer holds the depth of a pre-pass, so that
+    /// the shaded scene pass only needs to test for equal depth.
+    depth_prepass_drawn: bool,
+
+    /// Whether order-independent transparency has been drawn that still
+    /// needs to be resolved into the composition.
+    transparency_pending: bool,
+
+    /// The part of `target` that the frame is presented into, as given to
+    /// `Pipeline::start_frame_in_viewport`.
+    viewport: Option<glium::Rect>,
 }
 
 #[must_use]
 pub struct StartFrameStep<'a, F, S>(StepContext<'a, F, S>);
 
+#[must_use]
+pub struct DepthPrePassStep<'a, F, S>(StepContext<'a, F, S>);
+
 #[must_use]
 pub struct ShadowPassStep<'a, F, S>(StepContext<'a, F, S>);
 
@@ -105,6 +139,8 @@ impl Pipeline {
         info!("Creating screen quad");
         let screen_quad = ScreenQuad::create(facade)?;
 
+        let context_buffer = UniformBuffer::empty(facade).map_err(crate::CreationError::from)?;
+
         info!("Pipeline initialized");
 
         Ok(Pipeline {
@@ -116,8 +152,44 @@ impl Pipeline {
             postprocess_texture,
             composition_program,
             copy_texture_program,
+            context_buffer,
             fxaa,
             screen_quad,
+            last_frame_stats: DrawStats::default(),
+        })
+    }
+
+    /// Creates a pass that only writes the depth of a scene core.
+    ///
+    /// Drawing the opaque parts of the scene in a depth pre-pass allows the
+    /// shaded scene pass to discard hidden fragments before shading them,
+    /// reducing overdraw.
+    pub fn create_depth_prepass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        instancing_mode: InstancingMode,
+    ) -> Result<DepthPrePass<C>, crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        info!(
+            "Creating depth pre-pass for `C={}`",
+            std::any::type_name::<C>()
+        );
+
+        let shader_core = shaders::invariant_position_core_transform(
+            shaders::depth_only_core_transform(shaders::log_depth_core_transform(
+                shaders::context_block_core_transform(scene_core.scene_core()),
+            )),
+        );
+        let program = shader_core.build_program(facade, instancing_mode)?;
+
+        Ok(DepthPrePass {
+            instancing_mode,
+            program,
+            shader_core,
         })
     }
 
@@ -160,7 +232,9 @@ impl Pipeline {
         F: glium::backend::Facade,
         C: SceneCore,
     {
-        let shader_core = scene_core.scene_core();
+        let shader_core = shaders::tint_core_transform(shaders::log_depth_core_transform(
+            shaders::context_block_core_transform(scene_core.scene_core()),
+        ));
         let program = shader_core.build_program(facade, instancing_mode)?;
 
         Ok(PlainScenePass {
@@ -170,24 +244,243 @@ impl Pipeline {
         })
     }
 
+    /// Creates a pass for transparent instances. With
+    /// `Transparency::WeightedBlended`, this builds an additional program for
+    /// order-independent transparency.
+    pub fn create_transparent_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        instancing_mode: InstancingMode,
+    ) -> Result<TransparentPass<C>, crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        let shader_core = shaders::tint_core_transform(shaders::log_depth_core_transform(
+            shaders::context_block_core_transform(scene_core.scene_core()),
+        ));
+        let program = shader_core.build_program(facade, instancing_mode)?;
+
+        let weighted_blended_program = if self.components.weighted_blended.is_some() {
+            Some(
+                oit::shaders::core_transform(shader_core.clone())
+                    .build_program(facade, instancing_mode)?,
+            )
+        } else {
+            None
+        };
+
+        Ok(TransparentPass {
+            instancing_mode,
+            program,
+            weighted_blended_program,
+            shader_core,
+        })
+    }
+
+    /// Creates a pass that shows the normals of `scene_core` as colors
+    /// instead of shading, for checking the normals of meshes. The pass is
+    /// drawn like a `PlainScenePass`, so it can be swapped in for debugging.
+    pub fn create_debug_normals_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        instancing_mode: InstancingMode,
+        space: NormalSpace,
+    ) -> Result<PlainScenePass<C>, crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        let shader_core = shaders::debug_normals_core_transform(
+            shaders::log_depth_core_transform(shaders::context_block_core_transform(
+                scene_core.scene_core(),
+            )),
+            space,
+        );
+        let program = shader_core.build_program(facade, instancing_mode)?;
+
+        Ok(PlainScenePass {
+            instancing_mode,
+            program,
+            shader_core,
+        })
+    }
+
+    pub fn create_wireframe_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        instancing_mode: InstancingMode,
+    ) -> Result<WireframePass<C>, crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        let shader_core = shaders::wireframe_core_transform(shaders::log_depth_core_transform(
+            shaders::context_block_core_transform(scene_core.scene_core()),
+        ));
+        let program = shader_core.build_program(facade, instancing_mode)?;
+
+        Ok(WireframePass {
+            instancing_mode,
+            program,
+            shader_core,
+        })
+    }
+
+    /// Rebuilds the program of `pass` from `scene_core`, keeping the pass's
+    /// instancing mode and setup.
+    ///
+    /// This allows reloading shaders at runtime, e.g. with a `FileSceneCore`
+    /// that reads its GLSL sources from files. If building the new program
+    /// fails, the error is returned and `pass` keeps its last working
+    /// program.
+    pub fn rebuild_shaded_scene_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        pass: &mut ShadedScenePass<C>,
+    ) -> Result<(), crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        *pass = self.create_shaded_scene_pass(
+            facade,
+            scene_core,
+            pass.instancing_mode,
+            pass.setup.clone(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Rebuilds the program of `pass` from `scene_core`. See
+    /// `Pipeline::rebuild_shaded_scene_pass`.
+    pub fn rebuild_plain_scene_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        pass: &mut PlainScenePass<C>,
+    ) -> Result<(), crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        *pass = self.create_plain_scene_pass(facade, scene_core, pass.instancing_mode)?;
+
+        Ok(())
+    }
+
+    /// Rebuilds the program of `pass` from `scene_core`. See
+    /// `Pipeline::rebuild_shaded_scene_pass`.
+    pub fn rebuild_depth_prepass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        pass: &mut DepthPrePass<C>,
+    ) -> Result<(), crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        *pass = self.create_depth_prepass(facade, scene_core, pass.instancing_mode)?;
+
+        Ok(())
+    }
+
+    /// Rebuilds the program of `pass` from `scene_core`. See
+    /// `Pipeline::rebuild_shaded_scene_pass`.
+    pub fn rebuild_shadow_pass<F, C>(
+        &self,
+        facade: &F,
+        scene_core: C,
+        pass: &mut ShadowPass<C>,
+    ) -> Result<(), crate::CreationError>
+    where
+        F: glium::backend::Facade,
+        C: SceneCore,
+    {
+        if let Some(new_pass) = self.create_shadow_pass(facade, scene_core, pass.instancing_mode)? {
+            *pass = new_pass;
+        }
+
+        Ok(())
+    }
+
+    /// Draw calls and instances that were issued by the scene passes of the
+    /// last frame that has been presented.
+    ///
+    /// Instances that are removed with `RenderList::cull` before drawing are
+    /// counted in `DrawStats::instances_culled`.
+    pub fn last_frame_stats(&self) -> DrawStats {
+        self.last_frame_stats
+    }
+
+    /// Starts rendering a frame into `target`.
+    ///
+    /// The scene color is cleared to `Context::clear_color`, and the depth
+    /// buffer and all intermediate buffers are cleared as well, so callers do
+    /// not need to clear anything themselves. `target` itself is only drawn
+    /// to in `present`, which overwrites all of it.
     pub fn start_frame<'a, F: glium::backend::Facade, S: Surface>(
         &'a mut self,
         facade: &'a F,
-        clear_color: (f32, f32, f32),
         context: Context,
         target: &'a mut S,
+    ) -> Result<StartFrameStep<'a, F, S>, DrawError> {
+        let target_size = target.get_dimensions();
+
+        self.start_frame_with_size(facade, context, target, target_size, None)
+    }
+
+    /// Starts rendering a frame into `viewport` of `target` only, leaving the
+    /// rest of the target untouched.
+    ///
+    /// This allows for split screen, by rendering one frame per viewport with
+    /// different cameras. The scene is rendered in the resolution of the
+    /// viewport, so the camera's `viewport_size` should match it. The
+    /// pipeline's buffers are cleared in each `start_frame`, so frames do not
+    /// interfere, but the target itself is not cleared.
+    ///
+    /// Since the buffers are resized whenever the size changes, all viewports
+    /// should have the same size.
+    pub fn start_frame_in_viewport<'a, F: glium::backend::Facade, S: Surface>(
+        &'a mut self,
+        facade: &'a F,
+        context: Context,
+        target: &'a mut S,
+        viewport: glium::Rect,
+    ) -> Result<StartFrameStep<'a, F, S>, DrawError> {
+        let target_size = (viewport.width, viewport.height);
+
+        self.start_frame_with_size(facade, context, target, target_size, Some(viewport))
+    }
+
+    fn start_frame_with_size<'a, F: glium::backend::Facade, S: Surface>(
+        &'a mut self,
+        facade: &'a F,
+        context: Context,
+        target: &'a mut S,
+        target_size: (u32, u32),
+        viewport: Option<glium::Rect>,
     ) -> Result<StartFrameStep<'a, F, S>, DrawError> {
         let prof_guard = coarse_prof::enter("pipeline");
         profile!("start_frame");
 
-        if target.get_dimensions() != self.target_size {
-            info!(
-                "Target size has changed to {:?}, resizing",
-                target.get_dimensions()
-            );
+        let context = if context.camera_relative {
+            context.relative_to_eye()
+        } else {
+            context
+        };
+
+        if target_size != self.target_size {
+            info!("Target size has changed to {:?}, resizing", target_size);
 
-            self.on_target_resize(facade, target.get_dimensions())?;
-            self.target_size = target.get_dimensions();
+            self.on_target_resize(facade, target_size)?;
+            self.target_size = target_size;
         }
 
         let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(
@@ -195,19 +488,45 @@ impl Pipeline {
             &self.scene_color_texture,
             &self.scene_depth_texture,
         )?;
-        framebuffer.clear_color_and_depth((clear_color.0, clear_color.1, clear_color.2, 1.0), 1.0);
+        let clear_color = context.clear_color.unwrap_or((0.0, 0.0, 0.0, 1.0));
+        framebuffer.clear_color_and_depth(
+            (clear_color.0, clear_color.1, clear_color.2, 1.0),
+            context.clear_depth(),
+        );
 
         self.components.clear_buffers(facade)?;
 
+        // Upload the context once, instead of setting its uniforms in every
+        // draw call of the scene passes
+        self.context_buffer.write(&context.to_block());
+
         Ok(StartFrameStep(StepContext {
             _prof_guard: prof_guard,
             pipeline: self,
             facade,
             context,
             target,
+            stats: DrawStats::default(),
+            depth_prepass_drawn: false,
+            transparency_pending: false,
+            viewport,
         }))
     }
 
+    /// Uniforms that bind the uniform buffer holding the `Context` of the
+    /// current frame, as given to `start_frame`, to the `ContextBlock` of the
+    /// scene pass programs.
+    ///
+    /// This is needed for issuing custom draw calls with the `program` of a
+    /// scene pass, e.g. `ShadedScenePass::program`.
+    pub fn context_uniforms(
+        &self,
+    ) -> MyUniformsStorage<'static, &UniformBuffer<ContextBlock>, MyEmptyUniforms> {
+        plain_uniforms! {
+            context: &self.context_buffer,
+        }
+    }
+
     fn on_target_resize<F: glium::backend::Facade>(
         &mut self,
         facade: &F,
@@ -251,6 +570,10 @@ impl Pipeline {
 }
 
 impl<'a, F: glium::backend::Facade, S> StartFrameStep<'a, F, S> {
+    pub fn depth_prepass(self) -> DepthPrePassStep<'a, F, S> {
+        DepthPrePassStep(self.0)
+    }
+
     pub fn shadow_pass(self) -> ShadowPassStep<'a, F, S> {
         ShadowPassStep(self.0)
     }
@@ -278,9 +601,67 @@ impl<'a, F: glium::backend::Facade, S> StartFrameStep<'a, F, S> {
     }
 }
 
+impl<'a, F: glium::backend::Facade, S: Surface> DepthPrePassStep<'a, F, S> {
+    /// Draws the depth of `drawable` into the scene depth buffer.
+    ///
+    /// Note that the pre-pass should use the same backface culling as the
+    /// shaded scene pass, so that the resulting depth values match exactly.
+    pub fn draw<C, D, P>(
+        mut self,
+        pass: &DepthPrePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        profile!("depth_prepass");
+
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
+
+        let mut framebuffer =
+            SimpleFrameBuffer::depth_only(self.0.facade, &self.0.pipeline.scene_depth_texture)?;
+
+        let draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: self.0.context.depth_test(glium::DepthTest::IfLess),
+                write: true,
+                ..Default::default()
+            },
+            ..draw_params.clone()
+        };
+
+        self.0.stats += drawable.draw(
+            &pass.program,
+            &(self.0.pipeline.context_uniforms(), params),
+            &draw_params,
+            &mut framebuffer,
+        )?;
+
+        self.0.depth_prepass_drawn = true;
+
+        Ok(self)
+    }
+
+    pub fn shadow_pass(self) -> ShadowPassStep<'a, F, S> {
+        ShadowPassStep(self.0)
+    }
+
+    pub fn shaded_scene_pass(self) -> ShadedScenePassStep<'a, F, S> {
+        ShadedScenePassStep(self.0)
+    }
+}
+
 impl<'a, F: glium::backend::Facade, S: Surface> ShadowPassStep<'a, F, S> {
+    /// Draws `drawable` into the shadow map, if shadow mapping is enabled.
+    ///
+    /// The depth test is set up by the pipeline; everything else, including
+    /// `backface_culling`, is taken from `draw_params`.
     pub fn draw<C, D, P>(
-        self,
+        mut self,
         pass: &Option<ShadowPass<C>>,
         drawable: &D,
         params: &P,
@@ -295,9 +676,9 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadowPassStep<'a, F, S> {
             pass.as_ref(),
             self.0.pipeline.components.shadow_mapping.as_ref(),
         ) {
-            assert_eq!(pass.instancing_mode, D::INSTANCING_MODE);
+            assert_eq!(pass.instancing_mode, drawable.instancing_mode());
 
-            shadow_mapping.shadow_pass(
+            self.0.stats += shadow_mapping.shadow_pass(
                 self.0.facade,
                 drawable,
                 &pass.program,
@@ -315,6 +696,19 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadowPassStep<'a, F, S> {
 }
 
 impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
+    /// Draws `drawable` with lighting into the scene buffers.
+    ///
+    /// The depth test is set up by the pipeline; everything else, including
+    /// `backface_culling`, is taken from `draw_params`. Since front faces are
+    /// counter-clockwise, `CullClockwise` removes back faces. Meshes with the
+    /// opposite winding can use `CullCounterClockwise` instead.
+    ///
+    /// If a depth pre-pass was drawn in this frame, only fragments whose
+    /// depth equals that of the pre-pass are shaded, and depth is not written
+    /// again (see `DepthConfig::after_depth_prepass`). Everything drawn with
+    /// this method then needs to have been drawn in the pre-pass as well;
+    /// other drawables can use `draw_with_depth` with the default
+    /// `DepthConfig`.
     pub fn draw<C, D, P>(
         self,
         pass: &ShadedScenePass<C>,
@@ -327,7 +721,61 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
         D: Drawable<C::Instance, C::Vertex>,
         P: shader::input::CompatibleWith<C::Params>,
     {
-        assert_eq!(pass.instancing_mode, D::INSTANCING_MODE);
+        let depth = if self.0.depth_prepass_drawn {
+            DepthConfig::after_depth_prepass()
+        } else {
+            DepthConfig::default()
+        };
+
+        self.draw_with_depth(pass, drawable, params, draw_params, &depth)
+    }
+
+    /// Draws `drawable` like `draw`, but with the depth test set up by
+    /// `depth` instead of the pipeline's default.
+    pub fn draw_with_depth<C, D, P>(
+        mut self,
+        pass: &ShadedScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        depth: &DepthConfig,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
+
+        if depth.clear {
+            self.0.clear_depth()?;
+        }
+
+        let draw_params = depth.to_draw_params(&self.0.context, draw_params);
+
+        self.draw_with_draw_params(pass, drawable, params, &draw_params)
+    }
+
+    /// Draws `drawable` like `draw`, but with `draw_params` used exactly as
+    /// they are given, overriding the pipeline's depth test.
+    ///
+    /// This is an escape hatch for setups that the other methods do not
+    /// cover. Note that the depth test then needs to be set up by the caller,
+    /// taking `Context::depth_test` into account with reverse-Z. Without a
+    /// depth test, instances are drawn in the order of the `drawable`.
+    pub fn draw_with_draw_params<C, D, P>(
+        mut self,
+        pass: &ShadedScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
 
         let pipeline = &self.0.pipeline;
 
@@ -342,26 +790,43 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
             &pipeline.scene_depth_texture,
         )?;
 
-        let draw_params = glium::DrawParameters {
-            depth: glium::Depth {
-                test: glium::DepthTest::IfLessOrEqual,
-                write: true,
-                ..Default::default()
-            },
-            ..draw_params.clone()
-        };
-
-        pipeline.components.scene_pass::<C, _, _, _>(
+        self.0.stats += pipeline.components.scene_pass::<C, _, _, _, _>(
             drawable,
             &pass.program,
             (&self.0.context, params),
-            &draw_params,
+            pipeline.context_uniforms(),
+            draw_params,
             &mut framebuffer,
         )?;
 
         Ok(self)
     }
 
+    /// Draws `skybox` into the scene color buffer, behind everything that
+    /// has been drawn into the scene depth buffer so far.
+    ///
+    /// The sky is then composed like the background color: it is tone mapped
+    /// and gamma corrected like shaded surfaces, but not lit or fogged. This
+    /// is best done after the opaque parts of the scene, so that hidden
+    /// pixels of the sky are not shaded. See `Skybox::render`.
+    pub fn draw_skybox(self, skybox: &Skybox) -> Result<Self, DrawError> {
+        let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(
+            self.0.facade,
+            &self.0.pipeline.scene_color_texture,
+            &self.0.pipeline.scene_depth_texture,
+        )?;
+
+        skybox.render(&self.0.context, &mut framebuffer)?;
+
+        Ok(self)
+    }
+
+    /// Lights the scene and combines the buffers into the composition texture.
+    ///
+    /// With deferred shading, every light in `lights` contributes, and there
+    /// is no limit on their number: each additional light costs one draw of
+    /// its bounding volume. Without deferred shading, `lights` is ignored and
+    /// the scene is lit by `Context::main_light_pos` only.
     pub fn compose(mut self, lights: &[Light]) -> Result<AfterComposeStep<'a, F, S>, DrawError> {
         let pipeline = &mut self.0.pipeline;
         let components = &mut pipeline.components;
@@ -373,6 +838,21 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
             deferred_shading.light_pass(self.0.facade, &self.0.context.camera, lights)?;
         }
 
+        // Compute ambient occlusion
+        if let (Some(deferred_shading), Some(ssao)) = (
+            components.deferred_shading.as_ref(),
+            components.ssao.as_ref(),
+        ) {
+            profile!("ssao_pass");
+
+            ssao.ssao_pass(
+                self.0.facade,
+                &self.0.context.camera,
+                deferred_shading.position_texture(),
+                deferred_shading.normal_texture(),
+            )?;
+        }
+
         // Blur the glow texture
         if let Some(glow) = components.glow.as_ref() {
             profile!("blur_glow_pass");
@@ -394,6 +874,10 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
                 .deferred_shading
                 .as_ref()
                 .map(|c| CompositionPassComponent::params(c));
+            let ssao_uniforms = components
+                .ssao
+                .as_ref()
+                .map(|c| CompositionPassComponent::params(c));
             let glow_uniforms = components
                 .glow
                 .as_ref()
@@ -401,7 +885,7 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
 
             let uniforms = (
                 &color_uniform,
-                &deferred_shading_uniforms,
+                (&deferred_shading_uniforms, &ssao_uniforms),
                 &glow_uniforms,
                 &self.0.context,
             );
@@ -420,9 +904,37 @@ impl<'a, F: glium::backend::Facade, S: Surface> ShadedScenePassStep<'a, F, S> {
 }
 
 impl<'a, F: glium::backend::Facade, S: Surface> StepContext<'a, F, S> {
-    fn postprocess(self) -> Result<AfterPostprocessStep<'a, F, S>, DrawError> {
+    fn clear_depth(&mut self) -> Result<(), DrawError> {
+        let mut framebuffer =
+            SimpleFrameBuffer::depth_only(self.facade, &self.pipeline.scene_depth_texture)?;
+        framebuffer.clear_depth(self.context.clear_depth());
+        self.depth_prepass_drawn = false;
+
+        Ok(())
+    }
+
+    fn resolve_transparency(&mut self) -> Result<(), DrawError> {
+        if !self.transparency_pending {
+            return Ok(());
+        }
+
+        if let Some(weighted_blended) = self.pipeline.components.weighted_blended.as_ref() {
+            profile!("transparency_resolve");
+
+            weighted_blended.resolve_pass(self.facade, &self.pipeline.composition_texture)?;
+            weighted_blended.clear_buffers(self.facade)?;
+        }
+
+        self.transparency_pending = false;
+
+        Ok(())
+    }
+
+    fn postprocess(mut self) -> Result<AfterPostprocessStep<'a, F, S>, DrawError> {
         profile!("postprocess");
 
+        self.resolve_transparency()?;
+
         let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(
             self.facade,
             &self.pipeline.postprocess_texture,
@@ -452,6 +964,13 @@ impl<'a, F: glium::backend::Facade, S: Surface> StepContext<'a, F, S> {
     }
 
     fn present(self) -> Result<(), DrawError> {
+        self.pipeline.last_frame_stats = self.stats;
+
+        let draw_params = glium::DrawParameters {
+            viewport: self.viewport,
+            ..Default::default()
+        };
+
         // TODO: Use blitting instead
         self.target.draw(
             &self.pipeline.screen_quad.vertex_buffer,
@@ -460,7 +979,7 @@ impl<'a, F: glium::backend::Facade, S: Surface> StepContext<'a, F, S> {
             &uniform! {
                 color_texture: &self.pipeline.postprocess_texture,
             },
-            &Default::default(),
+            &draw_params,
         )?;
 
         Ok(())
@@ -478,7 +997,70 @@ impl<'a, F: glium::backend::Facade, S: Surface> AfterComposeStep<'a, F, S> {
 }
 
 impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassStep<'a, F, S> {
+    /// Draws `drawable` into the composed scene, with `draw_params` used as
+    /// they are given. See `draw_with_depth` for letting the pipeline set up
+    /// the depth test.
     pub fn draw<C, D, P>(
+        mut self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
+
+        let mut framebuffer = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(
+            self.0.facade,
+            &self.0.pipeline.composition_texture,
+            &self.0.pipeline.scene_depth_texture,
+        )?;
+
+        self.0.stats += drawable.draw(
+            &pass.program,
+            &(self.0.pipeline.context_uniforms(), params),
+            &draw_params,
+            &mut framebuffer,
+        )?;
+
+        Ok(self)
+    }
+
+    /// Draws `drawable` like `draw`, but with the depth test set up by
+    /// `depth`, taking `Context::reverse_z` into account.
+    pub fn draw_with_depth<C, D, P>(
+        mut self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        depth: &DepthConfig,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        if depth.clear {
+            self.0.clear_depth()?;
+        }
+
+        let draw_params = depth.to_draw_params(&self.0.context, draw_params);
+
+        self.draw(pass, drawable, params, &draw_params)
+    }
+
+    /// Draws transparent instances with alpha blending.
+    ///
+    /// The depth test is enabled, so that transparent instances are hidden
+    /// behind opaque ones, but depth is not written. For correct blending,
+    /// draw all opaque instances first and sort the transparent instances
+    /// back-to-front, e.g. with `RenderList::sort_back_to_front`.
+    pub fn draw_transparent<C, D, P>(
         self,
         pass: &PlainScenePass<C>,
         drawable: &D,
@@ -490,7 +1072,224 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassStep<'a, F, S> {
         D: Drawable<C::Instance, C::Vertex>,
         P: shader::input::CompatibleWith<C::Params>,
     {
-        assert_eq!(pass.instancing_mode, D::INSTANCING_MODE);
+        self.draw_blended(pass, drawable, params, draw_params, BlendMode::Alpha)
+    }
+
+    /// Draws transparent instances like `draw_transparent`, but with the
+    /// given `blend_mode`. Instances drawn with `BlendMode::Additive` do not
+    /// need to be sorted.
+    pub fn draw_blended<C, D, P>(
+        self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        blend_mode: BlendMode,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        self.draw_blended_with_depth(
+            pass,
+            drawable,
+            params,
+            draw_params,
+            blend_mode,
+            &DepthConfig::transparent(),
+        )
+    }
+
+    /// Draws blended instances like `draw_blended`, but with the depth test
+    /// set up by `depth`, e.g. for alpha-blended decals with a
+    /// `DepthConfig::polygon_offset`.
+    pub fn draw_blended_with_depth<C, D, P>(
+        self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        blend_mode: BlendMode,
+        depth: &DepthConfig,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        let draw_params = glium::DrawParameters {
+            blend: blend_mode.to_blend(),
+            ..draw_params.clone()
+        };
+
+        self.draw_with_depth(pass, drawable, params, &draw_params, depth)
+    }
+
+    /// Draws transparent instances without the need for sorting, if
+    /// `Transparency::WeightedBlended` is enabled. Otherwise, this is the same
+    /// as `draw_transparent`.
+    ///
+    /// With weighted blending, instances are accumulated into separate
+    /// targets in a single draw, which are blended over the scene by
+    /// `resolve_transparency`. Thus, they are depth tested only against what
+    /// has been drawn before, so opaque plain instances should be drawn
+    /// first.
+    pub fn draw_order_independent<C, D, P>(
+        mut self,
+        pass: &TransparentPass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
+
+        let pipeline = &self.0.pipeline;
+        let depth = glium::Depth {
+            test: self.0.context.depth_test(glium::DepthTest::IfLess),
+            write: false,
+            ..Default::default()
+        };
+
+        if let (Some(program), Some(weighted_blended)) = (
+            pass.weighted_blended_program.as_ref(),
+            pipeline.components.weighted_blended.as_ref(),
+        ) {
+            let mut framebuffer = MultiOutputFrameBuffer::with_depth_buffer(
+                self.0.facade,
+                weighted_blended.output_textures().iter().cloned(),
+                &pipeline.scene_depth_texture,
+            )?;
+
+            self.0.stats += drawable.draw(
+                program,
+                &(self.0.pipeline.context_uniforms(), params),
+                &glium::DrawParameters {
+                    blend: oit::WeightedBlended::blend(),
+                    depth,
+                    ..draw_params.clone()
+                },
+                &mut framebuffer,
+            )?;
+
+            self.0.transparency_pending = true;
+        } else {
+            let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(
+                self.0.facade,
+                &pipeline.composition_texture,
+                &pipeline.scene_depth_texture,
+            )?;
+
+            self.0.stats += drawable.draw(
+                &pass.program,
+                &(self.0.pipeline.context_uniforms(), params),
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    depth,
+                    ..draw_params.clone()
+                },
+                &mut framebuffer,
+            )?;
+        }
+
+        Ok(self)
+    }
+
+    /// Blends the instances of `draw_order_independent` over what has been
+    /// drawn so far. Call this after the last transparent draw, so that
+    /// overlays and other draws that follow are not blended under the
+    /// transparent instances. Pending instances are also resolved by
+    /// `postprocess`.
+    ///
+    /// Does nothing unless `Transparency::WeightedBlended` is enabled.
+    pub fn resolve_transparency(mut self) -> Result<Self, DrawError> {
+        self.0.resolve_transparency()?;
+
+        Ok(self)
+    }
+
+    /// Draws on top of everything that has been drawn so far, e.g. for
+    /// gizmos or selection highlights.
+    ///
+    /// Depth is neither tested nor written, and colors are alpha blended.
+    /// For drawing only some instances of a render list on top, use
+    /// `RenderList::set_depth` with `InstanceDepth::overlay` instead.
+    pub fn draw_overlay<C, D, P>(
+        self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        let draw_params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            depth: glium::Depth {
+                test: glium::DepthTest::Overwrite,
+                write: false,
+                ..Default::default()
+            },
+            ..draw_params.clone()
+        };
+
+        self.draw(pass, drawable, params, &draw_params)
+    }
+
+    /// Draws the edges of triangles as lines of `line_width` pixels.
+    ///
+    /// Since this is drawn after lighting, it can be used as an overlay for
+    /// inspecting the tessellation of meshes that have already been drawn in
+    /// the shaded scene pass. Depth is tested but not written.
+    pub fn draw_wireframe<C, D, P>(
+        self,
+        pass: &PlainScenePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        line_width: f32,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        let draw_params = wireframe_draw_params(&self.0.context, draw_params, line_width);
+
+        self.draw(pass, drawable, params, &draw_params)
+    }
+
+    /// Draws the edges of triangles as lines of `line_width` pixels in
+    /// `color`, e.g. for CAD-style views that show the edges of solid
+    /// geometry. Draw the solid geometry in the shaded scene pass first, and
+    /// then its edges with this.
+    ///
+    /// Lines are pulled towards the camera by a polygon offset, so that they
+    /// do not z-fight with the surfaces that they lie on. Edges that are
+    /// hidden behind other surfaces are not drawn.
+    pub fn draw_wireframe_overlay<C, D, P>(
+        mut self,
+        pass: &WireframePass<C>,
+        drawable: &D,
+        params: &P,
+        draw_params: &glium::DrawParameters,
+        color: &na::Vector4<f32>,
+        line_width: f32,
+    ) -> Result<Self, DrawError>
+    where
+        C: SceneCore,
+        D: Drawable<C::Instance, C::Vertex>,
+        P: shader::input::CompatibleWith<C::Params>,
+    {
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
 
         let mut framebuffer = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(
             self.0.facade,
@@ -498,9 +1297,18 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassStep<'a, F, S> {
             &self.0.pipeline.scene_depth_texture,
         )?;
 
-        drawable.draw(
+        let draw_params = wireframe_draw_params(&self.0.context, draw_params, line_width);
+        let wireframe_uniforms = plain_uniforms! {
+            wireframe_color: color.to_uniform(),
+        };
+
+        self.0.stats += drawable.draw(
             &pass.program,
-            &(&self.0.context, params),
+            &(
+                self.0.pipeline.context_uniforms(),
+                params,
+                wireframe_uniforms,
+            ),
             &draw_params,
             &mut framebuffer,
         )?;
@@ -508,6 +1316,18 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassStep<'a, F, S> {
         Ok(self)
     }
 
+    /// Clears the scene depth buffer, so that subsequent draws are on top of
+    /// everything that has been drawn so far.
+    ///
+    /// This can be used for drawing several layers, e.g. a gizmo or an
+    /// overlay after the scene. To keep depth between layers, simply do not
+    /// call this.
+    pub fn clear_depth(mut self) -> Result<Self, DrawError> {
+        self.0.clear_depth()?;
+
+        Ok(self)
+    }
+
     pub fn postprocess(self) -> Result<AfterPostprocessStep<'a, F, S>, DrawError> {
         self.0.postprocess()
     }
@@ -518,6 +1338,8 @@ impl<'a, F: glium::backend::Facade, S: Surface> AfterPostprocessStep<'a, F, S> {
         PlainScenePassAfterPostprocessStep(self.0)
     }
 
+    /// Draws the frame into the target, or into the viewport given to
+    /// `Pipeline::start_frame_in_viewport`.
     pub fn present(self) -> Result<(), DrawError> {
         self.0.present()
     }
@@ -525,7 +1347,7 @@ impl<'a, F: glium::backend::Facade, S: Surface> AfterPostprocessStep<'a, F, S> {
 
 impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassAfterPostprocessStep<'a, F, S> {
     pub fn draw<C, D, P>(
-        self,
+        mut self,
         pass: &PlainScenePass<C>,
         drawable: &D,
         params: &P,
@@ -536,7 +1358,7 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassAfterPostprocessSt
         D: Drawable<C::Instance, C::Vertex>,
         P: shader::input::CompatibleWith<C::Params>,
     {
-        assert_eq!(pass.instancing_mode, D::INSTANCING_MODE);
+        assert_eq!(pass.instancing_mode, drawable.instancing_mode());
 
         let mut framebuffer = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(
             self.0.facade,
@@ -544,9 +1366,9 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassAfterPostprocessSt
             &self.0.pipeline.scene_depth_texture,
         )?;
 
-        drawable.draw(
+        self.0.stats += drawable.draw(
             &pass.program,
-            &(&self.0.context, params),
+            &(self.0.pipeline.context_uniforms(), params),
             &draw_params,
             &mut framebuffer,
         )?;
@@ -554,6 +1376,14 @@ impl<'a, F: glium::backend::Facade, S: Surface> PlainScenePassAfterPostprocessSt
         Ok(self)
     }
 
+    /// Clears the scene depth buffer. See `PlainScenePassStep::clear_depth`.
+    pub fn clear_depth(mut self) -> Result<Self, DrawError> {
+        self.0.clear_depth()?;
+
+        Ok(self)
+    }
+
+    /// See `AfterPostprocessStep::present`.
     pub fn present(self) -> Result<(), DrawError> {
         self.0.present()
     }
@@ -577,3 +1407,27 @@ impl From<components::CreationError> for CreationError {
         CreationError::Components(err)
     }
 }
+
+/// Draw parameters for drawing the edges of triangles on top of the surfaces
+/// that they belong to.
+fn wireframe_draw_params<'p>(
+    context: &Context,
+    draw_params: &glium::DrawParameters<'p>,
+    line_width: f32,
+) -> glium::DrawParameters<'p> {
+    glium::DrawParameters {
+        polygon_mode: glium::PolygonMode::Line,
+        line_width: Some(line_width),
+        polygon_offset: glium::draw_parameters::PolygonOffset {
+            fill: false,
+            line: true,
+            ..PolygonOffset::default().to_glium(context)
+        },
+        depth: glium::Depth {
+            test: context.depth_test(glium::DepthTest::IfLessOrEqual),
+            write: false,
+            ..Default::default()
+        },
+        ..draw_params.clone()
+    }
+}
@@ -0,0 +1,168 @@
+//! Weighted blended order-independent transparency.
+//!
+//! As in:
+//!     http://jcgt.org/published/0002/02/09/
+//!
+//! Transparent fragments are accumulated with weights that depend on their
+//! depth, so that they can be drawn in any order. In the resolve pass, the
+//! accumulated colors are averaged and blended over the opaque scene. This
+//! is an approximation, but it has no artifacts at intersecting surfaces,
+//! where sorting by instance fails.
+//!
+//! Fragments are drawn once into two float targets. Since all targets share
+//! the same blending, the revealage is kept in the alpha channel of the
+//! accumulation target, which is blended multiplicatively, while the sum of
+//! weights goes into a separate target. This is the variant of the paper for
+//! hardware without blending per target.
+
+pub mod shaders;
+
+use log::info;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Program, Surface, Texture2d};
+
+use crate::pipeline::render_pass::RenderPassComponent;
+use crate::{shader, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+pub struct WeightedBlended {
+    accumulation_texture: Texture2d,
+    weight_texture: Texture2d,
+    resolve_program: Program,
+    screen_quad: ScreenQuad,
+}
+
+impl RenderPassComponent for WeightedBlended {
+    fn clear_buffers<F: glium::backend::Facade>(&self, facade: &F) -> Result<(), DrawError> {
+        // Revealage in the alpha channel is multiplied by one minus the
+        // alpha of each fragment
+        let mut accumulation_buffer = SimpleFrameBuffer::new(facade, &self.accumulation_texture)?;
+        accumulation_buffer.clear_color(0.0, 0.0, 0.0, 1.0);
+
+        let mut weight_buffer = SimpleFrameBuffer::new(facade, &self.weight_texture)?;
+        weight_buffer.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        Ok(())
+    }
+}
+
+impl WeightedBlended {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        info!("Creating weighted blended transparency textures");
+        let accumulation_texture = Self::create_accumulation_texture(facade, target_size)?;
+        let weight_texture = Self::create_weight_texture(facade, target_size)?;
+
+        info!("Creating transparency resolve program");
+        let resolve_program =
+            shaders::resolve_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(WeightedBlended {
+            accumulation_texture,
+            weight_texture,
+            resolve_program,
+            screen_quad,
+        })
+    }
+
+    pub fn accumulation_texture(&self) -> &Texture2d {
+        &self.accumulation_texture
+    }
+
+    pub fn weight_texture(&self) -> &Texture2d {
+        &self.weight_texture
+    }
+
+    /// The targets of `shaders::core_transform`, for drawing into a
+    /// `MultiOutputFrameBuffer`.
+    pub fn output_textures(&self) -> [(&'static str, &Texture2d); 2] {
+        [
+            (shaders::F_ACCUMULATION.0, &self.accumulation_texture),
+            (shaders::F_WEIGHT.0, &self.weight_texture),
+        ]
+    }
+
+    /// Blending for both targets. Colors and weights are summed up, while
+    /// alpha multiplies the transmittance of all fragments.
+    pub fn blend() -> glium::Blend {
+        glium::Blend {
+            color: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::One,
+                destination: glium::LinearBlendingFactor::One,
+            },
+            alpha: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::Zero,
+                destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Blends the accumulated transparent fragments over `target`.
+    pub fn resolve_pass<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+        target: &Texture2d,
+    ) -> Result<(), DrawError> {
+        let mut target_buffer = SimpleFrameBuffer::new(facade, target)?;
+
+        target_buffer.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.resolve_program,
+            &uniform! {
+                accumulation_texture: &self.accumulation_texture,
+                weight_texture: &self.weight_texture,
+            },
+            &glium::DrawParameters {
+                blend: glium::Blend::alpha_blending(),
+                ..Default::default()
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn on_target_resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        self.accumulation_texture = Self::create_accumulation_texture(facade, target_size)?;
+        self.weight_texture = Self::create_weight_texture(facade, target_size)?;
+
+        Ok(())
+    }
+
+    fn create_accumulation_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+
+    fn create_weight_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+}
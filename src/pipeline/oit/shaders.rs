@@ -0,0 +1,91 @@
+use glium::uniforms::UniformType;
+
+use crate::{screen_quad, shader, Context};
+
+const WEIGHT_DEFS: &str = "
+    // Weight function (9) from McGuire and Bavoil, Weighted Blended
+    // Order-Independent Transparency, 2013. Nearer and more opaque fragments
+    // get larger weights.
+    float oit_weight(float view_depth, float alpha) {
+        return alpha * clamp(
+            10.0 / (1e-5 + pow(view_depth / 5.0, 2.0) + pow(view_depth / 200.0, 6.0)),
+            1e-2,
+            3e3
+        );
+    }
+";
+
+/// Weighted, premultiplied color, with the revealage in the alpha channel.
+pub const F_ACCUMULATION: (&str, shader::FragmentOutDef) = (
+    "f_accumulation",
+    shader::FragmentOutDef(shader::Type::FloatVec4, shader::FragmentOutQualifier::Yield),
+);
+
+/// Weight of the fragment, to be summed up for averaging the accumulation.
+pub const F_WEIGHT: (&str, shader::FragmentOutDef) = (
+    "f_weight",
+    shader::FragmentOutDef(shader::Type::Float, shader::FragmentOutQualifier::Yield),
+);
+
+/// Shader core transform for writing transparent fragments into the targets
+/// of `WeightedBlended::output_textures` in a single draw. `f_color` is only
+/// used as an input here.
+pub fn core_transform<P, I, V>(
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    assert!(
+        core.vertex.has_out_def(shader::defs::V_WORLD_POS),
+        "VertexCore needs V_WORLD_POS output for weighted blended transparency"
+    );
+    assert!(
+        core.fragment.has_out_def(shader::defs::F_COLOR),
+        "FragmentCore needs F_COLOR output for weighted blended transparency"
+    );
+
+    let fragment = core
+        .fragment
+        .with_in_def(shader::defs::V_WORLD_POS)
+        .with_defs(WEIGHT_DEFS)
+        .with_out_def((
+            shader::defs::F_COLOR.0,
+            shader::FragmentOutDef(shader::Type::FloatVec4, shader::FragmentOutQualifier::Local),
+        ))
+        .with_out(
+            F_WEIGHT,
+            "oit_weight(abs((context_camera_view * v_world_pos).z), f_color.a)",
+        )
+        .with_out(
+            F_ACCUMULATION,
+            "vec4(f_color.rgb * f_color.a * f_weight, f_color.a)",
+        );
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Core for averaging the accumulated colors, which are then blended over
+/// the opaque scene by how much of it is covered.
+pub fn resolve_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let vertex = shader::VertexCore::empty()
+        .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+        .with_out(shader::defs::V_POS, "position");
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("accumulation_texture", UniformType::Sampler2d)
+        .with_extra_uniform("weight_texture", UniformType::Sampler2d)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_body(
+            "
+            vec4 accumulation = texture(accumulation_texture, v_tex_coord);
+            float weight = texture(weight_texture, v_tex_coord).r;
+            ",
+        )
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(accumulation.rgb / max(weight, 1e-5), 1.0 - accumulation.a)",
+        );
+
+    shader::Core { vertex, fragment }
+}
@@ -5,6 +5,20 @@ use crate::{screen_quad, shader, DrawError};
 
 pub struct ShadowPass<C: SceneCore> {
     pub instancing_mode: InstancingMode,
+
+    /// The compiled program. See `ShadedScenePass::program` for its uniforms.
+    pub program: glium::Program,
+
+    /// The transformed shader core that was used for building the `program`.
+    /// Currently this is basically just phantom data.
+    #[allow(dead_code)]
+    pub shader_core: shader::Core<(Context, C::Params), C::Instance, C::Vertex>,
+}
+
+pub struct DepthPrePass<C: SceneCore> {
+    pub instancing_mode: InstancingMode,
+
+    /// The compiled program. See `ShadedScenePass::program` for its uniforms.
     pub program: glium::Program,
 
     /// The transformed shader core that was used for building the `program`.
@@ -17,12 +31,152 @@ pub struct ShadowPass<C: SceneCore> {
 pub struct ShadedScenePassSetup {
     pub draw_shadowed: bool,
     pub draw_glowing: bool,
+
+    /// Convert `v_color` from sRGB to linear space before lighting.
+    ///
+    /// Enable this if instance colors are given in sRGB (e.g. picked from
+    /// an image editor), so that they are not gamma-corrected twice.
+    pub linearize_colors: bool,
+
+    /// Flip the normal of back faces towards the viewer, so that thin
+    /// surfaces such as single-sided quads are lit on both sides. This only
+    /// makes sense if backface culling is disabled for the pass.
+    pub two_sided_lighting: bool,
+}
+
+/// How a draw in the shaded scene pass uses the scene depth buffer, e.g. for
+/// decals that are drawn onto existing surfaces without writing depth.
+///
+/// The test is given for the standard depth range. It is reversed by the
+/// pipeline if `Context::reverse_z` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthConfig {
+    pub test: glium::DepthTest,
+    pub write: bool,
+
+    /// Clear the scene depth buffer before drawing.
+    pub clear: bool,
+
+    /// Offset the depth of the drawn polygons towards the camera, e.g. to
+    /// draw decals onto coplanar surfaces without z-fighting. If `None`, the
+    /// polygon offset of the given draw parameters is kept.
+    pub polygon_offset: Option<PolygonOffset>,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            test: glium::DepthTest::IfLessOrEqual,
+            write: true,
+            clear: false,
+            polygon_offset: None,
+        }
+    }
+}
+
+impl DepthConfig {
+    /// Depth setup for blending transparent instances over the scene, which
+    /// are hidden behind opaque ones but do not hide anything themselves.
+    /// This is what `PlainScenePassStep::draw_blended` uses.
+    pub fn transparent() -> Self {
+        Self {
+            test: glium::DepthTest::IfLess,
+            write: false,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the depth test and polygon offset of `draw_params`. Clearing
+    /// is left to the caller.
+    pub(crate) fn to_draw_params<'a>(
+        &self,
+        context: &Context,
+        draw_params: &glium::DrawParameters<'a>,
+    ) -> glium::DrawParameters<'a> {
+        glium::DrawParameters {
+            depth: glium::Depth {
+                test: context.depth_test(self.test),
+                write: self.write,
+                ..Default::default()
+            },
+            polygon_offset: self
+                .polygon_offset
+                .map_or(draw_params.polygon_offset, |offset| {
+                    offset.to_glium(context)
+                }),
+            ..draw_params.clone()
+        }
+    }
+
+    /// Depth setup for drawing what has already been drawn in a depth
+    /// pre-pass, so that each pixel is shaded only once. This is what
+    /// `ShadedScenePassStep::draw` uses after a pre-pass.
+    pub fn after_depth_prepass() -> Self {
+        Self {
+            test: glium::DepthTest::IfEqual,
+            write: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// Offset of polygon depth towards the camera, as in `glPolygonOffset`, but
+/// with positive values meaning closer. The pipeline flips the sign for
+/// `Context::reverse_z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonOffset {
+    /// Offset scaled by the slope of the polygon's depth.
+    pub factor: f32,
+
+    /// Offset in units of the smallest resolvable depth difference.
+    pub units: f32,
+}
+
+impl Default for PolygonOffset {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            units: 1.0,
+        }
+    }
+}
+
+impl PolygonOffset {
+    pub(crate) fn to_glium(self, context: &Context) -> glium::draw_parameters::PolygonOffset {
+        // Towards the camera means towards smaller depth values, unless
+        // depth is reversed.
+        let sign = if context.reverse_z { 1.0 } else { -1.0 };
+
+        glium::draw_parameters::PolygonOffset {
+            factor: sign * self.factor,
+            units: sign * self.units,
+            fill: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Space of the normals that are shown by a pass of
+/// `Pipeline::create_debug_normals_pass`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalSpace {
+    World,
+    View,
 }
 
 pub struct ShadedScenePass<C: SceneCore> {
     pub instancing_mode: InstancingMode,
     pub setup: ShadedScenePassSetup,
 
+    /// The compiled program, e.g. for issuing custom draw calls with the
+    /// buffers of a `Mesh`, such as those of `basic_obj::Resources::mesh`.
+    ///
+    /// Its uniforms are those of `Context` (`context_camera_view`,
+    /// `context_camera_projection`, `context_main_light_pos`, ...), which are
+    /// read from a uniform buffer with a `ContextBlock` that is bound as
+    /// `context` by `Pipeline::context_uniforms`, those of `C::Params` and,
+    /// when drawing with `InstancingMode::Uniforms`, those of `C::Instance`. Components such as shadow mapping add further
+    /// uniforms, so prefer drawing through the pipeline steps when possible.
     pub program: glium::Program,
 
     /// The transformed shader core that was used for building the `program`.
@@ -31,8 +185,17 @@ pub struct ShadedScenePass<C: SceneCore> {
     pub shader_core: shader::Core<(Context, C::Params), C::Instance, C::Vertex>,
 }
 
+/// A pass that draws without lighting, outputting the color of the scene
+/// core directly, e.g. for UI elements, light markers or emissive objects.
+///
+/// Plain passes are drawn after composition, so they are neither lit nor
+/// shadowed, but they are still depth tested against the shaded scene. With
+/// `basic_obj::Core`, this outputs the instance color as it is, so there is
+/// no need to turn up the ambient light for unlit objects.
 pub struct PlainScenePass<C: SceneCore> {
     pub instancing_mode: InstancingMode,
+
+    /// The compiled program. See `ShadedScenePass::program` for its uniforms.
     pub program: glium::Program,
 
     /// The transformed shader core that was used for building the `program`.
@@ -41,6 +204,41 @@ pub struct PlainScenePass<C: SceneCore> {
     pub shader_core: shader::Core<(Context, C::Params), C::Instance, C::Vertex>,
 }
 
+/// A pass that draws the edges of triangles in a single color, e.g. on top of
+/// solid geometry in CAD-style views. See
+/// `PlainScenePassStep::draw_wireframe_overlay`.
+pub struct WireframePass<C: SceneCore> {
+    pub instancing_mode: InstancingMode,
+
+    /// The compiled program. See `ShadedScenePass::program` for its uniforms.
+    /// The color is given by the additional `wireframe_color` uniform.
+    pub program: glium::Program,
+
+    /// The transformed shader core that was used for building the `program`.
+    /// Currently this is basically just phantom data.
+    #[allow(dead_code)]
+    pub shader_core: shader::Core<(Context, C::Params), C::Instance, C::Vertex>,
+}
+
+/// A pass for transparent instances, which are blended as configured by
+/// `Config::transparency`. See `PlainScenePassStep::draw_order_independent`.
+pub struct TransparentPass<C: SceneCore> {
+    pub instancing_mode: InstancingMode,
+
+    /// The compiled program for blending in order. See
+    /// `ShadedScenePass::program` for its uniforms.
+    pub program: glium::Program,
+
+    /// The program that writes into the accumulation and weight targets at
+    /// once, if weighted blended transparency is enabled.
+    pub weighted_blended_program: Option<glium::Program>,
+
+    /// The transformed shader core that was used for building the `program`.
+    /// Currently this is basically just phantom data.
+    #[allow(dead_code)]
+    pub shader_core: shader::Core<(Context, C::Params), C::Instance, C::Vertex>,
+}
+
 pub trait RenderPassComponent {
     fn clear_buffers<F: glium::backend::Facade>(&self, facade: &F) -> Result<(), DrawError>;
 }
@@ -1,33 +1,356 @@
 use glium::uniforms::UniformType;
 
-use crate::{screen_quad, shader, Context};
+use crate::pipeline::{NormalSpace, ToneMap, ToneMapOperator};
+use crate::{screen_quad, shader, Context, ContextBlock, Material};
+
+/// Returns the GLSL expressions for the `Material` of a fragment core, in the
+/// order ambient, diffuse, specular and shininess. Coefficients that are not
+/// output by the core are replaced by their default values.
+pub(crate) fn material_exprs<P>(fragment: &shader::FragmentCore<P>) -> [String; 4] {
+    let default = Material::default();
+    let expr = |name: &str, value: String| {
+        if fragment.has_out(name) {
+            name.to_string()
+        } else {
+            value
+        }
+    };
+
+    [
+        expr("f_ambient", format!("{:?}", default.ambient)),
+        expr("f_diffuse", format!("{:?}", default.diffuse)),
+        expr(
+            "f_specular",
+            format!(
+                "vec3({:?}, {:?}, {:?})",
+                default.specular.x, default.specular.y, default.specular.z
+            ),
+        ),
+        expr("f_shininess", format!("{:?}", default.shininess)),
+    ]
+}
+
+/// Returns the GLSL expression for the world-space normal that is to be used
+/// for lighting. This is `f_normal` if the core outputs it, e.g. for normal
+/// mapping, and the interpolated `v_world_normal` otherwise.
+pub(crate) fn normal_expr<P>(fragment: &shader::FragmentCore<P>) -> &'static str {
+    if fragment.has_out("f_normal") {
+        "f_normal"
+    } else {
+        "v_world_normal"
+    }
+}
 
 pub fn diffuse_scene_core_transform<P, I, V>(
     core: shader::Core<(Context, P), I, V>,
 ) -> shader::Core<(Context, P), I, V> {
-    let color_expr = if core.fragment.has_out("f_shadow") {
-        "vec4((0.3 + f_shadow * diffuse) * f_color.rgb, f_color.a)"
+    let shadow = if core.fragment.has_out("f_shadow") {
+        "f_shadow * "
+    } else {
+        ""
+    };
+    let normal = normal_expr(&core.fragment);
+    let [ambient, diffuse, specular, shininess] = material_exprs(&core.fragment);
+    let specular_expr = if core.fragment.has_out("f_specular") {
+        format!(
+            " + {}{} * specular_term({}, {})",
+            shadow, specular, normal, shininess
+        )
     } else {
-        "vec4((0.3 + diffuse) * f_color.rgb, f_color.a)"
+        String::new()
     };
+    let color_expr = format!(
+        "vec4(({} * ambient + {}{} * diffuse_term({})) * f_color.rgb{}, f_color.a)",
+        ambient, shadow, diffuse, normal, specular_expr,
+    );
 
     let fragment = core
         .fragment
         .with_in_def(shader::defs::V_WORLD_NORMAL)
         .with_in_def(shader::defs::V_WORLD_POS)
+        .with_defs(
+            "
+            float diffuse_term(vec3 normal) {
+                vec3 light_dir = normalize(context_main_light_pos - v_world_pos.xyz);
+
+                return max(dot(normalize(normal), light_dir), 0.05);
+            }
+
+            float specular_term(vec3 normal, float shininess) {
+                if (shininess <= 0.0)
+                    return 0.0;
+
+                normal = normalize(normal);
+                vec3 light_dir = normalize(context_main_light_pos - v_world_pos.xyz);
+                vec3 view_dir = normalize(context_camera_eye - v_world_pos.xyz);
+                vec3 half_dir = normalize(light_dir + view_dir);
+
+                return pow(max(dot(normal, half_dir), 0.0), shininess);
+            }
+            ",
+        )
         .with_body(
             "
             float ambient = 0.3;
-            float diffuse = max(
-                dot(
-                    normalize(v_world_normal),
-                    normalize(context_main_light_pos - v_world_pos.xyz)
-                ),
-                0.05
-            );
         ",
         )
-        .with_out_expr("f_color", color_expr);
+        .with_out_expr("f_color", &color_expr);
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Shader core transform for multiplying `f_color` with `f_tint`, if the
+/// core outputs it. This is applied after lighting, so that the tint also
+/// affects specular highlights.
+pub fn tint_core_transform<P, I, V>(core: shader::Core<P, I, V>) -> shader::Core<P, I, V> {
+    if !core.fragment.has_out(shader::defs::F_TINT.0) {
+        return core;
+    }
+
+    let fragment = core.fragment.with_out_expr("f_color", "f_tint * f_color");
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+const FOG_DEFS: &str = "
+    vec3 apply_fog(vec3 color, float distance) {
+        float visibility = 1.0;
+
+        if (context_fog_mode == 1) {
+            visibility = exp(-context_fog_density * distance);
+        } else if (context_fog_mode == 2) {
+            // Guard against division by zero if `end` is not after `start`,
+            // which then gives a hard edge at `end`.
+            float range = max(context_fog_range.y - context_fog_range.x, 0.0001);
+
+            visibility = clamp((context_fog_range.y - distance) / range, 0.0, 1.0);
+        }
+
+        return mix(context_fog_color, color, visibility);
+    }
+";
+
+/// Shader core transform for blending the lit color into the fog of
+/// `Context::fog` by the fragment's distance from the camera.
+pub fn fog_core_transform<P, I, V>(
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    let fragment = core
+        .fragment
+        .with_in_def(shader::defs::V_WORLD_POS)
+        .with_defs(FOG_DEFS)
+        .with_out_expr(
+            "f_color",
+            "vec4(
+                apply_fog(f_color.rgb, distance(v_world_pos.xyz, context_camera_eye)),
+                f_color.a
+            )",
+        );
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Composition shader core transform for fog with deferred shading, where
+/// lighting is only known after composing the buffers.
+///
+/// Needs the `position_texture` and `normal_texture` uniforms of deferred
+/// shading. The background is kept as-is.
+pub fn fog_composition_core_transform(
+    core: shader::Core<Context, (), screen_quad::Vertex>,
+) -> shader::Core<Context, (), screen_quad::Vertex> {
+    assert!(
+        core.fragment.has_out_def(shader::defs::F_COLOR),
+        "FragmentCore needs F_COLOR output for fog composition pass"
+    );
+
+    let fragment = core
+        .fragment
+        .with_extra_uniform("position_texture", UniformType::Sampler2d)
+        .with_extra_uniform("normal_texture", UniformType::Sampler2d)
+        .with_defs(FOG_DEFS)
+        .with_out_expr(
+            "f_color",
+            "mix(
+                f_color,
+                vec4(
+                    apply_fog(
+                        f_color.rgb,
+                        distance(texture(position_texture, v_tex_coord).xyz, context_camera_eye)
+                    ),
+                    f_color.a
+                ),
+                step(0.001, length(texture(normal_texture, v_tex_coord).rgb))
+            )",
+        );
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Shader core transform for converting `v_color` from sRGB to linear space.
+pub fn linearize_color_core_transform<P, I, V>(
+    core: shader::Core<P, I, V>,
+) -> shader::Core<P, I, V> {
+    assert!(
+        core.vertex.has_out_def(shader::defs::V_COLOR),
+        "VertexCore needs V_COLOR output for linearizing colors"
+    );
+
+    let vertex = core
+        .vertex
+        .with_defs(
+            "
+            vec3 srgb_to_linear(vec3 color) {
+                return mix(
+                    color / 12.92,
+                    pow((color + 0.055) / 1.055, vec3(2.4)),
+                    step(0.04045, color)
+                );
+            }
+            ",
+        )
+        .with_out_expr("v_color", "vec4(srgb_to_linear(v_color.rgb), v_color.a)");
+
+    shader::Core {
+        vertex,
+        fragment: core.fragment,
+    }
+}
+
+/// Shader core transform for flipping the normal of back faces, so that they
+/// are lit as if they were facing the viewer.
+pub fn two_sided_lighting_core_transform<P, I, V>(
+    core: shader::Core<P, I, V>,
+) -> shader::Core<P, I, V> {
+    let fragment = if core.fragment.has_out("f_normal") {
+        core.fragment
+            .with_out_expr("f_normal", "gl_FrontFacing ? f_normal : -f_normal")
+    } else {
+        core.fragment
+            .with_in_def(shader::defs::V_WORLD_NORMAL)
+            .with_out(
+                shader::defs::F_NORMAL,
+                "gl_FrontFacing ? v_world_normal : -v_world_normal",
+            )
+    };
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Shader core transform for reading the uniforms of `Context` from the
+/// `ContextBlock` uniform block, which the pipeline uploads once per frame.
+pub fn context_block_core_transform<P, I, V>(
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    let members = ContextBlock::members();
+
+    shader::Core {
+        vertex: core.vertex.with_uniform_block(ContextBlock::NAME, &members),
+        fragment: core
+            .fragment
+            .with_uniform_block(ContextBlock::NAME, &members),
+    }
+}
+
+/// Shader core transform for logarithmic depth, which is enabled at runtime
+/// through `Context::log_depth`.
+///
+/// This needs to be applied to all scene passes that share the depth buffer,
+/// so that their depth values are comparable.
+pub fn log_depth_core_transform<P, I, V>(
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    let vertex = core
+        .vertex
+        .with_defs(
+            "
+            vec4 log_depth(vec4 pos) {
+                if (context_log_depth_coef > 0.0) {
+                    pos.z = (log2(max(1e-6, 1.0 + pos.w)) * context_log_depth_coef - 1.0)
+                        * pos.w;
+                }
+
+                return pos;
+            }
+            ",
+        )
+        .with_position_expr("log_depth(gl_Position)");
+
+    shader::Core {
+        vertex,
+        fragment: core.fragment,
+    }
+}
+
+/// Shader core transform for computing `gl_Position` in exactly the same way
+/// in all programs that are built from the core.
+///
+/// This is needed for depth equality tests between passes, such as after a
+/// depth pre-pass, since the compiler may otherwise optimize the position
+/// computation differently per program.
+pub fn invariant_position_core_transform<P, I, V>(
+    core: shader::Core<P, I, V>,
+) -> shader::Core<P, I, V> {
+    shader::Core {
+        vertex: core.vertex.with_defs("invariant gl_Position;\n"),
+        fragment: core.fragment,
+    }
+}
+
+/// Shader core transform for only writing depth, discarding all color output
+/// of the original core.
+pub fn depth_only_core_transform<P, I, V>(core: shader::Core<P, I, V>) -> shader::Core<P, I, V> {
+    shader::Core {
+        vertex: core.vertex,
+        fragment: shader::FragmentCore::empty(),
+    }
+}
+
+/// Shader core transform for drawing in a single color that is given by the
+/// `wireframe_color` uniform, discarding all color output of the original
+/// core.
+pub fn wireframe_core_transform<P, I, V>(core: shader::Core<P, I, V>) -> shader::Core<P, I, V> {
+    shader::Core {
+        vertex: core.vertex,
+        fragment: shader::FragmentCore::empty()
+            .with_extra_uniform("wireframe_color", UniformType::FloatVec4)
+            .with_out(shader::defs::F_COLOR, "wireframe_color"),
+    }
+}
+
+/// Shader core transform for debugging normals, which outputs the normal
+/// that would be used for lighting, mapped from [-1, 1] to [0, 1], as color.
+pub fn debug_normals_core_transform<P, I, V>(
+    core: shader::Core<(Context, P), I, V>,
+    space: NormalSpace,
+) -> shader::Core<(Context, P), I, V> {
+    let normal = normal_expr(&core.fragment);
+    let normal = match space {
+        NormalSpace::World => normal.to_string(),
+        NormalSpace::View => format!("mat3(context_camera_view) * {}", normal),
+    };
+
+    let fragment = core
+        .fragment
+        .with_in_def(shader::defs::V_WORLD_NORMAL)
+        .with_out_expr(
+            "f_color",
+            &format!("vec4(normalize({}) * 0.5 + 0.5, 1.0)", normal),
+        );
 
     shader::Core {
         vertex: core.vertex,
@@ -53,19 +376,36 @@ pub fn composition_core<P>() -> shader::Core<P, (), screen_quad::Vertex> {
 
 pub fn hdr_composition_core_transform<P>(
     core: shader::Core<P, (), screen_quad::Vertex>,
+    tone_map: &ToneMap,
 ) -> shader::Core<P, (), screen_quad::Vertex> {
     assert!(
         core.fragment.has_out_def(shader::defs::F_COLOR),
         "FragmentCore needs F_COLOR output for HDR composition pass"
     );
 
+    let operator = match tone_map.operator {
+        ToneMapOperator::Reinhard => "color / (color + 1.0)",
+        ToneMapOperator::Aces => {
+            "clamp(
+                (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14),
+                0.0,
+                1.0
+            )"
+        }
+    };
+
     let fragment = core
         .fragment
-        //.with_out_expr(shader::F_COLOR, "vec4(vec3(1.0) - exp(-f_color.rgb), 1.0)");
-        .with_out_expr(
-            "f_color",
-            "vec4(vec3(f_color) / (vec3(f_color) + 1.0), 1.0)",
-        );
+        .with_defs(&format!(
+            "
+            vec3 tone_map(vec3 color) {{
+                color *= {:?};
+                return {};
+            }}
+            ",
+            tone_map.exposure, operator,
+        ))
+        .with_out_expr("f_color", "vec4(tone_map(vec3(f_color)), 1.0)");
 
     shader::Core {
         vertex: core.vertex,
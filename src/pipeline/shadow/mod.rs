@@ -1,5 +1,14 @@
 //! Shadow mapping.
 //!
+//! With more than one cascade, the view frustum is split into depth ranges,
+//! each of which gets its own shadow map that is fitted tightly around it.
+//! Fragments then look up the shadow map of the range that contains them, so
+//! that shadows near the camera get more resolution than those far away.
+//!
+//! For point lights, the distance to the closest caster is instead rendered
+//! into the six faces of a cubemap around the light, which fragments look up
+//! in the direction from the light.
+//!
 //! Heavily inspired by:
 //! https://github.com/glium/glium/blob/master/examples/shadow_mapping.rs
 
@@ -9,21 +18,66 @@ use log::info;
 
 use nalgebra as na;
 
-use glium::texture::DepthTexture2d;
+use glium::texture::{CubeLayer, DepthCubemap, DepthTexture2d};
 use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler};
 use glium::Surface;
 
 use crate::pipeline::render_pass::{HasScenePassParams, RenderPassComponent, ScenePassComponent};
-use crate::shader::{self, ToUniforms};
-use crate::{Camera, Context, DrawError, Drawable};
+use crate::shader::{self, ToUniform, ToUniforms};
+use crate::{Camera, Context, DrawError, DrawStats, Drawable, Projection};
 
 pub use crate::CreationError;
 
+/// Maximal value of `Config::num_cascades`.
+pub const MAX_CASCADES: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub shadow_map_size: na::Vector2<u32>,
     pub shadow_value: f32,
     pub pcf_distance: usize,
+
+    /// Distance by which shadow lookups are offset along the surface normal.
+    ///
+    /// Increase this if surfaces show self-shadowing artifacts ("shadow
+    /// acne"); decrease it if shadows detach from their casters.
+    pub normal_offset_bias: f32,
+
+    /// Number of cascades, between one and `MAX_CASCADES`. Other values make
+    /// `ShadowMapping::create` fail with `CreationError::InvalidConfig`.
+    ///
+    /// A single cascade covers a fixed area around
+    /// `Context::main_light_center`. More cascades follow the camera, which
+    /// needs a projection that is not `Projection::Custom`.
+    pub num_cascades: usize,
+
+    /// Determines how the view frustum is split into cascades, blending
+    /// between uniform splits at `0.0` and logarithmic splits at `1.0`.
+    pub cascade_split_lambda: f32,
+
+    /// Cast shadows in all directions from `Context::main_light_pos`, as
+    /// from a point light, instead of along the direction towards
+    /// `Context::main_light_center`. The cascade settings and `pcf_distance`
+    /// are then not used.
+    pub point_light: Option<PointLightConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PointLightConfig {
+    /// Width and height of each face of the shadow cubemap.
+    pub cubemap_size: u32,
+
+    /// Distance from the light beyond which nothing is shadowed.
+    pub far: f32,
+}
+
+impl Default for PointLightConfig {
+    fn default() -> Self {
+        Self {
+            cubemap_size: 1024,
+            far: 50.0,
+        }
+    }
 }
 
 impl Default for Config {
@@ -32,36 +86,80 @@ impl Default for Config {
             shadow_map_size: na::Vector2::new(4096, 4096),
             shadow_value: 0.5,
             pcf_distance: 1,
+            normal_offset_bias: 0.02,
+            num_cascades: 1,
+            cascade_split_lambda: 0.5,
+            point_light: None,
         }
     }
 }
 
+/// Faces of the shadow cubemap, with the direction in which each face looks
+/// and its up vector, following the OpenGL cubemap conventions.
+const CUBE_FACES: [(CubeLayer, [f32; 3], [f32; 3]); 6] = [
+    (CubeLayer::PositiveX, [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::NegativeX, [-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::PositiveY, [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    (CubeLayer::NegativeY, [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    (CubeLayer::PositiveZ, [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::NegativeZ, [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
 pub struct ShadowMapping {
     config: Config,
-    shadow_texture: DepthTexture2d,
+    shadow_textures: Vec<DepthTexture2d>,
+
+    /// Shadow cubemap for `Config::point_light`. Without a point light, this
+    /// is a placeholder of size one, since the uniforms of the scene pass
+    /// need a texture either way. The same holds for `shadow_textures` with a
+    /// point light.
+    shadow_cubemap: DepthCubemap,
 }
 
 impl RenderPassComponent for ShadowMapping {
     fn clear_buffers<F: glium::backend::Facade>(&self, facade: &F) -> Result<(), DrawError> {
-        let mut shadow_target =
-            glium::framebuffer::SimpleFrameBuffer::depth_only(facade, &self.shadow_texture)?;
+        for shadow_texture in self.shadow_textures.iter() {
+            let mut shadow_target =
+                glium::framebuffer::SimpleFrameBuffer::depth_only(facade, shadow_texture)?;
+
+            shadow_target.clear_depth(1.0);
+        }
 
-        shadow_target.clear_depth(1.0);
+        for &(layer, _, _) in CUBE_FACES.iter() {
+            let mut shadow_target = glium::framebuffer::SimpleFrameBuffer::depth_only(
+                facade,
+                self.shadow_cubemap.main_level().image(layer),
+            )?;
+
+            shadow_target.clear_depth(1.0);
+        }
 
         Ok(())
     }
 }
 
+/// Uniforms of the shadowed scene pass. Cascades that are not used are
+/// filled with copies of the last one.
 pub struct ScenePassParams<'a> {
-    light_projection_view: na::Matrix4<f32>,
-    shadow_map: Sampler<'a, DepthTexture2d>,
+    light_projection_views: [na::Matrix4<f32>; MAX_CASCADES],
+    shadow_maps: [Sampler<'a, DepthTexture2d>; MAX_CASCADES],
+    cascade_ends: [f32; MAX_CASCADES],
+    shadow_cubemap: Sampler<'a, DepthCubemap>,
 }
 
 impl_uniform_input!(
     ScenePassParams<'a>,
     self => {
-        shadow_light_projection_view: [[f32; 4]; 4] = self.light_projection_view.into(),
-        shadow_map: Sampler<'a, DepthTexture2d> = self.shadow_map,
+        shadow_light_projection_view_0: [[f32; 4]; 4] = self.light_projection_views[0].to_uniform(),
+        shadow_light_projection_view_1: [[f32; 4]; 4] = self.light_projection_views[1].to_uniform(),
+        shadow_light_projection_view_2: [[f32; 4]; 4] = self.light_projection_views[2].to_uniform(),
+        shadow_light_projection_view_3: [[f32; 4]; 4] = self.light_projection_views[3].to_uniform(),
+        shadow_map_0: Sampler<'a, DepthTexture2d> = self.shadow_maps[0],
+        shadow_map_1: Sampler<'a, DepthTexture2d> = self.shadow_maps[1],
+        shadow_map_2: Sampler<'a, DepthTexture2d> = self.shadow_maps[2],
+        shadow_map_3: Sampler<'a, DepthTexture2d> = self.shadow_maps[3],
+        shadow_cascade_ends: [f32; 4] = self.cascade_ends,
+        shadow_cubemap: Sampler<'a, DepthCubemap> = self.shadow_cubemap,
     },
 );
 
@@ -78,17 +176,49 @@ impl ScenePassComponent for ShadowMapping {
         &self,
         core: shader::Core<(Context, P), I, V>,
     ) -> shader::Core<(Context, P), I, V> {
-        shaders::render_shadowed_core_transform(
-            self.config.shadow_value,
-            self.config.pcf_distance,
-            core,
-        )
+        if let Some(point_light) = self.config.point_light.as_ref() {
+            shaders::render_point_shadowed_core_transform(
+                self.config.shadow_value,
+                self.config.normal_offset_bias,
+                point_light.far,
+                core,
+            )
+        } else {
+            shaders::render_shadowed_core_transform(
+                self.config.shadow_value,
+                self.config.pcf_distance,
+                self.config.normal_offset_bias,
+                self.shadow_textures.len(),
+                core,
+            )
+        }
     }
 
     fn params(&self, context: &Context) -> ScenePassParams {
+        let light_view = self.light_view(context);
+        let cascades = self.cascades(context);
+
+        let cascade = |index: usize| {
+            let index = index
+                .min(cascades.len() - 1)
+                .min(self.shadow_textures.len() - 1);
+            let sampler = Sampler::new(&self.shadow_textures[index])
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest);
+
+            (
+                cascades[index].0.to_matrix() * light_view,
+                sampler,
+                cascades[index].1,
+            )
+        };
+        let c = [cascade(0), cascade(1), cascade(2), cascade(3)];
+
         ScenePassParams {
-            light_projection_view: self.light_projection() * self.light_view(context),
-            shadow_map: Sampler::new(&self.shadow_texture)
+            light_projection_views: [c[0].0, c[1].0, c[2].0, c[3].0],
+            shadow_maps: [c[0].1, c[1].1, c[2].1, c[3].1],
+            cascade_ends: [c[0].2, c[1].2, c[2].2, c[3].2],
+            shadow_cubemap: Sampler::new(&self.shadow_cubemap)
                 .magnify_filter(MagnifySamplerFilter::Nearest)
                 .minify_filter(MinifySamplerFilter::Nearest),
         }
@@ -100,21 +230,129 @@ impl ShadowMapping {
         facade: &F,
         config: &Config,
     ) -> Result<ShadowMapping, CreationError> {
-        info!("Creating shadow texture");
-        let shadow_texture =
-            DepthTexture2d::empty(facade, config.shadow_map_size.x, config.shadow_map_size.y)?;
+        if config.num_cascades < 1 || config.num_cascades > MAX_CASCADES {
+            return Err(CreationError::InvalidConfig(format!(
+                "Shadow mapping needs between 1 and {} cascades, got {}",
+                MAX_CASCADES, config.num_cascades,
+            )));
+        }
+
+        let (shadow_textures, shadow_cubemap) = if let Some(point_light) = &config.point_light {
+            info!("Creating shadow cubemap");
+            (
+                vec![DepthTexture2d::empty(facade, 1, 1)?],
+                DepthCubemap::empty(facade, point_light.cubemap_size)?,
+            )
+        } else {
+            info!("Creating {} shadow textures", config.num_cascades);
+            let shadow_textures = (0..config.num_cascades)
+                .map(|_| {
+                    DepthTexture2d::empty(
+                        facade,
+                        config.shadow_map_size.x,
+                        config.shadow_map_size.y,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            (shadow_textures, DepthCubemap::empty(facade, 1)?)
+        };
 
         info!("Shadow mapping initialized");
 
         Ok(ShadowMapping {
             config: config.clone(),
-            shadow_texture,
+            shadow_textures,
+            shadow_cubemap,
         })
     }
 
-    fn light_projection(&self) -> na::Matrix4<f32> {
+    /// Light projection of each cascade, together with the view depth at
+    /// which the cascade ends.
+    ///
+    /// Falls back to a single cascade with the fixed light projection if the
+    /// camera's clipping planes are unknown.
+    fn cascades(&self, context: &Context) -> Vec<(Projection, f32)> {
+        let camera = &context.camera;
+        let inverse = (camera.projection_matrix() * camera.view).try_inverse();
+
+        let (near, far, inverse) = match (camera.near(), camera.far(), inverse) {
+            (Some(near), Some(far), Some(inverse)) if self.config.num_cascades > 1 => {
+                (near, far, inverse)
+            }
+            _ => return vec![(self.light_projection(), std::f32::MAX)],
+        };
+
+        // Rays along the edges of the view frustum, from the near plane to
+        // the far plane in world space
+        let edges = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| {
+                (
+                    inverse.transform_point(&na::Point3::new(x, y, -1.0)),
+                    inverse.transform_point(&na::Point3::new(x, y, 1.0)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let num_cascades = self.config.num_cascades;
+        let lambda = self.config.cascade_split_lambda;
+        let split = |index: usize| {
+            let fraction = index as f32 / num_cascades as f32;
+            let log_split = near * (far / near).powf(fraction);
+            let uniform_split = near + (far - near) * fraction;
+
+            lambda * log_split + (1.0 - lambda) * uniform_split
+        };
+
+        let light_view = self.light_view(context);
+
+        (0..num_cascades)
+            .map(|index| {
+                let (start, end) = (split(index), split(index + 1));
+
+                let mut min = na::Vector3::repeat(std::f32::MAX);
+                let mut max = na::Vector3::repeat(std::f32::MIN);
+
+                for &(near_point, far_point) in edges.iter() {
+                    for &depth in [start, end].iter() {
+                        let t = (depth - near) / (far - near);
+                        let point = near_point + (far_point - near_point) * t;
+                        let point = light_view.transform_point(&point).coords;
+
+                        min = min.inf(&point);
+                        max = max.sup(&point);
+                    }
+                }
+
+                // The light looks along negative z. Keep the near plane close
+                // to the light, so that casters in front of the cascade are
+                // included.
+                let projection = Projection::Orthographic {
+                    left: min.x,
+                    right: max.x,
+                    bottom: min.y,
+                    top: max.y,
+                    near: (-max.z).min(0.01),
+                    far: -min.z,
+                };
+
+                (projection, end)
+            })
+            .collect()
+    }
+
+    fn light_projection(&self) -> Projection {
         let w = 20.0;
-        na::Matrix4::new_orthographic(-w, w, -w, w, 0.01, 50.0)
+
+        Projection::Orthographic {
+            left: -w,
+            right: w,
+            bottom: -w,
+            top: w,
+            near: 0.01,
+            far: 50.0,
+        }
     }
 
     fn light_view(&self, context: &Context) -> na::Matrix4<f32> {
@@ -127,12 +365,17 @@ impl ShadowMapping {
 
     pub fn shadow_pass_core_transform<P, I, V>(
         &self,
-        core: shader::Core<P, I, V>,
-    ) -> shader::Core<P, I, V> {
-        shaders::depth_map_core_transform(core)
+        core: shader::Core<(Context, P), I, V>,
+    ) -> shader::Core<(Context, P), I, V> {
+        if let Some(point_light) = self.config.point_light.as_ref() {
+            shaders::distance_map_core_transform(point_light.far, core)
+        } else {
+            shaders::depth_map_core_transform(core)
+        }
     }
 
-    /// Render scene from the light's point of view into depth buffer.
+    /// Render scene from the light's point of view into the depth buffer of
+    /// each cascade.
     pub fn shadow_pass<F, I, V, P>(
         &self,
         facade: &F,
@@ -140,29 +383,82 @@ impl ShadowMapping {
         program: &glium::Program,
         params: (&Context, P),
         draw_params: &glium::DrawParameters,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         F: glium::backend::Facade,
         V: glium::vertex::Vertex,
         P: ToUniforms,
     {
-        let mut shadow_target =
-            glium::framebuffer::SimpleFrameBuffer::depth_only(facade, &self.shadow_texture)?;
+        if let Some(point_light) = self.config.point_light.as_ref() {
+            return self.point_shadow_pass(
+                facade,
+                drawable,
+                program,
+                params,
+                draw_params,
+                point_light,
+            );
+        }
 
-        let light_projection = self.light_projection();
         let light_view = self.light_view(params.0);
 
-        let camera = Camera {
-            viewport_size: params.0.camera.viewport_size,
-            projection: light_projection,
-            view: light_view,
+        let draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLessOrEqual,
+                write: true,
+                ..Default::default()
+            },
+            ..draw_params.clone()
         };
 
-        let light_context = Context {
-            camera,
-            ..*params.0
-        };
+        let mut stats = DrawStats::default();
+
+        for (shadow_texture, (light_projection, _)) in
+            self.shadow_textures.iter().zip(self.cascades(params.0))
+        {
+            let mut shadow_target =
+                glium::framebuffer::SimpleFrameBuffer::depth_only(facade, shadow_texture)?;
+
+            let camera = Camera {
+                viewport_size: params.0.camera.viewport_size,
+                projection: light_projection,
+                view: light_view,
+            };
+
+            let light_context = Context {
+                camera,
+                log_depth: false,
+                reverse_z: false,
+                ..*params.0
+            };
+
+            stats += drawable.draw(
+                program,
+                &(light_context, &params.1),
+                &draw_params,
+                &mut shadow_target,
+            )?;
+        }
 
+        Ok(stats)
+    }
+
+    /// Render scene from the point light's position into each face of the
+    /// shadow cubemap.
+    fn point_shadow_pass<F, I, V, P>(
+        &self,
+        facade: &F,
+        drawable: &impl Drawable<I, V>,
+        program: &glium::Program,
+        params: (&Context, P),
+        draw_params: &glium::DrawParameters,
+        point_light: &PointLightConfig,
+    ) -> Result<DrawStats, DrawError>
+    where
+        F: glium::backend::Facade,
+        V: glium::vertex::Vertex,
+        P: ToUniforms,
+    {
         let draw_params = glium::DrawParameters {
             depth: glium::Depth {
                 test: glium::DepthTest::IfLessOrEqual,
@@ -172,11 +468,48 @@ impl ShadowMapping {
             ..draw_params.clone()
         };
 
-        drawable.draw(
-            program,
-            &(light_context, params.1),
-            &draw_params,
-            &mut shadow_target,
-        )
+        // Each face covers a quarter turn in both directions
+        let projection = Projection::Perspective {
+            fovy: std::f32::consts::FRAC_PI_2,
+            aspect: 1.0,
+            near: 0.01,
+            far: point_light.far,
+        };
+        let light_pos = params.0.main_light_pos;
+
+        let mut stats = DrawStats::default();
+
+        for &(layer, direction, up) in CUBE_FACES.iter() {
+            let mut shadow_target = glium::framebuffer::SimpleFrameBuffer::depth_only(
+                facade,
+                self.shadow_cubemap.main_level().image(layer),
+            )?;
+
+            let camera = Camera {
+                viewport_size: params.0.camera.viewport_size,
+                projection,
+                view: na::Matrix4::look_at_rh(
+                    &light_pos,
+                    &(light_pos + na::Vector3::from(direction)),
+                    &na::Vector3::from(up),
+                ),
+            };
+
+            let light_context = Context {
+                camera,
+                log_depth: false,
+                reverse_z: false,
+                ..*params.0
+            };
+
+            stats += drawable.draw(
+                program,
+                &(light_context, &params.1),
+                &draw_params,
+                &mut shadow_target,
+            )?;
+        }
+
+        Ok(stats)
     }
 }
@@ -1,6 +1,6 @@
 use glium::uniforms::UniformType;
 
-use crate::pipeline::Context;
+use crate::pipeline::{shaders, Context};
 use crate::shader;
 
 /// Shader core for rendering the depth map from the light source's perspective.
@@ -15,10 +15,43 @@ pub fn depth_map_core_transform<P, I, V>(core: shader::Core<P, I, V>) -> shader:
     }
 }
 
+/// Shader core for rendering the distance from the point light into the
+/// shadow cubemap, scaled by `far` into the depth range.
+pub fn distance_map_core_transform<P, I, V>(
+    far: f32,
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    assert!(
+        core.vertex.has_out_def(shader::defs::V_WORLD_POS),
+        "VertexCore needs V_WORLD_POS output for point light shadow mapping"
+    );
+
+    let fragment = shader::FragmentCore::empty()
+        .with_in_def(shader::defs::V_WORLD_POS)
+        .with_body(&format!(
+            "
+            float light_distance = length(v_world_pos.xyz - context_main_light_pos) / {:?};
+            gl_FragDepth = light_distance;
+            ",
+            far,
+        ))
+        .with_out(shader::defs::F_FRAGMENT_DEPTH, "light_distance");
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
 /// Shader core for rendering the shadowed scene.
+///
+/// Each fragment is shadowed by the first of the `num_cascades` cascades
+/// whose end lies beyond the fragment's view depth.
 pub fn render_shadowed_core_transform<P, I, V>(
     shadow_value: f32,
     pcf_distance: usize,
+    normal_offset_bias: f32,
+    num_cascades: usize,
     core: shader::Core<(Context, P), I, V>,
 ) -> shader::Core<(Context, P), I, V> {
     assert!(
@@ -30,32 +63,11 @@ pub fn render_shadowed_core_transform<P, I, V>(
         "VertexCore needs V_WORLD_NORMAL output for shadow mapping"
     );
 
-    // Position of current vertex in light space
-    let v_light_space_pos = (
-        "v_light_space_pos",
-        shader::VertexOutDef(shader::Type::FloatVec4, shader::VertexOutQualifier::Smooth),
-    );
-
-    let vertex = core
-        .vertex
-        .with_extra_uniform("shadow_light_projection_view", UniformType::FloatMat4)
-        .with_out(
-            v_light_space_pos.clone(),
-            // Bias shadow coord a bit in the direction of the normal --
-            // this is a simple fix for a lot of self-shadowing artifacts
-            "shadow_light_projection_view * (v_world_pos + 0.02 * vec4(v_world_normal, 0.0))",
-        );
-
-    let shadow_calculation = "
-        float shadow_calculation(vec4 light_space_pos) {
-            vec3 light_dir = normalize(vec3(context_main_light_pos - v_world_pos.xyz));
-
+    let shadow_lookup = "
+        float shadow_lookup(sampler2D shadow_map, vec4 light_space_pos) {
             vec3 proj_coords = light_space_pos.xyz / light_space_pos.w;
             proj_coords = proj_coords * 0.5 + 0.5;
 
-            if (dot(light_dir, v_world_normal) < 0.0)
-                return SHADOW_VALUE;
-
             // Q: Is there a way to do this on texture-level?
             // Answer: yes, for x/y, but it's not supported in glium.
             // (GL_CLAMP_TO_BORDER + GL_TEXTURE_BORDER_COLOR)
@@ -72,11 +84,11 @@ pub fn render_shadowed_core_transform<P, I, V>(
             for (int x = -PCF_DISTANCE; x <= PCF_DISTANCE; ++x) {
                 for (int y = -PCF_DISTANCE; y <= PCF_DISTANCE; ++y) {
                     float closest_depth = textureOffset(
-                        shadow_map, 
+                        shadow_map,
                         proj_coords.xy,
                         ivec2(x, y)
                     ).r;
-                    
+
                     shadow += proj_coords.z > closest_depth ? SHADOW_VALUE : 1.0;
                 }
             }
@@ -84,23 +96,128 @@ pub fn render_shadowed_core_transform<P, I, V>(
 
             return shadow;
         }
+    ";
+
+    // Pick the cascade by view depth. The last cascade is used for
+    // everything beyond.
+    let cascade_selection = (0..num_cascades)
+        .map(|i| {
+            let lookup = format!(
+                "return shadow_lookup(shadow_map_{}, shadow_light_projection_view_{} * pos);",
+                i, i,
+            );
+
+            if i + 1 < num_cascades {
+                format!("if (view_depth < shadow_cascade_ends[{}]) {}\n", i, lookup)
+            } else {
+                lookup
+            }
+        })
+        .collect::<String>();
+
+    let shadow_calculation = "
+        float shadow_calculation() {
+            vec3 light_dir = normalize(vec3(context_main_light_pos - v_world_pos.xyz));
+
+            if (dot(light_dir, NORMAL) < 0.0)
+                return SHADOW_VALUE;
+
+            // Bias shadow coord a bit in the direction of the normal --
+            // this is a simple fix for a lot of self-shadowing artifacts
+            vec4 pos = v_world_pos + NORMAL_OFFSET_BIAS * vec4(NORMAL, 0.0);
+
+            float view_depth = -(context_camera_view * v_world_pos).z;
+
+            CASCADE_SELECTION
+        }
+    ";
+
+    let defs = (shadow_lookup.to_string() + shadow_calculation)
+        .replace("SHADOW_VALUE", &shadow_value.to_string())
+        .replace("PCF_DISTANCE", &pcf_distance.to_string())
+        .replace("PCF_SAMPLES", &(2 * pcf_distance + 1).pow(2).to_string())
+        .replace("NORMAL_OFFSET_BIAS", &format!("{:?}", normal_offset_bias))
+        .replace("NORMAL", shaders::normal_expr(&core.fragment))
+        .replace("CASCADE_SELECTION", &cascade_selection);
+
+    let mut fragment = core
+        .fragment
+        .with_extra_uniform("shadow_cascade_ends", UniformType::FloatVec4);
+
+    for i in 0..num_cascades {
+        fragment = fragment
+            .with_extra_uniform(
+                &format!("shadow_light_projection_view_{}", i),
+                UniformType::FloatMat4,
+            )
+            .with_extra_uniform(&format!("shadow_map_{}", i), UniformType::Sampler2d);
+    }
+
+    let fragment = fragment
+        .with_in_def(shader::defs::V_WORLD_POS)
+        .with_in_def(shader::defs::V_WORLD_NORMAL)
+        .with_defs(&defs)
+        .with_out(shader::defs::F_SHADOW, "shadow_calculation()");
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
+
+/// Shader core for rendering the scene shadowed by a point light, looking up
+/// the shadow cubemap in the direction from the light to the fragment.
+pub fn render_point_shadowed_core_transform<P, I, V>(
+    shadow_value: f32,
+    normal_offset_bias: f32,
+    far: f32,
+    core: shader::Core<(Context, P), I, V>,
+) -> shader::Core<(Context, P), I, V> {
+    assert!(
+        core.vertex.has_out_def(shader::defs::V_WORLD_POS),
+        "VertexCore needs V_WORLD_POS output for shadow mapping"
+    );
+    assert!(
+        core.vertex.has_out_def(shader::defs::V_WORLD_NORMAL),
+        "VertexCore needs V_WORLD_NORMAL output for shadow mapping"
+    );
+
+    let defs = "
+        float shadow_calculation() {
+            vec3 light_dir = normalize(context_main_light_pos - v_world_pos.xyz);
+
+            if (dot(light_dir, NORMAL) < 0.0)
+                return SHADOW_VALUE;
+
+            // As for directional lights, offset the lookup along the normal
+            // against self-shadowing
+            vec3 pos = v_world_pos.xyz + NORMAL_OFFSET_BIAS * NORMAL;
+            vec3 light_to_pos = pos - context_main_light_pos;
+
+            float current_distance = length(light_to_pos) / FAR;
+            if (current_distance > 1.0)
+                return 1.0;
+
+            float closest_distance = texture(shadow_cubemap, light_to_pos).r;
+
+            return current_distance > closest_distance ? SHADOW_VALUE : 1.0;
+        }
     "
-    .to_string()
     .replace("SHADOW_VALUE", &shadow_value.to_string())
-    .replace("PCF_DISTANCE", &pcf_distance.to_string())
-    .replace("PCF_SAMPLES", &(2 * pcf_distance + 1).pow(2).to_string());
+    .replace("NORMAL_OFFSET_BIAS", &format!("{:?}", normal_offset_bias))
+    .replace("NORMAL", shaders::normal_expr(&core.fragment))
+    .replace("FAR", &format!("{:?}", far));
 
     let fragment = core
         .fragment
-        .with_extra_uniform("shadow_map", UniformType::Sampler2d)
+        .with_extra_uniform("shadow_cubemap", UniformType::SamplerCube)
         .with_in_def(shader::defs::V_WORLD_POS)
         .with_in_def(shader::defs::V_WORLD_NORMAL)
-        .with_in_def(v_light_space_pos)
-        .with_defs(&shadow_calculation)
-        .with_out(
-            shader::defs::F_SHADOW,
-            "shadow_calculation(v_light_space_pos)",
-        );
-
-    shader::Core { vertex, fragment }
+        .with_defs(&defs)
+        .with_out(shader::defs::F_SHADOW, "shadow_calculation()");
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
 }
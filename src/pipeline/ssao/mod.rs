@@ -0,0 +1,273 @@
+//! Screen-space ambient occlusion.
+//!
+//! Occlusion is computed from the position/normal buffers of deferred
+//! shading, so this component requires deferred shading to be enabled. The
+//! resulting occlusion attenuates the ambient light in the composition pass.
+//!
+//! Heavily inspired by:
+//! https://learnopengl.com/Advanced-Lighting/SSAO
+
+pub mod shaders;
+
+use log::info;
+
+use nalgebra as na;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{uniform, Program, Surface, Texture2d};
+
+use crate::pipeline::render_pass::{
+    CompositionPassComponent, HasCompositionPassParams, RenderPassComponent,
+};
+use crate::shader::ToUniforms;
+use crate::{screen_quad, shader, Camera, Context, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of samples in the hemisphere around each pixel. Must be at
+    /// least one.
+    pub kernel_size: usize,
+
+    /// Radius of the sampled hemisphere, in world units.
+    pub radius: f32,
+
+    /// Depth offset for avoiding self-occlusion on flat surfaces.
+    pub bias: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kernel_size: 32,
+            radius: 0.5,
+            bias: 0.025,
+        }
+    }
+}
+
+/// Width and height of the texture of random rotations for the kernel.
+const NOISE_SIZE: usize = 4;
+
+pub struct Ssao {
+    occlusion_texture: Texture2d,
+    occlusion_texture_back: Texture2d,
+    noise_texture: Texture2d,
+
+    ssao_program: Program,
+    blur_program: Program,
+
+    screen_quad: ScreenQuad,
+}
+
+impl RenderPassComponent for Ssao {
+    fn clear_buffers<F: glium::backend::Facade>(&self, facade: &F) -> Result<(), DrawError> {
+        let mut framebuffer = SimpleFrameBuffer::new(facade, &self.occlusion_texture)?;
+        framebuffer.clear_color(1.0, 1.0, 1.0, 1.0);
+
+        Ok(())
+    }
+}
+
+pub struct CompositionPassParams<'a> {
+    occlusion_texture: &'a Texture2d,
+}
+
+impl_uniform_input!(
+    CompositionPassParams<'a>,
+    self => {
+        occlusion_texture: &'a Texture2d = self.occlusion_texture,
+    },
+);
+
+impl<'u> HasCompositionPassParams<'u> for Ssao {
+    type Params = CompositionPassParams<'u>;
+}
+
+impl CompositionPassComponent for Ssao {
+    fn core_transform(
+        &self,
+        core: shader::Core<Context, (), screen_quad::Vertex>,
+    ) -> shader::Core<Context, (), screen_quad::Vertex> {
+        shaders::composition_core_transform(core)
+    }
+
+    fn params(&self) -> CompositionPassParams {
+        CompositionPassParams {
+            occlusion_texture: &self.occlusion_texture,
+        }
+    }
+}
+
+impl Ssao {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        config: &Config,
+        target_size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        if config.kernel_size == 0 {
+            return Err(CreationError::InvalidConfig(
+                "SSAO needs a kernel size of at least 1".into(),
+            ));
+        }
+
+        info!("Creating SSAO textures");
+        let occlusion_texture = Self::create_texture(facade, target_size)?;
+        let occlusion_texture_back = Self::create_texture(facade, target_size)?;
+        let noise_texture = Self::create_noise_texture(facade)?;
+
+        info!("Creating SSAO programs");
+        let ssao_program = shaders::ssao_core(config, &Self::kernel(config.kernel_size))
+            .build_program(facade, shader::InstancingMode::Uniforms)?;
+        let blur_program =
+            shaders::blur_core().build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        info!("Creating screen quad");
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(Ssao {
+            occlusion_texture,
+            occlusion_texture_back,
+            noise_texture,
+            ssao_program,
+            blur_program,
+            screen_quad,
+        })
+    }
+
+    /// Computes the occlusion of the scene, given the world-space positions
+    /// and normals from the deferred shading scene pass.
+    pub fn ssao_pass<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+        camera: &Camera,
+        position_texture: &Texture2d,
+        normal_texture: &Texture2d,
+    ) -> Result<(), DrawError> {
+        let mut occlusion_buffer_back =
+            SimpleFrameBuffer::new(facade, &self.occlusion_texture_back)?;
+        let textures = uniform! {
+            position_texture: nearest_sampler(position_texture),
+            normal_texture: nearest_sampler(normal_texture),
+            noise_texture: &self.noise_texture,
+        };
+
+        occlusion_buffer_back.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.ssao_program,
+            &(&textures, camera).to_uniforms(),
+            &Default::default(),
+        )?;
+
+        let mut occlusion_buffer = SimpleFrameBuffer::new(facade, &self.occlusion_texture)?;
+
+        occlusion_buffer.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.blur_program,
+            &uniform! {
+                occlusion_texture: nearest_sampler(&self.occlusion_texture_back),
+            },
+            &Default::default(),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn on_target_resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        target_size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        self.occlusion_texture = Self::create_texture(facade, target_size)?;
+        self.occlusion_texture_back = Self::create_texture(facade, target_size)?;
+
+        Ok(())
+    }
+
+    /// Sample points in the hemisphere around the z axis, scaled so that more
+    /// samples are close to the origin.
+    fn kernel(kernel_size: usize) -> Vec<na::Vector3<f32>> {
+        (0..kernel_size)
+            .map(|i| {
+                let direction = na::Vector3::new(
+                    halton(i + 1, 2) * 2.0 - 1.0,
+                    halton(i + 1, 3) * 2.0 - 1.0,
+                    halton(i + 1, 5),
+                )
+                .normalize();
+
+                let scale = i as f32 / kernel_size as f32;
+                let scale = 0.1 + 0.9 * scale * scale;
+
+                direction * halton(i + 1, 7) * scale
+            })
+            .collect()
+    }
+
+    fn create_noise_texture<F: glium::backend::Facade>(
+        facade: &F,
+    ) -> Result<Texture2d, CreationError> {
+        // Random rotations around the z axis
+        let data = (0..NOISE_SIZE * NOISE_SIZE)
+            .flat_map(|i| {
+                let angle = halton(i + 1, 2) * 2.0 * std::f32::consts::PI;
+                vec![angle.cos(), angle.sin(), 0.0]
+            })
+            .collect::<Vec<f32>>();
+
+        let image = RawImage2d {
+            data: data.into(),
+            width: NOISE_SIZE as u32,
+            height: NOISE_SIZE as u32,
+            format: ClientFormat::F32F32F32,
+        };
+
+        Ok(Texture2d::with_format(
+            facade,
+            image,
+            UncompressedFloatFormat::F32F32F32,
+            MipmapsOption::NoMipmap,
+        )?)
+    }
+
+    fn create_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::F32,
+            MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+}
+
+fn nearest_sampler(texture: &Texture2d) -> Sampler<Texture2d> {
+    Sampler::new(texture)
+        .magnify_filter(MagnifySamplerFilter::Nearest)
+        .minify_filter(MinifySamplerFilter::Nearest)
+        .wrap_function(SamplerWrapFunction::Clamp)
+}
+
+/// Element `index` of the Halton sequence with the given `base`. This gives
+/// well-distributed values in [0, 1) without needing a random number
+/// generator.
+fn halton(mut index: usize, base: usize) -> f32 {
+    let mut result = 0.0;
+    let mut f = 1.0;
+
+    while index > 0 {
+        f /= base as f32;
+        result += f * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
@@ -0,0 +1,171 @@
+use nalgebra as na;
+
+use glium::uniforms::UniformType;
+
+use crate::pipeline::ssao::{Config, NOISE_SIZE};
+use crate::{screen_quad, shader, Camera, Context};
+
+fn screen_quad_vertex_core<P>() -> shader::VertexCore<P, (), screen_quad::Vertex> {
+    shader::VertexCore::empty()
+        .with_out(shader::defs::V_TEX_COORD, "tex_coord")
+        .with_out(shader::defs::V_POS, "position")
+}
+
+/// Shader core for computing the ambient occlusion of each pixel, given the
+/// position/normal buffers from the deferred shading scene pass.
+///
+/// The kernel is baked into the shader as a constant array. Occlusion is
+/// computed in view space, where samples are compared against the depth of
+/// the scene at their projected position.
+pub fn ssao_core(
+    config: &Config,
+    kernel: &[na::Vector3<f32>],
+) -> shader::Core<Camera, (), screen_quad::Vertex> {
+    let kernel_glsl = kernel
+        .iter()
+        .map(|v| format!("vec3({:?}, {:?}, {:?})", v.x, v.y, v.z))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("position_texture", UniformType::Sampler2d)
+        .with_extra_uniform("normal_texture", UniformType::Sampler2d)
+        .with_extra_uniform("noise_texture", UniformType::Sampler2d)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_defs(&format!(
+            "
+            const int KERNEL_SIZE = {};
+            const vec3 KERNEL[KERNEL_SIZE] = vec3[](
+                {}
+            );
+            const float RADIUS = {:?};
+            const float BIAS = {:?};
+            const int NOISE_SIZE = {};
+            ",
+            kernel.len(),
+            kernel_glsl,
+            config.radius,
+            config.bias,
+            NOISE_SIZE,
+        ))
+        .with_body(
+            "
+            vec3 world_normal = texture(normal_texture, v_tex_coord).xyz;
+            float occlusion = 1.0;
+
+            // Background pixels have no normal and are not occluded.
+            if (dot(world_normal, world_normal) > 0.5) {
+                vec3 world_pos = texture(position_texture, v_tex_coord).xyz;
+                vec3 position = (camera_view * vec4(world_pos, 1.0)).xyz;
+                vec3 normal = normalize(mat3(camera_view) * world_normal);
+
+                // Randomly rotate the kernel around the normal
+                vec3 random_vec = texelFetch(
+                    noise_texture,
+                    ivec2(gl_FragCoord.xy) % NOISE_SIZE,
+                    0
+                ).xyz;
+                vec3 tangent = normalize(random_vec - normal * dot(random_vec, normal));
+                vec3 bitangent = cross(normal, tangent);
+                mat3 tbn = mat3(tangent, bitangent, normal);
+
+                float occluded = 0.0;
+                for (int i = 0; i < KERNEL_SIZE; i++) {
+                    vec3 sample_pos = position + tbn * KERNEL[i] * RADIUS;
+
+                    vec4 projected = camera_projection * vec4(sample_pos, 1.0);
+                    vec2 sample_tex_coord = projected.xy / projected.w * 0.5 + 0.5;
+
+                    vec3 sample_normal = texture(normal_texture, sample_tex_coord).xyz;
+                    if (dot(sample_normal, sample_normal) < 0.5)
+                        continue;
+
+                    vec3 scene_world_pos = texture(position_texture, sample_tex_coord).xyz;
+                    float scene_depth = (camera_view * vec4(scene_world_pos, 1.0)).z;
+
+                    // Ignore occluders that are far away from the pixel
+                    float range_check = smoothstep(
+                        0.0,
+                        1.0,
+                        RADIUS / abs(position.z - scene_depth)
+                    );
+
+                    occluded += step(sample_pos.z + BIAS, scene_depth) * range_check;
+                }
+
+                occlusion = 1.0 - occluded / float(KERNEL_SIZE);
+            }
+            ",
+        )
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(occlusion, occlusion, occlusion, 1.0)",
+        );
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
+
+/// Shader core for removing the noise pattern from the occlusion texture, by
+/// averaging over a block the size of the noise texture.
+pub fn blur_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("occlusion_texture", UniformType::Sampler2d)
+        .with_in_def(shader::defs::V_TEX_COORD)
+        .with_defs(&format!("const int NOISE_SIZE = {};", NOISE_SIZE))
+        .with_body(
+            "
+            vec2 texel_size = 1.0 / vec2(textureSize(occlusion_texture, 0));
+            float occlusion = 0.0;
+
+            for (int x = 0; x < NOISE_SIZE; x++) {
+                for (int y = 0; y < NOISE_SIZE; y++) {
+                    vec2 offset = (vec2(x, y) - float(NOISE_SIZE) / 2.0 + 0.5) * texel_size;
+                    occlusion += texture(occlusion_texture, v_tex_coord + offset).r;
+                }
+            }
+
+            occlusion /= float(NOISE_SIZE * NOISE_SIZE);
+            ",
+        )
+        .with_out(
+            shader::defs::F_COLOR,
+            "vec4(occlusion, occlusion, occlusion, 1.0)",
+        );
+
+    shader::Core {
+        vertex: screen_quad_vertex_core(),
+        fragment,
+    }
+}
+
+/// Composition shader core transform for attenuating the ambient light by the
+/// occlusion.
+///
+/// This must be applied after the deferred shading composition transform,
+/// since it modifies the `lighting` that is computed there.
+pub fn composition_core_transform(
+    core: shader::Core<Context, (), screen_quad::Vertex>,
+) -> shader::Core<Context, (), screen_quad::Vertex> {
+    assert!(
+        core.fragment.has_in_def(shader::defs::V_TEX_COORD),
+        "FragmentCore needs V_TEX_COORD input for SSAO composition pass"
+    );
+
+    let fragment = core
+        .fragment
+        .with_extra_uniform("occlusion_texture", UniformType::Sampler2d)
+        .with_body(
+            "
+            float occlusion = texture(occlusion_texture, v_tex_coord).r;
+            lighting.rgb -= (1.0 - occlusion) * ambient * context_ambient_light;
+            ",
+        );
+
+    shader::Core {
+        vertex: core.vertex,
+        fragment,
+    }
+}
@@ -0,0 +1,148 @@
+//! Unlit rendering of raw line and point geometry.
+//!
+//! This bypasses `BasicObj` and is mostly meant for debugging, e.g. for
+//! visualizing rays, normals or point clouds. The geometry is collected in a
+//! `Batch`, uploaded as a `Mesh` and then drawn with a single `Instance`,
+//! usually in a `PlainScenePass`. For lines of a certain thickness, see
+//! `crate::line` instead.
+
+use nalgebra as na;
+
+use glium::implement_vertex;
+use glium::index::PrimitiveType;
+
+use crate::shader::ToUniform;
+use crate::{shader, Context, CreationError, HasTransform, HasTransformMut, Mesh, SceneCore};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(Vertex, position, color);
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub transform: na::Matrix4<f32>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            transform: na::Matrix4::identity(),
+        }
+    }
+}
+
+impl_instance_input!(
+    Instance,
+    self => {
+        instance_transform: [[f32; 4]; 4] = self.transform,
+    },
+);
+
+impl HasTransform for Instance {
+    fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+}
+
+impl HasTransformMut for Instance {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32> {
+        &mut self.transform
+    }
+}
+
+/// A list of vertices that are interpreted according to `primitive_type`.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pub primitive_type: PrimitiveType,
+    pub vertices: Vec<Vertex>,
+}
+
+impl Batch {
+    pub fn new(primitive_type: PrimitiveType) -> Self {
+        Self {
+            primitive_type,
+            vertices: Vec::new(),
+        }
+    }
+
+    pub fn lines() -> Self {
+        Self::new(PrimitiveType::LinesList)
+    }
+
+    pub fn points() -> Self {
+        Self::new(PrimitiveType::Points)
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn add_vertex(&mut self, position: &na::Point3<f32>, color: &na::Vector4<f32>) {
+        self.vertices.push(Vertex {
+            position: position.to_uniform(),
+            color: color.to_uniform(),
+        });
+    }
+
+    /// Adds a line segment. Only makes sense for `PrimitiveType::LinesList`.
+    pub fn add_line(
+        &mut self,
+        start: &na::Point3<f32>,
+        end: &na::Point3<f32>,
+        color: &na::Vector4<f32>,
+    ) {
+        self.add_vertex(start, color);
+        self.add_vertex(end, color);
+    }
+
+    /// Adds line segments with a shared color. Only makes sense for
+    /// `PrimitiveType::LinesList`.
+    pub fn add_lines(
+        &mut self,
+        lines: &[(na::Point3<f32>, na::Point3<f32>)],
+        color: &na::Vector4<f32>,
+    ) {
+        for (start, end) in lines {
+            self.add_line(start, end, color);
+        }
+    }
+
+    pub fn create_mesh<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+    ) -> Result<Mesh<Vertex>, CreationError> {
+        Mesh::create_without_indices(facade, self.primitive_type, &self.vertices)
+    }
+}
+
+pub struct Core;
+
+impl SceneCore for Core {
+    type Params = ();
+    type Instance = Instance;
+    type Vertex = Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, ()), Instance, Vertex> {
+        let vertex = shader::VertexCore::empty()
+            .with_out(shader::defs::V_COLOR, "color")
+            .with_out(
+                shader::defs::V_WORLD_POS,
+                "instance_transform * vec4(position, 1.0)",
+            )
+            .with_out(shader::defs::V_WORLD_NORMAL, "vec3(0.0, 0.0, 0.0)")
+            .with_out(
+                shader::defs::V_POS,
+                "context_camera_projection * context_camera_view * v_world_pos",
+            );
+
+        let fragment = shader::FragmentCore::empty()
+            .with_in_def(shader::defs::V_COLOR)
+            .with_out(shader::defs::F_COLOR, "v_color");
+
+        shader::Core { vertex, fragment }
+    }
+}
@@ -1,30 +1,550 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use nalgebra as na;
+
+use crate::frustum;
 use crate::shader::{InstanceInput, InstancingMode, ToUniforms};
-use crate::{DrawError, Drawable, Mesh};
+use crate::{Aabb, Camera, Context, DrawError, DrawStats, Drawable, Frustum, Mesh, Outline};
+
+/// Instances that are placed in the world by a transformation matrix.
+pub trait HasTransform {
+    fn transform(&self) -> &na::Matrix4<f32>;
+
+    fn position(&self) -> na::Point3<f32> {
+        self.transform().transform_point(&na::Point3::origin())
+    }
+
+    /// Bounding sphere in world space, as center and radius, of an instance
+    /// whose mesh is contained in a sphere of `radius` around the origin in
+    /// model space, e.g. `basic_obj::Resources::bounding_radius`.
+    ///
+    /// The radius is scaled by the largest scaling factor of the transform,
+    /// so the sphere stays conservative with non-uniform scaling.
+    fn bounding_sphere(&self, radius: f32) -> (na::Point3<f32>, f32) {
+        frustum::transformed_sphere(self.transform(), radius)
+    }
+}
+
+/// Instances whose transformation matrix can be replaced, e.g. for moving
+/// them relative to the eye with `Context::camera_relative`.
+pub trait HasTransformMut: HasTransform {
+    fn transform_mut(&mut self) -> &mut na::Matrix4<f32>;
+
+    /// This instance moved into the world of `Context::relative_to_eye`, if
+    /// `Context::camera_relative` is enabled. Otherwise, it is returned as is.
+    fn in_context(&self, context: &Context) -> Cow<Self>
+    where
+        Self: Clone,
+    {
+        if context.camera_relative {
+            let mut instance = self.clone();
+            *instance.transform_mut() = context.camera.transform_relative_to_eye(self.transform());
+            Cow::Owned(instance)
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+}
+
+/// Depth settings of a single instance, overriding those of the pass that it
+/// is drawn in, e.g. for gizmos or selection highlights that should always be
+/// visible. See `RenderList::set_depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceDepth {
+    /// Whether the instance can be hidden by what has been drawn before. If
+    /// false, the instance is drawn on top.
+    pub test: bool,
+
+    /// Whether the instance writes depth, if the pass does.
+    pub write: bool,
+}
+
+impl Default for InstanceDepth {
+    fn default() -> Self {
+        Self {
+            test: true,
+            write: true,
+        }
+    }
+}
+
+impl InstanceDepth {
+    /// Instance depth that is neither tested nor written.
+    pub fn overlay() -> Self {
+        Self {
+            test: false,
+            write: false,
+        }
+    }
+
+    /// Applies the overrides to the draw parameters of a pass.
+    pub fn draw_params<'a, 'b>(
+        self,
+        draw_params: &'b glium::DrawParameters<'a>,
+    ) -> Cow<'b, glium::DrawParameters<'a>> {
+        if self == Self::default() {
+            return Cow::Borrowed(draw_params);
+        }
+
+        let mut draw_params = draw_params.clone();
+
+        if !self.test {
+            draw_params.depth.test = glium::DepthTest::Overwrite;
+        }
+        if !self.write {
+            draw_params.depth.write = false;
+        }
+
+        Cow::Owned(draw_params)
+    }
+}
+
+/// Identifies an instance in a `RenderList`.
+///
+/// Ids stay valid while other instances are added, removed or reordered. Once
+/// the instance is removed, its id is never reused for another instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId {
+    slot: usize,
+    generation: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u64,
+
+    /// Position of the instance in the densely packed arrays, or `None` if
+    /// the slot is free.
+    index: Option<usize>,
+}
+
+/// Ids of densely packed instances, mapping each id to the position of its
+/// instance. The arrays of the instances themselves are kept by the owner,
+/// which needs to move them in the same way as the positions here.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Slots {
+    /// Slot of the instance at each position.
+    owners: Vec<usize>,
+
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
+
+    /// Previous `owners` while permuting, kept to reuse its allocation.
+    scratch_owners: Vec<usize>,
+}
+
+/// Reorders `values` so that the value at position `i` is moved from position
+/// `order[i]`. The allocations of `values` and `scratch` are kept, so that
+/// this does not allocate once both are large enough.
+fn permute_copied<T: Copy>(values: &mut Vec<T>, scratch: &mut Vec<T>, order: &[usize]) {
+    std::mem::swap(values, scratch);
+    values.clear();
+    values.extend(order.iter().map(|&index| scratch[index]));
+}
+
+impl Slots {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Slots {
+            owners: Vec::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            free_slots: Vec::new(),
+            scratch_owners: Vec::new(),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.owners.reserve(additional);
+        self.slots
+            .reserve(additional.saturating_sub(self.free_slots.len()));
+    }
+
+    /// Invalidates the ids of all instances.
+    pub fn clear(&mut self) {
+        while let Some(slot) = self.owners.pop() {
+            self.free_slot(slot);
+        }
+    }
+
+    /// Returns a new id for an instance that is pushed to the end of the
+    /// arrays.
+    pub fn push(&mut self) -> InstanceId {
+        let index = self.owners.len();
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot].index = Some(index);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    index: Some(index),
+                });
+                self.slots.len() - 1
+            }
+        };
+
+        self.owners.push(slot);
+
+        InstanceId {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Invalidates the id, returning the position of its instance, which
+    /// needs to be removed from the arrays with `Vec::swap_remove`.
+    pub fn swap_remove(&mut self, id: InstanceId) -> Option<usize> {
+        let index = self.index_of(id)?;
+
+        self.free_slot(id.slot);
+        self.owners.swap_remove(index);
 
+        if let Some(&moved_slot) = self.owners.get(index) {
+            self.slots[moved_slot].index = Some(index);
+        }
+
+        Some(index)
+    }
+
+    /// Invalidates the id of the instance at position `index`, which then
+    /// needs to be dropped with `permute`.
+    pub fn free_at(&mut self, index: usize) {
+        self.free_slot(self.owners[index]);
+    }
+
+    pub fn index_of(&self, id: InstanceId) -> Option<usize> {
+        self.slots.index_of(id)
+    }
+
+    pub fn id_at(&self, index: usize) -> Option<InstanceId> {
+        self.owners.get(index).map(|&slot| InstanceId {
+            slot,
+            generation: self.slots[slot].generation,
+        })
+    }
+
+    /// Moves the instance at position `order[i]` to position `i`, see
+    /// `RenderList::permute`.
+    pub fn permute(&mut self, order: &[usize]) {
+        permute_copied(&mut self.owners, &mut self.scratch_owners, order);
+
+        for (index, &slot) in self.owners.iter().enumerate() {
+            self.slots[slot].index = Some(index);
+        }
+    }
+
+    /// Invalidates the ids of the slot and makes it available for reuse.
+    fn free_slot(&mut self, slot: usize) {
+        self.slots[slot].generation += 1;
+        self.slots[slot].index = None;
+        self.free_slots.push(slot);
+    }
+}
+
+/// Instances of one type, e.g. those of one object, in the order in which
+/// they are drawn.
+///
+/// Instances are stored as an array of structs, which is uploaded as is. For
+/// `basic_obj::Instance`, `basic_obj::ColumnRenderList` keeps separate
+/// arrays per field instead.
 #[derive(Clone)]
 pub struct RenderList<I: InstanceInput> {
-    instances: Vec<I::Vertex>,
+    /// Instances are kept densely packed, so that they can be uploaded and
+    /// drawn directly.
+    instances: Vec<I>,
+
+    /// Whether each instance in `instances` is drawn, see `set_visible`.
+    visible: Vec<bool>,
+
+    /// Depth overrides of each instance in `instances`, see `set_depth`.
+    depths: Vec<InstanceDepth>,
+
+    /// Ids of the instances in `instances`.
+    slots: Slots,
+
+    /// Buffers for `permute`, kept to reuse their allocations. All entries
+    /// of `scratch_instances` are `None` outside of `permute`.
+    scratch_instances: Vec<Option<I>>,
+    scratch_visible: Vec<bool>,
+    scratch_depths: Vec<InstanceDepth>,
+
+    /// Whether the instances have changed since they were last uploaded with
+    /// `Instancing::update_if_dirty`.
+    dirty: bool,
+
+    /// Number of instances that have been removed by `cull` since the list
+    /// was last cleared.
+    num_culled: usize,
 }
 
 impl<I: InstanceInput> Default for RenderList<I> {
     fn default() -> Self {
         RenderList {
             instances: Vec::new(),
+            visible: Vec::new(),
+            depths: Vec::new(),
+            slots: Slots::default(),
+            scratch_instances: Vec::new(),
+            scratch_visible: Vec::new(),
+            scratch_depths: Vec::new(),
+            dirty: true,
+            num_culled: 0,
         }
     }
 }
 
 impl<I: InstanceInput> RenderList<I> {
+    /// Creates an empty list with space for at least `capacity` instances
+    /// before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        RenderList {
+            instances: Vec::with_capacity(capacity),
+            visible: Vec::with_capacity(capacity),
+            depths: Vec::with_capacity(capacity),
+            slots: Slots::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
+    /// Reserves space for at least `additional` more instances.
+    pub fn reserve(&mut self, additional: usize) {
+        self.instances.reserve(additional);
+        self.visible.reserve(additional);
+        self.depths.reserve(additional);
+        self.slots.reserve(additional);
+    }
+
+    /// Removes all instances, invalidating their ids.
     pub fn clear(&mut self) {
+        self.slots.clear();
         self.instances.clear();
+        self.visible.clear();
+        self.depths.clear();
+        self.dirty = true;
+        self.num_culled = 0;
     }
 
-    pub fn as_slice(&self) -> &[I::Vertex] {
+    /// All instances, including those hidden with `set_visible`.
+    pub fn as_slice(&self) -> &[I] {
         &self.instances
     }
 
-    pub fn add(&mut self, params: I) {
-        self.instances.push(params.to_vertex());
+    /// Instances that are drawn, together with their position in `as_slice`.
+    pub fn visible_instances(&self) -> impl Iterator<Item = (usize, &I)> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| self.visible[*index])
+    }
+
+    /// Adds an instance, returning an id for updating or removing it later.
+    pub fn add(&mut self, params: I) -> InstanceId {
+        self.instances.push(params);
+        self.visible.push(true);
+        self.depths.push(InstanceDepth::default());
+        self.dirty = true;
+
+        self.slots.push()
+    }
+
+    /// Moves all instances of `other` to the end of this list, returning
+    /// their new ids in the order of `other.as_slice()`. Visibility and depth
+    /// settings are kept.
+    ///
+    /// Ids of `other` are not valid in this list, so they need to be replaced
+    /// by the returned ones.
+    pub fn append(&mut self, other: RenderList<I>) -> Vec<InstanceId> {
+        self.reserve(other.instances.len());
+
+        other
+            .instances
+            .into_iter()
+            .zip(other.visible)
+            .zip(other.depths)
+            .map(|((instance, visible), depth)| {
+                let id = self.add(instance);
+                self.set_visible(id, visible);
+                self.set_depth(id, depth);
+                id
+            })
+            .collect()
+    }
+
+    /// Copies all instances of `other` to the end of this list, returning
+    /// their ids in this list in the order of `other.as_slice()`. Visibility
+    /// and depth settings are kept.
+    pub fn extend(&mut self, other: &RenderList<I>) -> Vec<InstanceId>
+    where
+        I: Clone,
+    {
+        self.reserve(other.instances.len());
+
+        other
+            .instances
+            .iter()
+            .zip(other.visible.iter())
+            .zip(other.depths.iter())
+            .map(|((instance, &visible), &depth)| {
+                let id = self.add(instance.clone());
+                self.set_visible(id, visible);
+                self.set_depth(id, depth);
+                id
+            })
+            .collect()
+    }
+
+    /// Replaces the instance with the given id, returning false if the id is
+    /// no longer valid.
+    pub fn update(&mut self, id: InstanceId, params: I) -> bool {
+        if let Some(index) = self.index_of(id) {
+            self.instances[index] = params;
+            self.dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the instance with the given id, returning it if the id was
+    /// still valid.
+    ///
+    /// The last instance takes the place of the removed one, so this changes
+    /// the order of instances.
+    pub fn remove(&mut self, id: InstanceId) -> Option<I> {
+        let index = self.slots.swap_remove(id)?;
+
+        let instance = self.instances.swap_remove(index);
+        self.visible.swap_remove(index);
+        self.depths.swap_remove(index);
+        self.dirty = true;
+
+        Some(instance)
+    }
+
+    /// Shows or hides the instance with the given id, returning false if the
+    /// id is no longer valid.
+    ///
+    /// Hidden instances keep their id and position, but are skipped when
+    /// drawing, uploading and picking. This is cheaper than removing and
+    /// adding the instance again.
+    pub fn set_visible(&mut self, id: InstanceId, visible: bool) -> bool {
+        if let Some(index) = self.index_of(id) {
+            if self.visible[index] != visible {
+                self.visible[index] = visible;
+                self.dirty = true;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the instance with the given id is drawn, or `None` if the id
+    /// is no longer valid.
+    pub fn is_visible(&self, id: InstanceId) -> Option<bool> {
+        self.index_of(id).map(|index| self.visible[index])
+    }
+
+    /// Overrides the depth settings of the pass for the instance with the
+    /// given id, returning false if the id is no longer valid.
+    ///
+    /// Draws are batched by depth settings, so instances with overrides cost
+    /// additional draw calls when drawn with `Instancing`.
+    pub fn set_depth(&mut self, id: InstanceId, depth: InstanceDepth) -> bool {
+        if let Some(index) = self.index_of(id) {
+            if self.depths[index] != depth {
+                self.depths[index] = depth;
+                self.dirty = true;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Depth settings of the instance with the given id, or `None` if the id
+    /// is no longer valid.
+    pub fn depth(&self, id: InstanceId) -> Option<InstanceDepth> {
+        self.index_of(id).map(|index| self.depths[index])
+    }
+
+    /// Depth settings of the instance at position `index` in `as_slice`.
+    pub fn depth_at(&self, index: usize) -> InstanceDepth {
+        self.depths[index]
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&I> {
+        self.index_of(id).map(|index| &self.instances[index])
+    }
+
+    /// Position of the instance with the given id in `as_slice`.
+    pub fn index_of(&self, id: InstanceId) -> Option<usize> {
+        self.slots
+            .get(id.slot)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.index)
+    }
+
+    /// Id of the instance at position `index` in `as_slice`, e.g. for
+    /// instances found by `pick`.
+    pub fn id_at(&self, index: usize) -> Option<InstanceId> {
+        self.slots.id_at(index)
+    }
+
+    /// Reorders instances so that the instance at position `i` is moved from
+    /// position `order[i]`, keeping ids intact. Instances whose position is
+    /// not in `order` are dropped; their slots must have been freed already.
+    fn permute(&mut self, order: &[usize]) {
+        let scratch = &mut self.scratch_instances;
+        scratch.extend(self.instances.drain(..).map(Some));
+
+        // Safe to unwrap, since `order` contains no duplicates
+        self.instances
+            .extend(order.iter().map(|&index| scratch[index].take().unwrap()));
+        scratch.clear();
+
+        permute_copied(&mut self.visible, &mut self.scratch_visible, order);
+        permute_copied(&mut self.depths, &mut self.scratch_depths, order);
+        self.slots.permute(order);
+
+        self.dirty = true;
+    }
+
+    /// Whether the instances have changed since they were last uploaded with
+    /// `Instancing::update_if_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Forces the next `Instancing::update_if_dirty` to upload the instances.
+    ///
+    /// All methods of `RenderList` that change instances already do this, so
+    /// this is only needed if instances are changed by other means.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Selects the instance with the given id for drawing an outline of
+    /// `thickness` around it with an `OutlinePass`. Returns `None` if the id
+    /// is no longer valid or the instance is hidden with `set_visible`.
+    pub fn render_outline(
+        &self,
+        id: InstanceId,
+        color: na::Vector4<f32>,
+        thickness: f32,
+    ) -> Option<Outline<I>> {
+        let index = self.index_of(id).filter(|&index| self.visible[index])?;
+
+        Some(Outline {
+            instance: &self.instances[index],
+            color,
+            thickness,
+        })
     }
 
     pub fn as_drawable<'a, V: glium::vertex::Vertex>(
@@ -35,6 +555,122 @@ impl<I: InstanceInput> RenderList<I> {
     }
 }
 
+impl<I: InstanceInput + HasTransform> RenderList<I> {
+    /// Sorts the instances by ascending distance from the camera.
+    ///
+    /// Drawing opaque instances front-to-back allows the depth test to
+    /// discard hidden fragments early. Sorting is opt-in, since it changes
+    /// the order in which instances are drawn.
+    ///
+    /// Transparent instances (i.e. those with alpha < 1.0) should not be
+    /// sorted with this. Keep them in a separate list and sort it with
+    /// `sort_back_to_front` instead.
+    pub fn sort_front_to_back(&mut self, camera: &Camera) {
+        let eye = camera.eye();
+        let instances = &self.instances;
+
+        let mut order = (0..instances.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            let distance_a = na::distance_squared(&eye, &instances[a].position());
+            let distance_b = na::distance_squared(&eye, &instances[b].position());
+
+            distance_a
+                .partial_cmp(&distance_b)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        self.permute(&order);
+    }
+
+    /// Sorts the instances by descending distance from the camera, as is
+    /// needed for blending transparent instances.
+    pub fn sort_back_to_front(&mut self, camera: &Camera) {
+        self.sort_front_to_back(camera);
+
+        let order = (0..self.instances.len()).rev().collect::<Vec<_>>();
+        self.permute(&order);
+    }
+
+    /// Removes instances that lie completely outside of `frustum`, returning
+    /// how many instances were removed. The ids of removed instances become
+    /// invalid.
+    ///
+    /// Each instance is approximated by a sphere of `radius` in model space,
+    /// which should contain the mesh that is drawn. Culling is opt-in, since
+    /// it costs some time on the CPU.
+    pub fn cull(&mut self, frustum: &Frustum, radius: f32) -> usize {
+        let visible = self
+            .instances
+            .iter()
+            .map(|instance| frustum.intersects_transformed_sphere(instance.transform(), radius))
+            .collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(visible.len());
+
+        for (index, &is_visible) in visible.iter().enumerate() {
+            if is_visible {
+                order.push(index);
+            } else {
+                self.slots.free_at(index);
+            }
+        }
+
+        let num_culled = visible.len() - order.len();
+        if num_culled > 0 {
+            self.permute(&order);
+        }
+
+        self.num_culled += num_culled;
+
+        num_culled
+    }
+
+    /// Number of instances that have been removed by `cull` since the list
+    /// was last cleared. This is reported as `DrawStats::instances_culled`
+    /// when drawing the list.
+    pub fn num_culled(&self) -> usize {
+        self.num_culled
+    }
+
+    /// Finds the visible instance that is hit first by a ray, returning its
+    /// index and the ray parameter `t` of the hit.
+    ///
+    /// Instances are approximated by `aabb`, given in model space. The ray
+    /// is transformed into the local space of each instance, so this works
+    /// for rotated and scaled instances as well.
+    pub fn pick(
+        &self,
+        ray_origin: &na::Point3<f32>,
+        ray_direction: &na::Vector3<f32>,
+        aabb: &Aabb,
+    ) -> Option<(usize, f32)> {
+        self.visible_instances()
+            .filter_map(|(index, instance)| {
+                let inverse = instance.transform().try_inverse()?;
+                let local_origin = inverse.transform_point(ray_origin);
+                let local_direction = inverse.transform_vector(ray_direction);
+
+                aabb.ray_intersection(&local_origin, &local_direction)
+                    .map(|t| (index, t))
+            })
+            .min_by(|(_, t_a), (_, t_b)| t_a.partial_cmp(t_b).unwrap_or(Ordering::Equal))
+    }
+}
+
+impl<I: InstanceInput + HasTransformMut + Clone> RenderList<I> {
+    /// Like `as_drawable`, but with each instance moved relative to the eye
+    /// when drawing, if `Context::camera_relative` is enabled in `context`.
+    ///
+    /// `context` needs to be the one that is given to the pipeline, i.e. not
+    /// the result of `Context::relative_to_eye`.
+    pub fn as_drawable_in<'a, V: glium::vertex::Vertex>(
+        &'a self,
+        mesh: &'a Mesh<V>,
+        context: &'a Context,
+    ) -> impl Drawable<I, V> + 'a {
+        ContextDrawableImpl(self, mesh, context)
+    }
+}
+
 struct DrawableImpl<'a, I: InstanceInput, V: Copy>(&'a RenderList<I>, &'a Mesh<V>);
 
 impl<'a, I, V> Drawable<I, V> for DrawableImpl<'a, I, V>
@@ -42,7 +678,9 @@ where
     I: InstanceInput,
     V: glium::vertex::Vertex,
 {
-    const INSTANCING_MODE: InstancingMode = InstancingMode::Uniforms;
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
 
     fn draw<U, S>(
         &self,
@@ -50,21 +688,295 @@ where
         uniforms: &U,
         draw_params: &glium::DrawParameters,
         target: &mut S,
-    ) -> Result<(), DrawError>
+    ) -> Result<DrawStats, DrawError>
     where
         U: ToUniforms,
         S: glium::Surface,
     {
-        for instance in &self.0.instances {
-            target.draw(
-                &self.1.vertex_buffer,
-                &self.1.index_buffer,
-                program,
-                &(uniforms, instance).to_uniforms(),
-                draw_params,
-            )?;
+        let mut num_instances = 0;
+
+        for (index, instance) in self.0.visible_instances() {
+            target
+                .draw(
+                    &self.1.vertex_buffer,
+                    &self.1.index_buffer,
+                    program,
+                    &(uniforms, instance).to_uniforms(),
+                    &self
+                        .1
+                        .draw_params(&self.0.depths[index].draw_params(draw_params)),
+                )
+                .map_err(|error| DrawError::Instance { index, error })?;
+
+            num_instances += 1;
         }
 
-        Ok(())
+        Ok(DrawStats {
+            draw_calls: num_instances,
+            instances: num_instances,
+            instances_culled: self.0.num_culled,
+        })
+    }
+}
+
+struct ContextDrawableImpl<'a, I: InstanceInput, V: Copy>(
+    &'a RenderList<I>,
+    &'a Mesh<V>,
+    &'a Context,
+);
+
+impl<'a, I, V> Drawable<I, V> for ContextDrawableImpl<'a, I, V>
+where
+    I: InstanceInput + HasTransformMut + Clone,
+    V: glium::vertex::Vertex,
+{
+    fn instancing_mode(&self) -> InstancingMode {
+        InstancingMode::Uniforms
+    }
+
+    fn draw<U, S>(
+        &self,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_params: &glium::DrawParameters,
+        target: &mut S,
+    ) -> Result<DrawStats, DrawError>
+    where
+        U: ToUniforms,
+        S: glium::Surface,
+    {
+        let mut num_instances = 0;
+
+        for (index, instance) in self.0.visible_instances() {
+            target
+                .draw(
+                    &self.1.vertex_buffer,
+                    &self.1.index_buffer,
+                    program,
+                    &(uniforms, instance.in_context(self.2).as_ref()).to_uniforms(),
+                    &self
+                        .1
+                        .draw_params(&self.0.depths[index].draw_params(draw_params)),
+                )
+                .map_err(|error| DrawError::Instance { index, error })?;
+
+            num_instances += 1;
+        }
+
+        Ok(DrawStats {
+            draw_calls: num_instances,
+            instances: num_instances,
+            instances_culled: self.0.num_culled,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::basic_obj::Instance;
+
+    fn instance(x: f32) -> Instance {
+        Instance {
+            transform: na::Matrix4::new_translation(&na::Vector3::new(x, 0.0, 0.0)),
+            ..Default::default()
+        }
+    }
+
+    fn x(list: &RenderList<Instance>, id: InstanceId) -> Option<f32> {
+        list.get(id).map(|instance| instance.position().x)
+    }
+
+    #[test]
+    fn removed_ids_are_rejected() {
+        let mut list = RenderList::default();
+        let a = list.add(instance(1.0));
+        let b = list.add(instance(2.0));
+
+        assert_eq!(
+            list.remove(a).map(|instance| instance.position().x),
+            Some(1.0)
+        );
+        assert!(list.remove(a).is_none());
+        assert!(list.get(a).is_none());
+        assert!(!list.update(a, instance(5.0)));
+        assert!(!list.set_visible(a, false));
+        assert_eq!(x(&list, b), Some(2.0));
+
+        // The slot of `a` is reused, but its old id must not match the new
+        // instance.
+        let c = list.add(instance(3.0));
+        assert_ne!(a, c);
+        assert!(list.get(a).is_none());
+        assert_eq!(x(&list, c), Some(3.0));
+    }
+
+    #[test]
+    fn cleared_ids_are_rejected() {
+        let mut list = RenderList::with_capacity(16);
+        let ids = (0..4)
+            .map(|i| list.add(instance(i as f32)))
+            .collect::<Vec<_>>();
+
+        list.clear();
+
+        assert!(list.as_slice().is_empty());
+        assert!(ids.iter().all(|&id| list.get(id).is_none()));
+
+        let id = list.add(instance(7.0));
+        assert!(ids.iter().all(|&old_id| old_id != id));
+        assert_eq!(x(&list, id), Some(7.0));
+    }
+
+    #[test]
+    fn clear_keeps_capacity() {
+        let mut list = RenderList::with_capacity(16);
+        for i in 0..16 {
+            list.add(instance(i as f32));
+        }
+        let capacity = list.instances.capacity();
+
+        list.clear();
+        list.sort_front_to_back(&Camera::perspective(
+            na::Vector2::new(1.0, 1.0),
+            1.0,
+            0.1,
+            100.0,
+        ));
+
+        assert!(list.instances.capacity() >= capacity);
+        assert!(list.slots.owners.capacity() >= capacity);
+        assert!(list.visible.capacity() >= capacity);
+        assert!(list.depths.capacity() >= capacity);
+    }
+
+    #[test]
+    fn remove_moves_last_instance() {
+        let mut list = RenderList::default();
+        let a = list.add(instance(1.0));
+        let b = list.add(instance(2.0));
+        let c = list.add(instance(3.0));
+
+        list.set_visible(c, false);
+        list.set_depth(c, InstanceDepth::overlay());
+        list.remove(a);
+
+        assert_eq!(list.index_of(c), Some(0));
+        assert_eq!(list.index_of(b), Some(1));
+        assert_eq!(list.id_at(0), Some(c));
+        assert_eq!(list.is_visible(c), Some(false));
+        assert_eq!(list.depth(c), Some(InstanceDepth::overlay()));
+        assert_eq!(list.is_visible(b), Some(true));
+    }
+
+    #[test]
+    fn ids_are_stable_across_sort() {
+        let camera = Camera::perspective(na::Vector2::new(1.0, 1.0), 1.0, 0.1, 100.0);
+        let mut list = RenderList::default();
+        let far = list.add(instance(3.0));
+        let near = list.add(instance(1.0));
+        let middle = list.add(instance(2.0));
+
+        list.set_visible(middle, false);
+        list.sort_front_to_back(&camera);
+
+        assert_eq!(list.index_of(near), Some(0));
+        assert_eq!(list.index_of(middle), Some(1));
+        assert_eq!(list.index_of(far), Some(2));
+        assert_eq!(list.is_visible(middle), Some(false));
+        for (index, id) in [near, middle, far].iter().enumerate() {
+            assert_eq!(list.id_at(index), Some(*id));
+        }
+
+        list.sort_back_to_front(&camera);
+
+        assert_eq!(list.index_of(far), Some(0));
+        assert_eq!(list.index_of(near), Some(2));
+        assert_eq!(x(&list, middle), Some(2.0));
+    }
+
+    #[test]
+    fn ids_are_stable_across_cull() {
+        let frustum = Frustum::from_matrix(
+            &na::Orthographic3::new(-5.0, 5.0, -5.0, 5.0, -1.0, 1.0).to_homogeneous(),
+        );
+        let mut list = RenderList::default();
+        let outside = list.add(instance(-10.0));
+        let a = list.add(instance(1.0));
+        let outside_too = list.add(instance(10.0));
+        let b = list.add(instance(-2.0));
+
+        assert_eq!(list.cull(&frustum, 0.5), 2);
+        assert_eq!(list.num_culled(), 2);
+
+        assert!(list.get(outside).is_none());
+        assert!(list.get(outside_too).is_none());
+        assert_eq!(x(&list, a), Some(1.0));
+        assert_eq!(x(&list, b), Some(-2.0));
+        assert_eq!(list.as_slice().len(), 2);
+        assert_eq!(list.id_at(list.index_of(b).unwrap()), Some(b));
+
+        list.clear();
+        assert_eq!(list.num_culled(), 0);
+    }
+
+    #[test]
+    fn append_and_extend_remap_ids() {
+        let mut other = RenderList::default();
+        let hidden = other.add(instance(1.0));
+        let overlay = other.add(instance(2.0));
+        other.set_visible(hidden, false);
+        other.set_depth(overlay, InstanceDepth::overlay());
+
+        let mut list = RenderList::default();
+        let existing = list.add(instance(0.0));
+
+        let extended = list.extend(&other);
+        assert_eq!(extended.len(), 2);
+        assert_eq!(x(&list, extended[0]), Some(1.0));
+        assert_eq!(list.is_visible(extended[0]), Some(false));
+        assert_eq!(list.depth(extended[1]), Some(InstanceDepth::overlay()));
+
+        let appended = list.append(other);
+        assert_eq!(x(&list, appended[1]), Some(2.0));
+        assert_eq!(list.is_visible(appended[0]), Some(false));
+        assert_eq!(list.is_visible(appended[1]), Some(true));
+        assert_eq!(list.depth(appended[1]), Some(InstanceDepth::overlay()));
+
+        assert_eq!(x(&list, existing), Some(0.0));
+        assert_eq!(list.as_slice().len(), 5);
+    }
+
+    #[test]
+    fn hidden_instances_are_skipped() {
+        let mut list = RenderList::default();
+        let a = list.add(instance(1.0));
+        let b = list.add(instance(2.0));
+
+        list.mark_clean();
+        assert!(list.set_visible(a, false));
+        assert!(list.is_dirty());
+
+        let visible = list
+            .visible_instances()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(visible, vec![list.index_of(b).unwrap()]);
+
+        let color = na::Vector4::new(1.0, 1.0, 1.0, 1.0);
+        assert!(list.render_outline(a, color, 1.0).is_none());
+        assert!(list.render_outline(b, color, 1.0).is_some());
+
+        let aabb = Aabb {
+            min: na::Point3::new(-0.5, -0.5, -0.5),
+            max: na::Point3::new(0.5, 0.5, 0.5),
+        };
+        let hit = list.pick(
+            &na::Point3::new(-5.0, 0.0, 0.0),
+            &na::Vector3::new(1.0, 0.0, 0.0),
+            &aabb,
+        );
+        assert_eq!(hit.map(|(index, _)| index), list.index_of(b));
     }
 }
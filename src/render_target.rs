@@ -0,0 +1,213 @@
+use log::info;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{
+    DepthFormat, DepthTexture2d, DepthTexture2dMultisample, MipmapsOption, RawImage2d, Texture2d,
+    Texture2dMultisample, UncompressedFloatFormat,
+};
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{BlitMask, BlitTarget, Rect, Surface};
+
+use crate::{CreationError, DrawError};
+
+/// An offscreen target that can be rendered into instead of the window.
+///
+/// This is useful for taking screenshots or for feeding the rendered scene
+/// into further postprocessing.
+///
+/// With multisampling, drawing goes into multisampled attachments, which need
+/// to be resolved into the color and depth textures with `resolve` before
+/// reading them.
+/// Note that `Pipeline` draws the scene into its own single-sampled textures
+/// and only copies the result into the target, so multisampling applies to
+/// what is drawn directly into the target's surface.
+pub struct RenderTarget {
+    size: (u32, u32),
+    color_texture: Texture2d,
+    depth_texture: DepthTexture2d,
+    multisample: Option<Multisample>,
+}
+
+struct Multisample {
+    num_samples: u32,
+    color_texture: Texture2dMultisample,
+    depth_texture: DepthTexture2dMultisample,
+}
+
+impl RenderTarget {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Self, CreationError> {
+        Self::create_multisampled(facade, size, 1)
+    }
+
+    /// Creates a target with `num_samples` samples per pixel. Multisampling is
+    /// disabled if `num_samples` is at most one.
+    pub fn create_multisampled<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+        num_samples: u32,
+    ) -> Result<Self, CreationError> {
+        info!(
+            "Creating render target with size {:?} and {} samples",
+            size, num_samples,
+        );
+
+        let multisample = if num_samples > 1 {
+            Some(Multisample {
+                num_samples,
+                color_texture: Texture2dMultisample::empty_with_format(
+                    facade,
+                    UncompressedFloatFormat::F32F32F32F32,
+                    MipmapsOption::NoMipmap,
+                    size.0,
+                    size.1,
+                    num_samples,
+                )?,
+                depth_texture: DepthTexture2dMultisample::empty_with_format(
+                    facade,
+                    DepthFormat::F32,
+                    MipmapsOption::NoMipmap,
+                    size.0,
+                    size.1,
+                    num_samples,
+                )?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            size,
+            color_texture: Self::create_color_texture(facade, size)?,
+            depth_texture: DepthTexture2d::empty_with_format(
+                facade,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                size.0,
+                size.1,
+            )?,
+            multisample,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Number of samples per pixel, which is one without multisampling.
+    pub fn num_samples(&self) -> u32 {
+        self.multisample
+            .as_ref()
+            .map_or(1, |multisample| multisample.num_samples)
+    }
+
+    /// Recreates the attachments if `size` differs from the current size.
+    pub fn resize<F: glium::backend::Facade>(
+        &mut self,
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<(), CreationError> {
+        if size != self.size {
+            *self = Self::create_multisampled(facade, size, self.num_samples())?;
+        }
+
+        Ok(())
+    }
+
+    /// The single-sampled color texture. With multisampling, this only contains
+    /// the rendered image after calling `resolve`.
+    pub fn color_texture(&self) -> &Texture2d {
+        &self.color_texture
+    }
+
+    /// The single-sampled depth texture, e.g. for `dof::Dof`. With
+    /// multisampling, this only contains the depth of the rendered image after
+    /// calling `resolve`.
+    pub fn depth_texture(&self) -> &DepthTexture2d {
+        &self.depth_texture
+    }
+
+    pub fn as_surface<'a, F: glium::backend::Facade>(
+        &'a self,
+        facade: &F,
+    ) -> Result<SimpleFrameBuffer<'a>, DrawError> {
+        if let Some(multisample) = self.multisample.as_ref() {
+            Ok(SimpleFrameBuffer::with_depth_buffer(
+                facade,
+                &multisample.color_texture,
+                &multisample.depth_texture,
+            )?)
+        } else {
+            Ok(SimpleFrameBuffer::with_depth_buffer(
+                facade,
+                &self.color_texture,
+                &self.depth_texture,
+            )?)
+        }
+    }
+
+    /// Blits the multisampled color and depth attachments into the color and
+    /// depth textures, so that they can be sampled or read. This does nothing
+    /// without multisampling.
+    ///
+    /// Depth is taken from one sample per pixel, since depth values can not
+    /// be averaged.
+    pub fn resolve<F: glium::backend::Facade>(&self, facade: &F) -> Result<(), DrawError> {
+        if let Some(multisample) = self.multisample.as_ref() {
+            let source = SimpleFrameBuffer::with_depth_buffer(
+                facade,
+                &multisample.color_texture,
+                &multisample.depth_texture,
+            )?;
+            let target = SimpleFrameBuffer::with_depth_buffer(
+                facade,
+                &self.color_texture,
+                &self.depth_texture,
+            )?;
+
+            // Depth can only be blitted with nearest filtering
+            target.blit_buffers_from_simple_framebuffer(
+                &source,
+                &Rect {
+                    left: 0,
+                    bottom: 0,
+                    width: self.size.0,
+                    height: self.size.1,
+                },
+                &BlitTarget {
+                    left: 0,
+                    bottom: 0,
+                    width: self.size.0 as i32,
+                    height: self.size.1 as i32,
+                },
+                MagnifySamplerFilter::Nearest,
+                BlitMask::color_and_depth(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reads the color attachment back from the GPU. With multisampling, call
+    /// `resolve` first.
+    ///
+    /// Note that this stalls until rendering into the target has finished.
+    pub fn read_to_image(&self) -> RawImage2d<'static, u8> {
+        self.color_texture.read()
+    }
+
+    fn create_color_texture<F: glium::backend::Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<Texture2d, CreationError> {
+        Ok(Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )?)
+    }
+}
@@ -0,0 +1,57 @@
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction,
+};
+
+/// How a texture is sampled, e.g. for sharpening textures that are viewed at
+/// grazing angles with anisotropic filtering.
+///
+/// The default matches glium's default sampling of textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerConfig {
+    pub magnify_filter: MagnifySamplerFilter,
+
+    /// Filters such as `LinearMipmapLinear` use mipmaps, which need to have
+    /// been generated for the texture.
+    pub minify_filter: MinifySamplerFilter,
+
+    pub wrap_function: SamplerWrapFunction,
+
+    /// Maximal level of anisotropic filtering, where 1 disables it. This is
+    /// clamped to what the hardware supports.
+    pub max_anisotropy: u16,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        let behavior = SamplerBehavior::default();
+
+        Self {
+            magnify_filter: behavior.magnify_filter,
+            minify_filter: behavior.minify_filter,
+            wrap_function: behavior.wrap_function.0,
+            max_anisotropy: behavior.max_anisotropy,
+        }
+    }
+}
+
+impl SamplerConfig {
+    pub fn sampler<'t, T>(&self, texture: &'t T) -> Sampler<'t, T> {
+        Sampler(texture, (*self).into())
+    }
+}
+
+impl From<SamplerConfig> for SamplerBehavior {
+    fn from(config: SamplerConfig) -> Self {
+        SamplerBehavior {
+            wrap_function: (
+                config.wrap_function,
+                config.wrap_function,
+                config.wrap_function,
+            ),
+            magnify_filter: config.magnify_filter,
+            minify_filter: config.minify_filter,
+            max_anisotropy: config.max_anisotropy,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use crate::scene::SceneCore;
+use crate::shader;
+use crate::Context;
+
+/// Paths of GLSL files that replace parts of a scene core.
+///
+/// The `defs` are placed before `main`, while the `body` is placed at the
+/// start of `main`, before the outputs of the core are assigned. Parts whose
+/// path is `None` are kept as given by the wrapped core.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderFiles {
+    pub vertex_defs: Option<PathBuf>,
+    pub vertex_body: Option<PathBuf>,
+    pub fragment_defs: Option<PathBuf>,
+    pub fragment_body: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Sources {
+    vertex_defs: Option<String>,
+    vertex_body: Option<String>,
+    fragment_defs: Option<String>,
+    fragment_body: Option<String>,
+}
+
+fn read(path: &Option<PathBuf>) -> Result<Option<String>, std::io::Error> {
+    path.as_ref().map(std::fs::read_to_string).transpose()
+}
+
+impl ShaderFiles {
+    fn read(&self) -> Result<Sources, std::io::Error> {
+        Ok(Sources {
+            vertex_defs: read(&self.vertex_defs)?,
+            vertex_body: read(&self.vertex_body)?,
+            fragment_defs: read(&self.fragment_defs)?,
+            fragment_body: read(&self.fragment_body)?,
+        })
+    }
+}
+
+/// A scene core whose GLSL is partly read from files, for editing shaders
+/// while the application is running.
+///
+/// The files are read by `FileSceneCore::load` and `FileSceneCore::reload`.
+/// After reloading, pass the core to e.g.
+/// `Pipeline::rebuild_shaded_scene_pass` to recompile the programs that use
+/// it.
+#[derive(Debug, Clone)]
+pub struct FileSceneCore<C> {
+    pub core: C,
+    pub files: ShaderFiles,
+    sources: Sources,
+}
+
+impl<C: SceneCore> FileSceneCore<C> {
+    pub fn load(core: C, files: ShaderFiles) -> Result<Self, crate::CreationError> {
+        let sources = files.read()?;
+
+        Ok(Self {
+            core,
+            files,
+            sources,
+        })
+    }
+
+    /// Reads the files again. If reading fails, the error is returned and
+    /// the last sources are kept.
+    pub fn reload(&mut self) -> Result<(), crate::CreationError> {
+        self.sources = self.files.read()?;
+
+        Ok(())
+    }
+}
+
+impl<C: SceneCore> SceneCore for FileSceneCore<C> {
+    type Params = C::Params;
+    type Instance = C::Instance;
+    type Vertex = C::Vertex;
+
+    fn scene_core(&self) -> shader::Core<(Context, Self::Params), Self::Instance, Self::Vertex> {
+        let mut core = self.core.scene_core();
+
+        if let Some(defs) = self.sources.vertex_defs.as_ref() {
+            core.vertex.defs = defs.clone();
+        }
+        if let Some(body) = self.sources.vertex_body.as_ref() {
+            core.vertex.body = body.clone();
+        }
+        if let Some(defs) = self.sources.fragment_defs.as_ref() {
+            core.fragment.defs = defs.clone();
+        }
+        if let Some(body) = self.sources.fragment_body.as_ref() {
+            core.fragment.body = body.clone();
+        }
+
+        core
+    }
+}
@@ -1,6 +1,19 @@
+mod file;
+
 use crate::shader::{self, InstanceInput, UniformInput};
 use crate::Context;
 
+pub use file::{FileSceneCore, ShaderFiles};
+
+/// Defines how a type of instance is rendered in the scene passes.
+///
+/// Every `SceneCore` gets its own program for each pass, e.g. a
+/// `ShadedScenePass<C>`, and its own `RenderList<C::Instance>`. In order to
+/// mix different cores in one frame, create a pass for each of them and draw
+/// their render lists one after another within the same pipeline step; see
+/// `examples/custom_scene_core.rs`. Since each core defines its own instance
+/// type, selecting a core per instance in a single render list is not
+/// supported.
 pub trait SceneCore {
     type Params: UniformInput + Clone;
     type Instance: InstanceInput + Clone;
@@ -0,0 +1,129 @@
+//! Conversions from nalgebra types to the arrays that glium takes as uniform
+//! values and vertex attributes.
+//!
+//! nalgebra matrices are stored in column-major order, as are the arrays
+//! that glium expects, so the conversions never transpose.
+
+use nalgebra as na;
+
+pub trait ToUniform {
+    type Value;
+
+    fn to_uniform(&self) -> Self::Value;
+}
+
+impl ToUniform for na::Matrix4<f32> {
+    type Value = [[f32; 4]; 4];
+
+    fn to_uniform(&self) -> Self::Value {
+        (*self).into()
+    }
+}
+
+impl ToUniform for na::Matrix3<f32> {
+    type Value = [[f32; 3]; 3];
+
+    fn to_uniform(&self) -> Self::Value {
+        (*self).into()
+    }
+}
+
+impl ToUniform for na::Vector2<f32> {
+    type Value = [f32; 2];
+
+    fn to_uniform(&self) -> Self::Value {
+        (*self).into()
+    }
+}
+
+impl ToUniform for na::Vector3<f32> {
+    type Value = [f32; 3];
+
+    fn to_uniform(&self) -> Self::Value {
+        (*self).into()
+    }
+}
+
+impl ToUniform for na::Vector4<f32> {
+    type Value = [f32; 4];
+
+    fn to_uniform(&self) -> Self::Value {
+        (*self).into()
+    }
+}
+
+impl ToUniform for na::Point2<f32> {
+    type Value = [f32; 2];
+
+    fn to_uniform(&self) -> Self::Value {
+        self.coords.into()
+    }
+}
+
+impl ToUniform for na::Point3<f32> {
+    type Value = [f32; 3];
+
+    fn to_uniform(&self) -> Self::Value {
+        self.coords.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix4_is_column_major() {
+        let m = na::Matrix4::from_fn(|r, c| (r * 4 + c) as f32);
+        let value = m.to_uniform();
+
+        for r in 0..4 {
+            for c in 0..4 {
+                assert_eq!(value[c][r], m[(r, c)]);
+            }
+        }
+
+        assert_eq!(na::Matrix4::from(value), m);
+    }
+
+    #[test]
+    fn matrix3_is_column_major() {
+        let m = na::Matrix3::from_fn(|r, c| (r * 3 + c) as f32);
+        let value = m.to_uniform();
+
+        for r in 0..3 {
+            for c in 0..3 {
+                assert_eq!(value[c][r], m[(r, c)]);
+            }
+        }
+
+        assert_eq!(na::Matrix3::from(value), m);
+    }
+
+    #[test]
+    fn vectors_round_trip() {
+        let v2 = na::Vector2::new(1.0f32, 2.0);
+        let v3 = na::Vector3::new(1.0f32, 2.0, 3.0);
+        let v4 = na::Vector4::new(1.0f32, 2.0, 3.0, 4.0);
+
+        assert_eq!(v2.to_uniform(), [1.0, 2.0]);
+        assert_eq!(v3.to_uniform(), [1.0, 2.0, 3.0]);
+        assert_eq!(v4.to_uniform(), [1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(na::Vector2::from(v2.to_uniform()), v2);
+        assert_eq!(na::Vector3::from(v3.to_uniform()), v3);
+        assert_eq!(na::Vector4::from(v4.to_uniform()), v4);
+    }
+
+    #[test]
+    fn points_round_trip() {
+        let p2 = na::Point2::new(1.0f32, 2.0);
+        let p3 = na::Point3::new(1.0f32, 2.0, 3.0);
+
+        assert_eq!(p2.to_uniform(), [1.0, 2.0]);
+        assert_eq!(p3.to_uniform(), [1.0, 2.0, 3.0]);
+
+        assert_eq!(na::Point2::from(na::Vector2::from(p2.to_uniform())), p2);
+        assert_eq!(na::Point3::from(na::Vector3::from(p3.to_uniform())), p3);
+    }
+}
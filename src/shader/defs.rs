@@ -20,6 +20,12 @@ pub const V_COLOR: (&str, VertexOutDef) = (
     VertexOutDef(Type::FloatVec4, VertexOutQualifier::Smooth),
 );
 
+/// Per-instance tint, for passing on to `F_TINT`.
+pub const V_TINT: (&str, VertexOutDef) = (
+    "v_tint",
+    VertexOutDef(Type::FloatVec4, VertexOutQualifier::Flat),
+);
+
 pub const V_TEX_COORD: (&str, VertexOutDef) = (
     "v_tex_coord",
     VertexOutDef(Type::FloatVec2, VertexOutQualifier::Smooth),
@@ -39,3 +45,44 @@ pub const F_SHADOW: (&str, FragmentOutDef) = (
     "f_shadow",
     FragmentOutDef(Type::Float, FragmentOutQualifier::Local),
 );
+
+/// World-space normal for lighting. Scene cores can output this in order to
+/// replace the interpolated `V_WORLD_NORMAL`, e.g. for normal mapping.
+pub const F_NORMAL: (&str, FragmentOutDef) = (
+    "f_normal",
+    FragmentOutDef(Type::FloatVec3, FragmentOutQualifier::Local),
+);
+
+/// Factor for the ambient light. See `Material`.
+pub const F_AMBIENT: (&str, FragmentOutDef) = (
+    "f_ambient",
+    FragmentOutDef(Type::Float, FragmentOutQualifier::Local),
+);
+
+/// Factor for diffuse lighting. See `Material`.
+pub const F_DIFFUSE: (&str, FragmentOutDef) = (
+    "f_diffuse",
+    FragmentOutDef(Type::Float, FragmentOutQualifier::Local),
+);
+
+/// Color of specular highlights. Scene cores can output this together with
+/// `F_SHININESS` in order to get Blinn-Phong highlights.
+pub const F_SPECULAR: (&str, FragmentOutDef) = (
+    "f_specular",
+    FragmentOutDef(Type::FloatVec3, FragmentOutQualifier::Local),
+);
+
+/// Color that the lit color, including specular highlights, is multiplied
+/// with after lighting, e.g. for color variants of one textured material.
+/// In passes without lighting, it is multiplied with `F_COLOR`.
+pub const F_TINT: (&str, FragmentOutDef) = (
+    "f_tint",
+    FragmentOutDef(Type::FloatVec4, FragmentOutQualifier::Local),
+);
+
+/// Blinn-Phong exponent; larger values give smaller, sharper highlights.
+/// Non-positive values disable specular highlights.
+pub const F_SHININESS: (&str, FragmentOutDef) = (
+    "f_shininess",
+    FragmentOutDef(Type::Float, FragmentOutQualifier::Local),
+);
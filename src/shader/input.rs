@@ -189,6 +189,46 @@ where
     }
 }
 
+/// An object-safe version of `Uniforms`, so that uniforms of different types
+/// can be kept in one collection as `Box<dyn DynUniforms + 'a>`.
+///
+/// This is implemented for all `Uniforms`, so that e.g. instances of
+/// different types can be collected by boxing the result of their
+/// `ToUniforms::to_uniforms`, which may borrow from the instances.
+///
+/// Boxed uniforms implement `ToUniforms`, so they can be combined with other
+/// uniforms in draw calls, and a collection of them can be drawn with
+/// `Mesh::as_dyn_drawable`. Note that each program still needs exactly the
+/// uniforms it was built for.
+pub trait DynUniforms {
+    fn visit_dyn_values<'a>(&'a self, output: &mut dyn FnMut(&str, UniformValue<'a>));
+}
+
+impl<U: Uniforms> DynUniforms for U {
+    fn visit_dyn_values<'a>(&'a self, output: &mut dyn FnMut(&str, UniformValue<'a>)) {
+        self.visit_values(|name, value| output(name, value));
+    }
+}
+
+impl<'b, 'c> Uniforms for &'b (dyn DynUniforms + 'c) {
+    fn visit_values<'a, F>(&'a self, mut output: F)
+    where
+        F: FnMut(&str, UniformValue<'a>),
+    {
+        (**self).visit_dyn_values(&mut output);
+    }
+}
+
+impl<'u, 'b> HasUniforms<'u> for Box<dyn DynUniforms + 'b> {
+    type Uniforms = &'u (dyn DynUniforms + 'u);
+}
+
+impl<'b> ToUniforms for Box<dyn DynUniforms + 'b> {
+    fn to_uniforms(&self) -> &dyn DynUniforms {
+        self.as_ref()
+    }
+}
+
 pub struct UniformsPair<U1, U2>(U1, U2);
 
 impl<U1, U2> Uniforms for UniformsPair<U1, U2>
@@ -289,6 +329,10 @@ impl StaticUniformType for bool {
     const TYPE: UniformType = UniformType::Bool;
 }
 
+impl StaticUniformType for i32 {
+    const TYPE: UniformType = UniformType::Int;
+}
+
 impl StaticUniformType for f32 {
     const TYPE: UniformType = UniformType::Float;
 }
@@ -333,6 +377,22 @@ impl<'a> StaticUniformType for Sampler<'a, glium::texture::DepthTexture2d> {
     const TYPE: UniformType = UniformType::Sampler2d;
 }
 
+impl<'a> StaticUniformType for &'a glium::texture::DepthCubemap {
+    const TYPE: UniformType = UniformType::SamplerCube;
+}
+
+impl<'a> StaticUniformType for Sampler<'a, glium::texture::DepthCubemap> {
+    const TYPE: UniformType = UniformType::SamplerCube;
+}
+
+impl<'a> StaticUniformType for &'a glium::texture::SrgbTexture2d {
+    const TYPE: UniformType = UniformType::Sampler2d;
+}
+
+impl<'a> StaticUniformType for Sampler<'a, glium::texture::SrgbTexture2d> {
+    const TYPE: UniformType = UniformType::Sampler2d;
+}
+
 impl<'a> StaticUniformType for &'a glium::texture::CompressedSrgbTexture2d {
     const TYPE: UniformType = UniformType::Sampler2d;
 }
@@ -1,5 +1,6 @@
 #[macro_use]
 pub mod input;
+pub mod convert;
 pub mod defs;
 
 use log::info;
@@ -13,7 +14,8 @@ use glsl::visitor::Host;
 use glium::uniforms::UniformType;
 use glium::vertex::AttributeType;
 
-pub use input::{HasUniforms, InstanceInput, ToUniforms, UniformInput};
+pub use convert::ToUniform;
+pub use input::{DynUniforms, HasUniforms, InstanceInput, ToUniforms, UniformInput};
 
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -43,6 +45,7 @@ pub struct FragmentOutDef(pub UniformType, pub FragmentOutQualifier);
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VertexCore<P, I, V> {
     pub extra_uniforms: BTreeMap<VariableName, UniformType>,
+    pub uniform_blocks: BTreeMap<VariableName, Vec<(VariableName, UniformType)>>,
     pub out_defs: BTreeMap<VariableName, VertexOutDef>,
     pub defs: GLSL,
     pub body: GLSL,
@@ -53,6 +56,7 @@ pub struct VertexCore<P, I, V> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FragmentCore<P> {
     pub extra_uniforms: BTreeMap<VariableName, UniformType>,
+    pub uniform_blocks: BTreeMap<VariableName, Vec<(VariableName, UniformType)>>,
     pub in_defs: BTreeMap<VariableName, VertexOutDef>,
     pub out_defs: BTreeMap<VariableName, FragmentOutDef>,
     pub defs: GLSL,
@@ -83,6 +87,7 @@ impl<P, I, V> Default for VertexCore<P, I, V> {
     fn default() -> Self {
         Self {
             extra_uniforms: BTreeMap::new(),
+            uniform_blocks: BTreeMap::new(),
             out_defs: BTreeMap::new(),
             defs: "".into(),
             body: "".into(),
@@ -96,6 +101,7 @@ impl<P> Default for FragmentCore<P> {
     fn default() -> Self {
         Self {
             extra_uniforms: BTreeMap::new(),
+            uniform_blocks: BTreeMap::new(),
             in_defs: BTreeMap::new(),
             out_defs: BTreeMap::new(),
             defs: "".into(),
@@ -126,6 +132,14 @@ impl<P, I, V> VertexCore<P, I, V> {
         self
     }
 
+    /// Declares a uniform block in `std140` layout with the given members.
+    /// Uniforms of `P` with the same names as members are read from the
+    /// block instead of being declared individually.
+    pub fn with_uniform_block(mut self, name: &str, members: &[(String, UniformType)]) -> Self {
+        self.uniform_blocks.insert(name.into(), members.to_vec());
+        self
+    }
+
     pub fn with_defs(mut self, defs: &str) -> Self {
         self.defs += defs;
         self
@@ -154,6 +168,14 @@ impl<P, I, V> VertexCore<P, I, V> {
         self
     }
 
+    /// Reassigns `gl_Position`, e.g. for transforming the position that has
+    /// been output by the core. Unlike other outputs, the position is built
+    /// in and needs no definition, so it can not go through `with_out_expr`.
+    pub fn with_position_expr(mut self, expr: &str) -> Self {
+        self.out_exprs.push((defs::V_POS.0.into(), expr.into()));
+        self
+    }
+
     pub fn with_out_def(mut self, (name, def): (&str, VertexOutDef)) -> Self {
         self.out_defs.insert(name.into(), def);
         self
@@ -190,6 +212,12 @@ impl<P> FragmentCore<P> {
         self
     }
 
+    /// See `VertexCore::with_uniform_block`.
+    pub fn with_uniform_block(mut self, name: &str, members: &[(String, UniformType)]) -> Self {
+        self.uniform_blocks.insert(name.into(), members.to_vec());
+        self
+    }
+
     pub fn with_defs(mut self, defs: &str) -> Self {
         self.defs += defs;
         self
@@ -438,6 +466,7 @@ fn compile_type(t: Type) -> &'static str {
         Type::IntVec3 => "ivec3",
         Type::IntVec4 => "ivec4",
         Type::Sampler2d => "sampler2D",
+        Type::SamplerCube => "samplerCube",
         Type::Bool => "bool",
         _ => unimplemented!("Given Type not yet supported: {:?}", t),
     }
@@ -488,10 +517,36 @@ fn compile_fragment_out_defs(defs: &BTreeMap<VariableName, FragmentOutDef>) -> S
         .join("")
 }
 
-fn compile_uniform_input<P: UniformInput>() -> String {
-    let uniforms = P::uniform_input_defs();
+fn compile_uniform_input<P: UniformInput>(
+    blocks: &BTreeMap<VariableName, Vec<(VariableName, UniformType)>>,
+) -> String {
+    let is_in_block = |name: &str| {
+        blocks
+            .values()
+            .any(|members| members.iter().any(|(member, _)| member == name))
+    };
+
+    let uniforms = P::uniform_input_defs()
+        .into_iter()
+        .filter(|(name, _)| !is_in_block(name));
+
+    compile_variable_defs("uniform", uniforms) + &compile_uniform_blocks(blocks)
+}
 
-    compile_variable_defs("uniform", uniforms.iter().cloned())
+fn compile_uniform_blocks(
+    blocks: &BTreeMap<VariableName, Vec<(VariableName, UniformType)>>,
+) -> String {
+    blocks
+        .iter()
+        .map(|(name, members)| {
+            format!(
+                "layout(std140) uniform {} {{\n{}}};\n",
+                name,
+                compile_variable_defs("   ", members.iter().cloned()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("")
 }
 
 fn compile_instance_input<P: UniformInput>(mode: InstancingMode) -> String {
@@ -542,7 +597,7 @@ where
 
         s += "#version 330\n\n";
 
-        s += &compile_uniform_input::<P>();
+        s += &compile_uniform_input::<P>(&self.uniform_blocks);
         s += "\n";
         s += &compile_instance_input::<I>(mode);
         s += "\n";
@@ -575,7 +630,7 @@ where
 
         s += "#version 330\n\n";
 
-        s += &compile_uniform_input::<P>();
+        s += &compile_uniform_input::<P>(&self.uniform_blocks);
         s += "\n";
         s += &compile_variable_defs("uniform", self.extra_uniforms.clone().into_iter());
         s += "\n";
@@ -0,0 +1,104 @@
+//! A background that is drawn behind everything else in the scene.
+//!
+//! The skybox covers the screen with a quad at the far plane, looking up a
+//! cubemap in the view direction of each pixel. Only the rotation of the
+//! camera is taken into account, so the sky appears infinitely far away.
+
+mod shaders;
+
+use log::info;
+
+use nalgebra as na;
+
+use glium::texture::SrgbCubemap;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{uniform, Program, Surface};
+
+use crate::shader::{self, ToUniform};
+use crate::{Context, DrawError, ScreenQuad};
+
+pub use crate::CreationError;
+
+pub struct Skybox {
+    texture: SrgbCubemap,
+    program: Program,
+    screen_quad: ScreenQuad,
+}
+
+impl Skybox {
+    pub fn create<F: glium::backend::Facade>(
+        facade: &F,
+        texture: SrgbCubemap,
+    ) -> Result<Self, CreationError> {
+        info!("Creating skybox program");
+        let core = shaders::skybox_core();
+        let program = core.build_program(facade, shader::InstancingMode::Uniforms)?;
+
+        info!("Creating screen quad");
+        let screen_quad = ScreenQuad::create(facade)?;
+
+        Ok(Skybox {
+            texture,
+            program,
+            screen_quad,
+        })
+    }
+
+    pub fn texture(&self) -> &SrgbCubemap {
+        &self.texture
+    }
+
+    /// Draws the sky onto `target`, behind everything that has already been
+    /// drawn into its depth buffer.
+    ///
+    /// Fragments are placed at the far plane and depth tested with
+    /// `IfLessOrEqual`, so the sky only shows where the depth buffer still
+    /// holds its cleared value. It can be drawn either first or after the
+    /// opaque parts of the scene; the latter saves shading hidden pixels.
+    /// With a `Pipeline`, use `ShadedScenePassStep::draw_skybox`, which draws
+    /// into the scene before composition and tests against its depth.
+    pub fn render<S: Surface>(&self, context: &Context, target: &mut S) -> Result<(), DrawError> {
+        // Drop the translation of the view matrix, keeping only the rotation.
+        let mut view = context.camera.view;
+        view.fixed_slice_mut::<na::U3, na::U1>(0, 3).fill(0.0);
+
+        // A degenerate projection leaves nothing to unproject, so the sky is
+        // simply not drawn then.
+        let inverse_projection_view =
+            match (context.camera.projection_matrix() * view).try_inverse() {
+                Some(inverse) => inverse,
+                None => return Ok(()),
+            };
+
+        // Normalized device coordinates of the depth buffer's cleared value.
+        let depth = 2.0 * context.clear_depth() - 1.0;
+
+        let texture_map = Sampler::new(&self.texture)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp);
+
+        let draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: context.depth_test(glium::DepthTest::IfLessOrEqual),
+                write: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        target.draw(
+            &self.screen_quad.vertex_buffer,
+            &self.screen_quad.index_buffer,
+            &self.program,
+            &uniform! {
+                skybox_inverse_projection_view: inverse_projection_view.to_uniform(),
+                skybox_depth: depth,
+                skybox_texture: texture_map,
+            },
+            &draw_params,
+        )?;
+
+        Ok(())
+    }
+}
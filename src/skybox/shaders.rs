@@ -0,0 +1,36 @@
+use glium::uniforms::UniformType;
+
+use crate::{screen_quad, shader};
+
+const V_DIRECTION: (&str, shader::VertexOutDef) = (
+    "v_direction",
+    shader::VertexOutDef(shader::Type::FloatVec3, shader::VertexOutQualifier::Smooth),
+);
+
+/// Shader core for drawing the skybox onto a screen quad at the far plane.
+///
+/// The view direction of each fragment is reconstructed by unprojecting the
+/// quad with `skybox_inverse_projection_view`, which only contains the
+/// camera's rotation, so that the sky stays in place as the camera moves.
+pub fn skybox_core() -> shader::Core<(), (), screen_quad::Vertex> {
+    let vertex = shader::VertexCore::empty()
+        .with_extra_uniform("skybox_inverse_projection_view", UniformType::FloatMat4)
+        .with_extra_uniform("skybox_depth", UniformType::Float)
+        .with_body(
+            "
+            vec4 unprojected = skybox_inverse_projection_view * vec4(position.xy, 0.0, 1.0);
+            ",
+        )
+        .with_out(V_DIRECTION, "unprojected.xyz / unprojected.w")
+        .with_out(shader::defs::V_POS, "vec4(position.xy, skybox_depth, 1.0)");
+
+    let fragment = shader::FragmentCore::empty()
+        .with_extra_uniform("skybox_texture", UniformType::SamplerCube)
+        .with_in_def(V_DIRECTION)
+        .with_out(
+            shader::defs::F_COLOR,
+            "texture(skybox_texture, normalize(v_direction))",
+        );
+
+    shader::Core { vertex, fragment }
+}
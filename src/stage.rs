@@ -1,6 +1,10 @@
 use nalgebra as na;
 
-use crate::Camera;
+use glium::implement_uniform_block;
+use glium::uniforms::UniformType;
+
+use crate::shader::ToUniform;
+use crate::{basic_obj, Camera, Projection};
 
 #[derive(Debug, Clone)]
 pub struct Context {
@@ -8,21 +12,391 @@ pub struct Context {
     pub main_light_pos: na::Point3<f32>,
     pub main_light_center: na::Point3<f32>,
     pub ambient_light: na::Vector3<f32>,
+
+    /// Time in seconds, available to shaders as `context_elapsed_time`.
+    ///
+    /// This is not updated by the pipeline. Keeping it fixed freezes all
+    /// time-based shader effects, which is useful for reproducible frames.
+    pub elapsed_time: f32,
+
+    /// Use a logarithmic depth buffer in the scene passes, which greatly
+    /// improves depth precision at large distances, e.g. against z-fighting
+    /// of distant surfaces. This only applies to `Projection::Perspective`,
+    /// since orthographic depth is already linear.
+    ///
+    /// Depth is only adjusted per vertex, so large triangles that extend
+    /// behind the camera may be clipped incorrectly.
+    pub log_depth: bool,
+
+    /// Use reverse-Z depth in the scene passes, mapping the near plane to a
+    /// depth of 1.0 and the far plane to 0.0.
+    ///
+    /// This is only a switch of convention, e.g. for interoperating with
+    /// code that expects reversed depth. It does not improve precision:
+    /// that would need a `[0, 1]` clip space depth range via
+    /// `glClipControl`, which glium does not expose, so depth still goes
+    /// through OpenGL's `[-1, 1]` range and loses the precision of floats
+    /// close to zero. Use `log_depth` against z-fighting instead.
+    ///
+    /// With reverse-Z, the depth buffer needs to be cleared to
+    /// `clear_depth()`, i.e. 0.0, and depth tests need to be reversed, which
+    /// `depth_test` does. The pipeline handles this itself; only custom draw
+    /// parameters, e.g. in `PlainScenePassStep::draw`, need to go through
+    /// `depth_test`. This takes precedence over `log_depth`.
+    pub reverse_z: bool,
+
+    /// Fog that distant surfaces fade into, or `None` to disable it.
+    pub fog: Option<Fog>,
+
+    /// Render relative to the camera's eye, against jitter of vertices far
+    /// from the origin; see `relative_to_eye`.
+    ///
+    /// The pipeline then draws with `relative_to_eye` of this context, and
+    /// instances are moved accordingly when drawn with
+    /// `RenderList::as_drawable_in` or uploaded with
+    /// `Instancing::update_in`, given this context. This needs instances that
+    /// implement `HasTransformMut`.
+    ///
+    /// Everything else ignores this flag, so its instances need to be moved
+    /// by the caller with `HasTransformMut::in_context`:
+    /// - `RenderList::as_drawable` and `Instancing::update_if_dirty`, which
+    ///   would otherwise skip the upload while only the camera moves,
+    /// - the drawables of `LodSet`,
+    /// - `IdPass`,
+    /// - `RenderList::render_outline` and `Outline`,
+    /// - the `Light`s given to `ShadedScenePassStep::compose`.
+    pub camera_relative: bool,
+
+    /// Color that `clear_target` clears to before drawing, or `None` to not
+    /// clear at all.
+    ///
+    /// `Pipeline::start_frame` clears the scene to this color as well. Since
+    /// the pipeline always needs to clear its own buffers, it uses black for
+    /// `None`, and the alpha is ignored.
+    pub clear_color: Option<(f32, f32, f32, f32)>,
+}
+
+impl Default for Context {
+    /// A context with a perspective camera at the origin, for a viewport of
+    /// size one. The camera needs to be set up for the actual target.
+    fn default() -> Self {
+        Self {
+            camera: Camera::perspective(
+                na::Vector2::new(1.0, 1.0),
+                60.0f32.to_radians(),
+                0.1,
+                1000.0,
+            ),
+            main_light_pos: na::Point3::new(10.0, 10.0, 10.0),
+            main_light_center: na::Point3::origin(),
+            ambient_light: na::Vector3::new(0.3, 0.3, 0.3),
+            elapsed_time: 0.0,
+            log_depth: false,
+            reverse_z: false,
+            fog: None,
+            camera_relative: false,
+            clear_color: Some((0.0, 0.0, 0.0, 1.0)),
+        }
+    }
 }
 
 impl_uniform_input!(
     Context,
     self => {
         context_camera_viewport_size: [f32; 2] = self.camera.viewport_size,
-        context_camera_projection: [[f32; 4]; 4] = self.camera.projection,
+        context_camera_projection: [[f32; 4]; 4] = self.projection_matrix(),
         context_camera_view: [[f32; 4]; 4] = self.camera.view,
-        context_main_light_pos: [f32; 3] = self.main_light_pos.coords,
+        context_camera_eye: [f32; 3] = self.camera.eye().to_uniform(),
+        context_main_light_pos: [f32; 3] = self.main_light_pos.to_uniform(),
         context_ambient_light: [f32; 3] = self.ambient_light,
+        context_elapsed_time: f32 = self.elapsed_time,
+        context_log_depth_coef: f32 = self.log_depth_coef(),
+        context_fog_mode: i32 = self.fog.map_or(0, |fog| fog.mode.to_index()),
+        context_fog_color: [f32; 3] = self.fog.map_or(na::Vector3::zeros(), |fog| fog.color),
+        context_fog_density: f32 = self.fog.map_or(0.0, |fog| fog.density),
+        context_fog_range: [f32; 2] = self.fog.map_or([0.0, 0.0], |fog| fog.mode.range()),
     },
 );
 
+/// The uniforms of `Context` as a uniform block in `std140` layout, so that
+/// they can be uploaded once per frame into a `UniformBuffer` instead of
+/// being set for every draw call.
+///
+/// The pipeline does this for the programs of its scene passes, which read
+/// the block `context`. The fields are ordered such that no padding is needed
+/// between them.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ContextBlock {
+    pub context_camera_projection: [[f32; 4]; 4],
+    pub context_camera_view: [[f32; 4]; 4],
+    pub context_camera_eye: [f32; 3],
+    pub context_elapsed_time: f32,
+    pub context_main_light_pos: [f32; 3],
+    pub context_log_depth_coef: f32,
+    pub context_ambient_light: [f32; 3],
+    pub context_fog_mode: i32,
+    pub context_fog_color: [f32; 3],
+    pub context_fog_density: f32,
+    pub context_camera_viewport_size: [f32; 2],
+    pub context_fog_range: [f32; 2],
+}
+
+implement_uniform_block!(
+    ContextBlock,
+    context_camera_projection,
+    context_camera_view,
+    context_camera_eye,
+    context_elapsed_time,
+    context_main_light_pos,
+    context_log_depth_coef,
+    context_ambient_light,
+    context_fog_mode,
+    context_fog_color,
+    context_fog_density,
+    context_camera_viewport_size,
+    context_fog_range
+);
+
+impl ContextBlock {
+    /// Name of the block in shaders.
+    pub const NAME: &'static str = "context";
+
+    /// Members of the block in the order of their declaration, for
+    /// `shader::VertexCore::with_uniform_block`.
+    pub fn members() -> Vec<(String, UniformType)> {
+        [
+            ("context_camera_projection", UniformType::FloatMat4),
+            ("context_camera_view", UniformType::FloatMat4),
+            ("context_camera_eye", UniformType::FloatVec3),
+            ("context_elapsed_time", UniformType::Float),
+            ("context_main_light_pos", UniformType::FloatVec3),
+            ("context_log_depth_coef", UniformType::Float),
+            ("context_ambient_light", UniformType::FloatVec3),
+            ("context_fog_mode", UniformType::Int),
+            ("context_fog_color", UniformType::FloatVec3),
+            ("context_fog_density", UniformType::Float),
+            ("context_camera_viewport_size", UniformType::FloatVec2),
+            ("context_fog_range", UniformType::FloatVec2),
+        ]
+        .iter()
+        .map(|&(name, t)| (name.to_string(), t))
+        .collect()
+    }
+}
+
+impl Context {
+    /// The uniforms of this context as a `ContextBlock`.
+    pub fn to_block(&self) -> ContextBlock {
+        ContextBlock {
+            context_camera_projection: self.projection_matrix().into(),
+            context_camera_view: self.camera.view.into(),
+            context_camera_eye: self.camera.eye().to_uniform(),
+            context_elapsed_time: self.elapsed_time,
+            context_main_light_pos: self.main_light_pos.to_uniform(),
+            context_log_depth_coef: self.log_depth_coef(),
+            context_ambient_light: self.ambient_light.into(),
+            context_fog_mode: self.fog.map_or(0, |fog| fog.mode.to_index()),
+            context_fog_color: self
+                .fog
+                .map_or(na::Vector3::zeros(), |fog| fog.color)
+                .into(),
+            context_fog_density: self.fog.map_or(0.0, |fog| fog.density),
+            context_camera_viewport_size: self.camera.viewport_size.into(),
+            context_fog_range: self.fog.map_or([0.0, 0.0], |fog| fog.mode.range()),
+        }
+    }
+
+    /// Coefficient for logarithmic depth, or zero if it is disabled.
+    fn log_depth_coef(&self) -> f32 {
+        match self.camera.projection {
+            Projection::Perspective { far, .. } if self.log_depth && !self.reverse_z => {
+                2.0 / (far + 1.0).log2()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The camera's projection matrix, with depth reversed if `reverse_z`
+    /// is enabled.
+    pub fn projection_matrix(&self) -> na::Matrix4<f32> {
+        if self.reverse_z {
+            na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(1.0, 1.0, -1.0))
+                * self.camera.projection_matrix()
+        } else {
+            self.camera.projection_matrix()
+        }
+    }
+
+    /// The value that the depth buffer needs to be cleared to, which is 0.0
+    /// with `reverse_z` and 1.0 otherwise.
+    pub fn clear_depth(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Clears the color of `target` to `clear_color` and its depth to
+    /// `clear_depth()`, so that a frame can be drawn from scratch. Nothing is
+    /// cleared if `clear_color` is `None`.
+    pub fn clear_target<S: glium::Surface>(&self, target: &mut S) {
+        if let Some(clear_color) = self.clear_color {
+            target.clear_color_and_depth(clear_color, self.clear_depth());
+        }
+    }
+
+    /// Reverses the comparison of `test` if `reverse_z` is enabled, so that
+    /// nearer fragments still pass.
+    pub fn depth_test(&self, test: glium::DepthTest) -> glium::DepthTest {
+        use glium::DepthTest::*;
+
+        if !self.reverse_z {
+            return test;
+        }
+
+        match test {
+            IfLess => IfMore,
+            IfLessOrEqual => IfMoreOrEqual,
+            IfMore => IfLess,
+            IfMoreOrEqual => IfLessOrEqual,
+            test => test,
+        }
+    }
+
+    /// Camera-relative rendering, for large worlds in which positions far
+    /// from the origin cause vertices to jitter.
+    ///
+    /// This returns the same context in a world that has been moved so that
+    /// the camera's eye is at the origin, which keeps coordinates small on
+    /// the GPU. Instance transforms then need to be moved in the same way
+    /// on the CPU, with `Camera::transform_relative_to_eye` of the original
+    /// camera, and so do the positions of `Light`s. The pipeline does this
+    /// itself if `camera_relative` is enabled.
+    pub fn relative_to_eye(&self) -> Self {
+        let offset = self.camera.eye().coords;
+
+        Self {
+            camera: self.camera.relative_to_eye(),
+            main_light_pos: self.main_light_pos - offset,
+            main_light_center: self.main_light_center - offset,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_time(self, elapsed_time: f32) -> Self {
+        Self {
+            elapsed_time,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    /// Visibility falls off as `exp(-density * distance)`.
+    Exponential,
+
+    /// Visibility falls off linearly from full at `start` to none at `end`.
+    /// `Fog::density` is not used in this mode. If `end` is not greater than
+    /// `start`, surfaces are fully fogged beyond `end`.
+    Linear { start: f32, end: f32 },
+}
+
+impl FogMode {
+    /// Index of the mode in the `context_fog_mode` uniform. Zero means that
+    /// fog is disabled.
+    fn to_index(self) -> i32 {
+        match self {
+            FogMode::Exponential => 1,
+            FogMode::Linear { .. } => 2,
+        }
+    }
+
+    fn range(self) -> [f32; 2] {
+        match self {
+            FogMode::Exponential => [0.0, 0.0],
+            FogMode::Linear { start, end } => [start, end],
+        }
+    }
+}
+
+/// Depth cueing by blending surfaces into `color` by their distance from the
+/// camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    /// Color of the fog, in linear space.
+    pub color: na::Vector3<f32>,
+    pub density: f32,
+    pub mode: FogMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// A light that shines in all directions from `Light::position`, fading
+    /// with `Light::attenuation`.
+    Point,
+
+    /// A light with parallel rays and no attenuation, such as the sun.
+    /// `direction` is the direction in which the light travels.
+    Directional { direction: na::Vector3<f32> },
+
+    /// A point light that only shines into a cone around `direction`.
+    ///
+    /// Full intensity is reached within `inner_angle`, fading out smoothly
+    /// until `outer_angle`. Both angles are in radians, measured from
+    /// `direction`.
+    Spot {
+        direction: na::Vector3<f32>,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl LightKind {
+    pub fn is_directional(&self) -> bool {
+        matches!(self, LightKind::Directional { .. })
+    }
+
+    /// Index of the kind, as given to shaders in `light_kind`.
+    fn index(&self) -> i32 {
+        match self {
+            LightKind::Point => 0,
+            LightKind::Directional { .. } => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+
+    fn direction(&self) -> na::Vector3<f32> {
+        match self {
+            LightKind::Point => na::Vector3::zeros(),
+            LightKind::Directional { direction } => *direction,
+            LightKind::Spot { direction, .. } => *direction,
+        }
+    }
+
+    fn spot_angles(&self) -> [f32; 2] {
+        match self {
+            LightKind::Spot {
+                inner_angle,
+                outer_angle,
+                ..
+            } => [*inner_angle, *outer_angle],
+            _ => [0.0, 0.0],
+        }
+    }
+}
+
+impl Default for LightKind {
+    fn default() -> Self {
+        LightKind::Point
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Light {
+    pub kind: LightKind,
     pub position: na::Point3<f32>,
     pub attenuation: na::Vector3<f32>,
     pub color: na::Vector3<f32>,
@@ -33,7 +407,10 @@ pub struct Light {
 impl_instance_input!(
     Light,
     self => {
-        light_position: [f32; 3] = self.position.coords,
+        light_kind: i32 = self.kind.index(),
+        light_direction: [f32; 3] = self.kind.direction(),
+        light_spot_angles: [f32; 2] = self.kind.spot_angles(),
+        light_position: [f32; 3] = self.position.to_uniform(),
         light_attenuation: [f32; 3] = self.attenuation,
         light_color: [f32; 3] = self.color,
         //light_is_main: Bool = self.is_main,
@@ -44,6 +421,7 @@ impl_instance_input!(
 impl Default for Light {
     fn default() -> Self {
         Self {
+            kind: LightKind::Point,
             position: na::Point3::origin(),
             attenuation: na::Vector3::zeros(),
             color: na::Vector3::zeros(),
@@ -52,3 +430,23 @@ impl Default for Light {
         }
     }
 }
+
+impl Light {
+    /// A sphere of `radius` around the light, in its color, for a cheap
+    /// glow around lamps.
+    ///
+    /// Add the instance to the `BasicObj::Sphere` list of a render list that
+    /// is drawn with `BlendMode::Additive`, e.g. with
+    /// `PlainScenePassStep::draw_blended`, so that overlapping volumes
+    /// accumulate brightness. `intensity` is the alpha of the instance, which
+    /// weights the color when blending.
+    pub fn render_volume(&self, intensity: f32) -> basic_obj::Instance {
+        basic_obj::Instance {
+            // The sphere mesh has a radius of 0.5
+            transform: na::Matrix4::new_translation(&self.position.coords)
+                * na::Matrix4::new_scaling(2.0 * self.radius),
+            color: na::Vector4::new(self.color.x, self.color.y, self.color.z, intensity),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Text labels at positions in the world, e.g. for debugging.
+//!
+//! Each character is drawn as a `billboard` instance that selects its glyph
+//! from the atlas of a `Font`, so labels always face the camera and all of
+//! them can be drawn in a single instanced call. The built-in font is a
+//! monospace 5x7 pixel font covering printable ASCII.
+
+use nalgebra as na;
+
+use glium::texture::{RawImage2d, SrgbTexture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+use crate::basic_obj::billboard;
+use crate::{CreationError, InstanceId, RenderList, SamplerConfig};
+
+/// First character in the font.
+const FIRST_CHAR: u8 = b' ';
+
+/// Number of characters in the font, from `FIRST_CHAR` to `~`.
+const NUM_CHARS: usize = 95;
+
+/// Glyph columns from left to right, with the top row in the lowest bit.
+#[rustfmt::skip]
+const GLYPHS: [[u8; 5]; NUM_CHARS] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], // ' ' '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14], // '"' '#'
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], // '$' '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], // '&' '''
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], // '(' ')'
+    [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08], // '*' '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], // ',' '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02], // '.' '/'
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], // '0' '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31], // '2' '3'
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], // '4' '5'
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03], // '6' '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], // '8' '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00], // ':' ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], // '<' '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], // '>' '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], // '@' 'A'
+    [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22], // 'B' 'C'
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], // 'D' 'E'
+    [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a], // 'F' 'G'
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], // 'H' 'I'
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41], // 'J' 'K'
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f], // 'L' 'M'
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e], // 'N' 'O'
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], // 'P' 'Q'
+    [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31], // 'R' 'S'
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], // 'T' 'U'
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f], // 'V' 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], // 'X' 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00], // 'Z' '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], // '\' ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40], // '^' '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], // '`' 'a'
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], // 'b' 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], // 'd' 'e'
+    [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e], // 'f' 'g'
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], // 'h' 'i'
+    [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00], // 'j' 'k'
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], // 'l' 'm'
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], // 'n' 'o'
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], // 'p' 'q'
+    [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20], // 'r' 's'
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], // 't' 'u'
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c], // 'v' 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], // 'x' 'y'
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], // 'z' '{'
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], // '|' '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08],                                 // '~'
+];
+
+/// Size of a glyph in the atlas, including one texel of spacing to the right
+/// and at the top.
+const CELL_WIDTH: u32 = 6;
+const CELL_HEIGHT: u32 = 8;
+
+/// Number of glyphs per row of the atlas.
+const CELLS_PER_ROW: u32 = 16;
+
+const NUM_ROWS: u32 = (NUM_CHARS as u32 + CELLS_PER_ROW - 1) / CELLS_PER_ROW;
+
+/// Width of a glyph relative to its height.
+const GLYPH_ASPECT: f32 = CELL_WIDTH as f32 / CELL_HEIGHT as f32;
+
+/// The built-in monospace font as a texture atlas for `billboard::Core`.
+pub struct Font {
+    atlas: SrgbTexture2d,
+}
+
+impl Font {
+    pub fn create<F: glium::backend::Facade>(facade: &F) -> Result<Self, CreationError> {
+        let width = CELLS_PER_ROW * CELL_WIDTH;
+        let height = NUM_ROWS * CELL_HEIGHT;
+        let mut data = vec![0u8; (width * height * 4) as usize];
+
+        for (index, glyph) in GLYPHS.iter().enumerate() {
+            let (cell_x, cell_y) = glyph_cell(index);
+
+            for (column, bits) in glyph.iter().enumerate() {
+                for row in 0..7 {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+
+                    // Rows are stored from the bottom up, and the top row of
+                    // each cell is left empty for spacing.
+                    let x = cell_x * CELL_WIDTH + column as u32;
+                    let y = cell_y * CELL_HEIGHT + CELL_HEIGHT - 2 - row;
+                    let offset = ((y * width + x) * 4) as usize;
+
+                    data[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+
+        let image = RawImage2d::from_raw_rgba(data, (width, height));
+
+        Ok(Self {
+            atlas: SrgbTexture2d::new(facade, image)?,
+        })
+    }
+
+    pub fn atlas(&self) -> &SrgbTexture2d {
+        &self.atlas
+    }
+
+    /// Parameters for drawing labels with `billboard::Core`, sampling the
+    /// atlas without filtering so that glyphs stay sharp.
+    pub fn params(&self) -> billboard::Params {
+        billboard::Params {
+            atlas: &self.atlas,
+            sampler: SamplerConfig {
+                magnify_filter: MagnifySamplerFilter::Nearest,
+                minify_filter: MinifySamplerFilter::Nearest,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Region of the atlas that holds the glyph of `c`, in the form of
+    /// `billboard::Instance::atlas_region`. Characters that are not in the
+    /// font are shown as `?`.
+    pub fn glyph_region(c: char) -> na::Vector4<f32> {
+        let index = if c.is_ascii() && (c as u8) >= FIRST_CHAR && (c as u8) <= b'~' {
+            (c as u8 - FIRST_CHAR) as usize
+        } else {
+            (b'?' - FIRST_CHAR) as usize
+        };
+        let (cell_x, cell_y) = glyph_cell(index);
+
+        na::Vector4::new(
+            cell_x as f32 / CELLS_PER_ROW as f32,
+            cell_y as f32 / NUM_ROWS as f32,
+            1.0 / CELLS_PER_ROW as f32,
+            1.0 / NUM_ROWS as f32,
+        )
+    }
+}
+
+/// Cell of the glyph with the given index in the atlas, counted from the
+/// lower left.
+fn glyph_cell(index: usize) -> (u32, u32) {
+    let index = index as u32;
+
+    (index % CELLS_PER_ROW, NUM_ROWS - 1 - index / CELLS_PER_ROW)
+}
+
+impl RenderList<billboard::Instance> {
+    /// Adds a text label that is centered at `position` and faces the
+    /// camera, returning the ids of the instances of its characters.
+    ///
+    /// `scale` is the height of a line in world space. Lines are separated
+    /// by `\n`. Draw the list with `billboard::Core` and `Font::params`, e.g.
+    /// with `PlainScenePassStep::draw_transparent`, since the space around
+    /// glyphs is transparent.
+    pub fn add_label(
+        &mut self,
+        position: &na::Point3<f32>,
+        text: &str,
+        color: &na::Vector4<f32>,
+        scale: f32,
+    ) -> Vec<InstanceId> {
+        let advance = GLYPH_ASPECT * scale;
+        let lines = text.lines().collect::<Vec<_>>();
+        let mut ids = Vec::new();
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let num_chars = line.chars().count();
+            let y = ((lines.len() as f32 - 1.0) / 2.0 - line_index as f32) * scale;
+
+            for (char_index, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+
+                let x = (char_index as f32 - (num_chars as f32 - 1.0) / 2.0) * advance;
+
+                ids.push(self.add(billboard::Instance {
+                    center: *position,
+                    size: na::Vector2::new(advance, scale),
+                    offset: na::Vector2::new(x, y),
+                    color: *color,
+                    atlas_region: Font::glyph_region(c),
+                }));
+            }
+        }
+
+        ids
+    }
+}
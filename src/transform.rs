@@ -0,0 +1,95 @@
+use nalgebra as na;
+
+/// A transformation given by translation, rotation and scale, which are
+/// applied in the order scale, rotation, translation.
+///
+/// The matrix is cached and only recomputed when one of the components is
+/// changed through the setters. Instances that take a matrix, e.g.
+/// `basic_obj::Instance`, can be given `*transform.matrix()` or
+/// `transform.into()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    translation: na::Vector3<f32>,
+    rotation: na::UnitQuaternion<f32>,
+    scale: na::Vector3<f32>,
+    matrix: na::Matrix4<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: na::Vector3::zeros(),
+            rotation: na::UnitQuaternion::identity(),
+            scale: na::Vector3::new(1.0, 1.0, 1.0),
+            matrix: na::Matrix4::identity(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new(
+        translation: na::Vector3<f32>,
+        rotation: na::UnitQuaternion<f32>,
+        scale: na::Vector3<f32>,
+    ) -> Self {
+        let mut transform = Self {
+            translation,
+            rotation,
+            scale,
+            matrix: na::Matrix4::identity(),
+        };
+        transform.update_matrix();
+
+        transform
+    }
+
+    pub fn translation(&self) -> &na::Vector3<f32> {
+        &self.translation
+    }
+
+    pub fn rotation(&self) -> &na::UnitQuaternion<f32> {
+        &self.rotation
+    }
+
+    pub fn scale(&self) -> &na::Vector3<f32> {
+        &self.scale
+    }
+
+    /// The combined transformation matrix.
+    pub fn matrix(&self) -> &na::Matrix4<f32> {
+        &self.matrix
+    }
+
+    pub fn set_translation(&mut self, translation: na::Vector3<f32>) {
+        self.translation = translation;
+        self.update_matrix();
+    }
+
+    pub fn set_rotation(&mut self, rotation: na::UnitQuaternion<f32>) {
+        self.rotation = rotation;
+        self.update_matrix();
+    }
+
+    pub fn set_scale(&mut self, scale: na::Vector3<f32>) {
+        self.scale = scale;
+        self.update_matrix();
+    }
+
+    fn update_matrix(&mut self) {
+        self.matrix = na::Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * na::Matrix4::new_nonuniform_scaling(&self.scale);
+    }
+}
+
+impl From<Transform> for na::Matrix4<f32> {
+    fn from(transform: Transform) -> Self {
+        transform.matrix
+    }
+}
+
+impl<'a> From<&'a Transform> for na::Matrix4<f32> {
+    fn from(transform: &'a Transform) -> Self {
+        transform.matrix
+    }
+}